@@ -1,10 +1,7 @@
-use crate::{error::Error, platform::imp, window::Builder};
+use crate::{error::Error, monitor::{Monitor, MonitorId}, platform::imp, window::{Builder, WindowId}};
 use crate::util::sync::Mutex;
 use std::sync::Arc;
 
-#[cfg(unix)]
-use crate::util::sync;
-
 /// A connection to a windowing backend, used as a factory object to create [`Window`](crate::window::Window)s and
 /// [`Builder`](Builder)s.
 /// 
@@ -15,12 +12,41 @@ use crate::util::sync;
 #[derive(Clone)]
 pub struct Connection(pub(crate) Arc<Mutex<imp::Connection>>);
 
+/// A windowing system which a [`Connection`] can be made to.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum Backend {
+    Win32,
+    X11,
+}
+
 impl Connection {
     /// Attempts to connect to the backend for the target platform.
+    /// 
+    /// On X11, the system libraries are loaded at runtime. If the `RAMEN_LIBXCB_PATH` environment variable is set,
+    /// libxcb is loaded from that path and nowhere else, for systems where it isn't installed as `libxcb.so.1`. It's
+    /// loaded before Xlib, so that Xlib and the other XCB libraries use it too, which needs it to have the soname
+    /// `libxcb.so.1` as usual: otherwise they look for their own copy under that name.
+    /// 
+    /// If a library can't be loaded, the error is [`Error::LibraryLoad`](Error::LibraryLoad). The exception is
+    /// libxcb-xinput, which isn't needed on servers without XInput2 anyway: without it, input comes from the core
+    /// protocol, which can't tell input devices apart.
     pub fn new() -> Result<Self, Error> {
         imp::Connection::new().map(|x| Self(Arc::new(Mutex::new(x))))
     }
 
+    /// Attempts to connect to the X server in a way that leaves Xlib's event queue alone. This function is only
+    /// available on X11.
+    /// 
+    /// Normally, ramen takes over the event queue of the Xlib `Display` returned by [`xdisplay`](Self::xdisplay),
+    /// which breaks libraries that expect to read events from it with Xlib, such as some GL loaders. With this,
+    /// ramen uses an XCB connection of its own instead, and Xlib's queue keeps working as normal. Window IDs are
+    /// shared by the whole server, so ramen's windows can still be used with the `Display`.
+    #[cfg(unix)]
+    pub fn new_xlib_compatible() -> Result<Self, Error> {
+        imp::Connection::new_xlib_compatible().map(|x| Self(Arc::new(Mutex::new(x))))
+    }
+
     /// Creates a new [`Builder`](Builder) for interactively building a [`Window`](crate::window::Window).
     /// 
     /// The Connection object does not need to be kept after creating a Window with it, unless you intend to use it
@@ -38,15 +64,208 @@ impl Connection {
         Builder::new(self, None)
     }
 
+    /// Lists the monitors that make up the user's desktop.
+    /// 
+    /// On X11, this needs the RandR extension. If it isn't available, the whole X screen is listed as one monitor.
+    pub fn monitors(&self) -> Vec<Monitor> {
+        let g = imp::lock_connection(&self.0);
+        g.monitors()
+    }
+
+    /// Returns the name of a monitor, or `None` if it isn't connected anymore or has no name.
+    /// 
+    /// On X11, this is the RandR output name, such as `"HDMI-1"`, which is the same name tools like `xrandr` use.
+    /// Without RandR, the whole screen is listed as one monitor, and it has no name. On Windows, this is the display
+    /// device name, such as `"\\.\DISPLAY1"`. Names aren't part of [`Monitor`] itself so that it can stay `Copy`.
+    pub fn monitor_name(&self, monitor: MonitorId) -> Option<String> {
+        let g = imp::lock_connection(&self.0);
+        g.monitor_name(monitor)
+    }
+
+    /// Gets the clipboard contents in the format given by `mime`, such as `"image/png"`, if they're available in it.
+    /// 
+    /// For plain text, use `"text/plain;charset=utf-8"`. On X11 this waits up to a second for the clipboard's owner
+    /// to respond, which may be another application or this one. On Windows, MIME types are used as the names of
+    /// registered clipboard formats, except for the plain text and `"image/png"` types, which are mapped to the
+    /// standard formats other applications use for them.
+    pub fn clipboard_data(&self, mime: &str) -> Option<Vec<u8>> {
+        let mut g = imp::lock_connection(&self.0);
+        g.clipboard_data(mime)
+    }
+
+    /// Replaces the clipboard contents with `entries`, each one being the same data in a different format, given as a
+    /// MIME type and the data in that format. An empty list clears the clipboard.
+    /// 
+    /// On X11, the clipboard contents belong to this application, so other applications can only paste them while
+    /// it's running. They're sent to other applications while any of this Connection's windows are polling
+    /// events, so they can't be pasted in the meantime if none are.
+    pub fn set_clipboard_data(&self, entries: &[(String, Vec<u8>)]) {
+        let mut g = imp::lock_connection(&self.0);
+        g.set_clipboard_data(entries)
+    }
+
+    /// Reads any events which have arrived for this Connection's windows, storing each one for the window it's for.
+    /// 
+    /// Each window still needs its own [`poll_events`](crate::window::Window::poll_events) call to see its events,
+    /// but an application with many windows can call this once per frame first, so that those calls mostly just go
+    /// through what's already been stored. On Windows, events are stored for each window as they arrive, so this
+    /// does nothing.
+    pub fn poll_events(&self) {
+        let mut g = imp::lock_connection(&self.0);
+        g.poll_events()
+    }
+
+    /// Lists the windows built from this Connection which are still open, for matching against
+    /// [`Window::id`](crate::window::Window::id).
+    /// 
+    /// Only IDs are listed, since the windows themselves are owned elsewhere. Windows being built or dropped by
+    /// other threads at the same time may or may not be included.
+    pub fn windows(&self) -> Vec<WindowId> {
+        let g = imp::lock_connection(&self.0);
+        g.windows()
+    }
+
+    /// Returns which windowing system this Connection is connected to.
+    pub fn backend(&self) -> Backend {
+        #[cfg(unix)]
+        {
+            Backend::X11
+        }
+        #[cfg(windows)]
+        {
+            Backend::Win32
+        }
+    }
+
+    /// Returns the scale factor of the user's primary monitor, such as `2.0` on a display set to 200% scaling. This can
+    /// be used to size the first window before any exist to ask.
+    /// 
+    /// On X11 this comes from the `Xft.dpi` setting which desktop environments use for scaling, and is `1.0` if
+    /// that's not set. On Windows, DPI awareness is set just before this is read if it hasn't been already, so
+    /// `Builder::dpi_aware` and `Connection::set_dpi_aware` have no effect after calling this, and it's `1.0` if
+    /// the process isn't per-monitor DPI aware.
+    pub fn default_scale_factor(&self) -> f64 {
+        let g = imp::lock_connection(&self.0);
+        g.default_scale_factor()
+    }
+
+    /// Returns the name of the display server's vendor, such as `"The X.Org Foundation"`, which is mostly useful for
+    /// bug reports.
+    /// 
+    /// On X11 this comes from the server's reply when connecting, and isn't necessarily accurate. On Windows there's
+    /// no separate server, so this is always `None`.
+    pub fn server_vendor(&self) -> Option<String> {
+        let g = imp::lock_connection(&self.0);
+        g.server_vendor()
+    }
+
+    /// Returns the major and minor version of the protocol spoken to the display server, such as `(11, 0)` for X11.
+    /// 
+    /// On Windows there's no protocol, so this is always `None`.
+    pub fn protocol_version(&self) -> Option<(u16, u16)> {
+        let g = imp::lock_connection(&self.0);
+        g.protocol_version()
+    }
+
+    /// Checks whether a compositor is running, which is needed for windows to be transparent.
+    /// 
+    /// On X11, this checks whether any client owns the compositing manager selection for this screen. On Windows,
+    /// the Desktop Window Manager composites the desktop, which is always enabled from Windows 8 onwards.
+    pub fn is_compositor_running(&self) -> bool {
+        let g = imp::lock_connection(&self.0);
+        g.is_compositor_running()
+    }
+
+    /// Returns the name of the running Window Manager, such as `"KWin"` or `"i3"`. This function is only available
+    /// on X11.
+    /// 
+    /// This returns `None` if there's no Window Manager, or if it doesn't follow the EWMH spec.
+    #[cfg(unix)]
+    pub fn window_manager_name(&self) -> Option<String> {
+        let g = imp::lock_connection(&self.0);
+        g.window_manager_name()
+    }
+
+    /// Checks whether the Window Manager claims to support an EWMH feature. This function is only available on X11.
+    /// 
+    /// Window Managers which don't follow the EWMH spec will not support anything.
+    #[cfg(unix)]
+    pub fn supports(&self, feature: crate::platform::linux::NetWmFeature) -> bool {
+        let g = imp::lock_connection(&self.0);
+        g.supports(feature)
+    }
+
+    /// Returns how many X screens the display has. This function is only available on X11.
+    /// 
+    /// Almost every display has one screen, but some multi-head setups have one for each monitor, such as `:0.0` and
+    /// `:0.1`. Windows can't move between screens, so the one to use has to be chosen with
+    /// [`Builder::screen`](crate::window::Builder::screen).
+    #[cfg(unix)]
+    pub fn screen_count(&self) -> i32 {
+        let g = imp::lock_connection(&self.0);
+        g.screen_count()
+    }
+
     #[cfg(unix)]
     pub fn xscreenid(&self) -> u32 {
-        let g = sync::mutex_lock(&*self.0);
+        let g = imp::lock_connection(&self.0);
         g.xscreenid()
     }
 
     #[cfg(unix)]
     pub fn xdisplay(&self) -> *mut crate::platform::linux::Display {
-        let g = sync::mutex_lock(&*self.0);
+        let g = imp::lock_connection(&self.0);
         g.xdisplay()
     }
+
+    /// Returns the file descriptor of the socket connected to the X server, for waiting on it in another event loop.
+    /// This function is only available on X11.
+    /// 
+    /// The descriptor becomes readable when the server sends something, after which `poll_events()` should be
+    /// called. It only needs to be polled for reading, and shouldn't be read from or closed. Since events are shared
+    /// between every window on this Connection, an event for one window may be picked up while polling another, and
+    /// then waits in a queue without making the descriptor readable again, so every window should be polled whenever
+    /// it becomes readable.
+    #[cfg(unix)]
+    pub fn fd(&self) -> std::os::unix::io::RawFd {
+        let g = imp::lock_connection(&self.0);
+        g.fd()
+    }
+
+    /// Sets the name of the application, which identifies it to the desktop environment. This function is only
+    /// available on X11.
+    /// 
+    /// This is used as the instance name in the `WM_CLASS` of every window built with this Connection from now on,
+    /// in place of the program's file name, and desktop environments use it to group windows and notifications.
+    /// It should usually be the name of the application's `.desktop` file. It isn't used if the user has set
+    /// `RESOURCE_NAME` in the environment.
+    #[cfg(unix)]
+    pub fn set_application_name(&self, name: &str) {
+        let mut g = imp::lock_connection(&self.0);
+        g.set_application_name(name)
+    }
+
+    /// Sets the client ID given to this application by the X session manager, so that its windows can be saved and
+    /// restored with the session. This function is only available on X11.
+    /// 
+    /// The ID is written to the client leader window shared by every window made with this Connection.
+    /// Registering with the session manager to get an ID is left up to the application.
+    #[cfg(unix)]
+    pub fn set_sm_client_id(&self, id: &str) {
+        let mut g = imp::lock_connection(&self.0);
+        g.set_sm_client_id(id)
+    }
+
+    /// Sets whether the process should declare itself as per-monitor DPI aware. This function is only available on
+    /// Windows.
+    /// 
+    /// This is the same as [`Builder::dpi_aware`](crate::window::Builder::dpi_aware), for deciding before anything
+    /// has been built, such as to read [`default_scale_factor`](Self::default_scale_factor) with DPI awareness
+    /// turned off. It only has an effect if called before the first window is created and before the scale factor
+    /// is first read.
+    #[cfg(windows)]
+    pub fn set_dpi_aware(&self, dpi_aware: bool) {
+        let mut g = imp::lock_connection(&self.0);
+        g.set_dpi_aware(dpi_aware)
+    }
 }
@@ -2,9 +2,23 @@ use std::borrow::Cow;
 
 #[derive(Debug)]
 pub enum Error {
+    /// The display server was reached, but it refused the connection, most likely due to missing or wrong
+    /// authorisation (for example, an X11 session forwarded over SSH without the right cookie)
+    AuthFailed,
+
+    /// The display server could not be reached at the address it was configured with
+    ConnectionRefused,
+
     /// The request can't be completed because of a user error such as an invalid parameter or program state
     Invalid,
 
+    /// A system library needed by the backend couldn't be loaded, such as libxcb on X11. The string is the reason
+    /// given by the dynamic loader, such as `"libxcb.so.1: cannot open shared object file: No such file or directory"`
+    LibraryLoad(String),
+
+    /// No display server has been configured for this process, such as when `$DISPLAY` is unset on X11
+    NoDisplay,
+
     /// The program has run out of stack or a heap allocation has failed
     OutOfMemory,
 
@@ -14,6 +28,13 @@ pub enum Error {
     /// A human-readable error string was returned by the operating system
     Text(Cow<'static, str>),
 
+    /// The X server rejected a request with a protocol error, other than for being out of resources. This is only
+    /// returned on X11, where it's reported instead of `Invalid` so the cause can be looked up.
+    /// 
+    /// `major` and `minor` are the opcodes of the request that failed (`minor` is only meaningful for extension
+    /// requests), and `code` is the error code, such as 8 for `BadMatch` or 2 for `BadValue`.
+    X11 { major: u8, minor: u16, code: u8 },
+
     /// The reason for failure can't be determined because none was reported by the backend
     Unknown,
 
@@ -1,7 +1,14 @@
 #[cfg(feature = "input")]
-use crate::input::{Key, MouseButton};
+use crate::input::{DeviceId, Key, KeyModifiers, MouseButton};
+use crate::monitor::{Monitor, MonitorId};
 
-#[derive(Copy, Clone, Debug, Hash, PartialEq)]
+use std::{fmt, hash::{Hash, Hasher}, mem};
+
+/// Something which happened to a window, as listed by [`Window::events`](crate::window::Window::events).
+/// 
+/// Events and everything they carry are `Copy`, so they can be stored past the next `poll_events()` call by simply
+/// copying them out of the buffer.
+#[derive(Copy, Clone, Debug, PartialEq)]
 #[non_exhaustive]
 pub enum Event {
     /// A request has been made to close the window, most likely by clicking the 'x' button or by pressing alt+F4.
@@ -9,8 +16,11 @@ pub enum Event {
     /// This can be ignored if desired - the window will not actually close until it is dropped.
     CloseRequest,
 
-    /// The window was focused (`true`) or un-focused (`false`).
-    Focus(bool),
+    /// The window was focused (`focused` is `true`) or un-focused (`false`), for the given reason.
+    Focus { focused: bool, reason: FocusReason },
+
+    /// The window entered (`true`) or left (`false`) fullscreen mode.
+    Fullscreen(bool),
 
     /// The window was maximised (`true`) or un-maximised (`false`).
     Maximise(bool),
@@ -18,24 +28,73 @@ pub enum Event {
     /// The window was minimised (`true`) or un-minimised (`false`).
     Minimise(bool),
 
+    /// A monitor was added to the user's desktop. This is also sent when an existing monitor's position or resolution
+    /// changes, with its new details.
+    MonitorConnected(Monitor),
+
+    /// A monitor was removed from the user's desktop. Any window which was on it will probably have been moved by the
+    /// operating system, and will receive a `Move` event if so.
+    MonitorDisconnected(MonitorId),
+
     /// The window was moved to a new position on the screen. The position relates to the top-left of the window's
     /// inner drawable area, excluding any borders or decorations, and is reported in pixels relative to the top-left
     /// of the user's desktop.
+    /// 
+    /// Moves and resizes are reported separately, so a window which was only moved doesn't receive a `Resize`, and
+    /// one which was only resized only receives a `Move` if its top-left moved too.
     Move((i16, i16)),
 
+    /// A frame presented to the window with the X Present extension has reached the screen. This is only sent on X11.
+    /// 
+    /// This confirms when a specific `PresentPixmap` request completed, which is how Mesa's OpenGL and Vulkan drivers
+    /// present, so it can be used to measure presentation latency. `serial` is the one given to `PresentPixmap`,
+    /// `ust` is the time the frame was shown, in microseconds on `CLOCK_MONOTONIC`, and `msc` is the vblank counter.
+    PresentComplete { serial: u32, ust: u64, msc: u64 },
+
+    /// The compositor is ready for a new frame to be drawn.
+    ///
+    /// This is only sent while frame pacing is enabled with
+    /// [`Window::set_frame_pacing`](crate::window::Window::set_frame_pacing), roughly once per display refresh.
+    /// The value is the estimated time the next frame will be presented at, in microseconds. It's measured on the
+    /// system's monotonic clock (`CLOCK_MONOTONIC` on Linux, `QueryPerformanceCounter` on Windows),
+    /// so it can only be compared against other readings from that clock.
+    RedrawRequested(u64),
+
     /// The window was resized. The width and height are reported in pixels.
+    /// 
+    /// This is also sent, along with a `Move`, in the first set of events after the window is created, so that its
+    /// real geometry is known even if the operating system didn't grant the requested size.
     Resize((u16, u16)),
 
+    /// The window's [`scale_factor`](crate::window::Window::scale_factor) has changed to this.
+    /// 
+    /// On Windows, this is sent when the window moves onto a monitor with a different scale, and the window has
+    /// already been resized to keep the same logical size. On X11, the scale is shared by every window, and this is
+    /// sent when the user changes the desktop's scaling setting.
+    ScaleFactorChanged(f64),
+
     /// The window's visibility has changed.
     Visible(bool),
 
-    /// A [`Key`] was pressed.
+    /// The time given with [`ControlFlow::WaitUntil`](crate::window::ControlFlow::WaitUntil) has been reached, or
+    /// [`ControlFlow::Poll`](crate::window::ControlFlow::Poll) was used and there were no new events.
+    /// 
+    /// This is only sent by [`Window::run`](crate::window::Window::run).
+    Wakeup,
+
+    /// A [`Key`] was pressed, along with the modifiers active at the time.
     /// 
     /// This event will tell you which physical key the user has pressed. If your aim is to process text input,
     /// consider using the [`Event::Input`] event type instead.
+    /// 
+    /// On X11 the modifiers are as of just before the event, so pressing Shift reports `shift: false`. On Windows
+    /// they include the key itself.
+    /// 
+    /// Keys which can't be represented as a [`Key`] aren't reported at all, though any text they type is still sent
+    /// as [`Event::Input`].
     #[cfg_attr(feature = "nightly-rustdoc", doc(cfg(feature = "input")))]
     #[cfg_attr(not(feature = "nightly-rustdoc"), cfg(feature = "input"))]
-    KeyboardDown(Key),
+    KeyboardDown(Key, KeyModifiers),
 
     /// A [`Key`] was auto-repeated by the system because the user is holding it down.
     /// 
@@ -43,14 +102,18 @@ pub enum Event {
     /// consider using the [`Event::Input`] event type instead.
     #[cfg_attr(feature = "nightly-rustdoc", doc(cfg(feature = "input")))]
     #[cfg_attr(not(feature = "nightly-rustdoc"), cfg(feature = "input"))]
-    KeyboardRepeat(Key),
+    KeyboardRepeat(Key, KeyModifiers),
 
-    /// A [`Key`] was released.
+    /// A [`Key`] was released, along with the modifiers active at the time.
     #[cfg_attr(feature = "nightly-rustdoc", doc(cfg(feature = "input")))]
     #[cfg_attr(not(feature = "nightly-rustdoc"), cfg(feature = "input"))]
-    KeyboardUp(Key),
+    KeyboardUp(Key, KeyModifiers),
 
-    /// A [`char`] was typed using the keyboard.
+    /// A [`char`] was typed using the keyboard. Holding a key down types it again each time it repeats.
+    /// 
+    /// Dead keys and Compose sequences are taken into account, so typing a dead acute and then `e` sends a single
+    /// `Input('é')`, and nothing for the dead key. A sequence which produces several characters sends one event for
+    /// each of them. On X11 the sequences come from the Compose file for the user's locale.
     #[cfg_attr(feature = "nightly-rustdoc", doc(cfg(feature = "input")))]
     #[cfg_attr(not(feature = "nightly-rustdoc"), cfg(feature = "input"))]
     Input(char),
@@ -65,23 +128,31 @@ pub enum Event {
     #[cfg_attr(not(feature = "nightly-rustdoc"), cfg(feature = "input"))]
     MouseUp(MouseButton),
 
-    /// The mouse was moved to a new position on the screen. The position is reported in pixels relative to the
-    /// top-left of the user's desktop.
-    #[cfg_attr(feature = "nightly-rustdoc", doc(cfg(feature = "input")))]
-    #[cfg_attr(not(feature = "nightly-rustdoc"), cfg(feature = "input"))]
-    MouseMove((u16, u16)),
-
-    /// The mouse wheel was scrolled up, away from the user.
+    /// The mouse was moved to a new position over the window. The position is reported in pixels relative to the
+    /// top-left of the window's inner drawable area.
+    /// 
+    /// The coordinates may be fractional on high-precision devices, such as with XInput2's 16.16 fixed-point
+    /// coordinates on X11. While a mouse button is held the window keeps receiving this event after the mouse leaves
+    /// it, so positions left of or above the window are negative.
     #[cfg_attr(feature = "nightly-rustdoc", doc(cfg(feature = "input")))]
     #[cfg_attr(not(feature = "nightly-rustdoc"), cfg(feature = "input"))]
-    ScrollUp,
+    MouseMove { x: f32, y: f32 },
 
-    /// The mouse wheel was scrolled down, towards the user.
+    /// The mouse wheel was scrolled, or a touchpad was scrolled with two fingers.
+    /// 
+    /// The distance is measured so that one notch of an ordinary mouse wheel is [`SCROLL_NOTCH`], and smooth
+    /// scrolling devices on Windows can report less than that. `dy` is positive when scrolling up, away from the
+    /// user, and `dx` is positive when scrolling right.
+    /// 
+    /// X11 reports the wheel as buttons 4 to 7, which are turned into one notch each here rather than being sent as
+    /// [`Event::MouseDown`].
     #[cfg_attr(feature = "nightly-rustdoc", doc(cfg(feature = "input")))]
     #[cfg_attr(not(feature = "nightly-rustdoc"), cfg(feature = "input"))]
-    ScrollDown,
+    Scroll { dx: i32, dy: i32 },
 
     /// The mouse has entered the client area of the window.
+    /// 
+    /// This is always followed by a [`Event::MouseMove`] with the position the mouse entered at.
     #[cfg_attr(feature = "nightly-rustdoc", doc(cfg(feature = "input")))]
     #[cfg_attr(not(feature = "nightly-rustdoc"), cfg(feature = "input"))]
     MouseEnter,
@@ -90,4 +161,107 @@ pub enum Event {
     #[cfg_attr(feature = "nightly-rustdoc", doc(cfg(feature = "input")))]
     #[cfg_attr(not(feature = "nightly-rustdoc"), cfg(feature = "input"))]
     MouseLeave,
+
+    /// An input device such as a keyboard or mouse was plugged in or enabled. This is only sent on X11.
+    /// 
+    /// Every window receives this event, since devices aren't tied to any one window.
+    #[cfg_attr(feature = "nightly-rustdoc", doc(cfg(feature = "input")))]
+    #[cfg_attr(not(feature = "nightly-rustdoc"), cfg(feature = "input"))]
+    DeviceAdded(DeviceId),
+
+    /// An input device was unplugged or disabled. This is only sent on X11.
+    /// 
+    /// Every window receives this event, since devices aren't tied to any one window.
+    #[cfg_attr(feature = "nightly-rustdoc", doc(cfg(feature = "input")))]
+    #[cfg_attr(not(feature = "nightly-rustdoc"), cfg(feature = "input"))]
+    DeviceRemoved(DeviceId),
+}
+
+// The scale factor and mouse positions are floats, so this can't be derived. They're never NaN, so hashing their bits
+// agrees with `PartialEq`, as long as zero is never negative, which it isn't.
+impl Hash for Event {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        mem::discriminant(self).hash(state);
+        match *self {
+            Self::CloseRequest | Self::Wakeup => (),
+            Self::Focus { focused, reason } => (focused, reason).hash(state),
+            Self::Fullscreen(x) | Self::Maximise(x) | Self::Minimise(x) | Self::Visible(x) => x.hash(state),
+            Self::MonitorConnected(monitor) => monitor.hash(state),
+            Self::MonitorDisconnected(id) => id.hash(state),
+            Self::Move(position) => position.hash(state),
+            Self::PresentComplete { serial, ust, msc } => (serial, ust, msc).hash(state),
+            Self::RedrawRequested(time) => time.hash(state),
+            Self::Resize(size) => size.hash(state),
+            Self::ScaleFactorChanged(scale) => scale.to_bits().hash(state),
+            #[cfg(feature = "input")]
+            Self::KeyboardDown(key, modifiers) | Self::KeyboardRepeat(key, modifiers) | Self::KeyboardUp(key, modifiers)
+                => (key, modifiers).hash(state),
+            #[cfg(feature = "input")]
+            Self::Input(c) => c.hash(state),
+            #[cfg(feature = "input")]
+            Self::MouseDown(button) | Self::MouseUp(button) => button.hash(state),
+            #[cfg(feature = "input")]
+            Self::MouseMove { x, y } => (x.to_bits(), y.to_bits()).hash(state),
+            #[cfg(feature = "input")]
+            Self::Scroll { dx, dy } => (dx, dy).hash(state),
+            #[cfg(feature = "input")]
+            Self::MouseEnter | Self::MouseLeave => (),
+            #[cfg(feature = "input")]
+            Self::DeviceAdded(id) | Self::DeviceRemoved(id) => id.hash(state),
+        }
+    }
+}
+
+/// Why a window gained or lost focus, as reported by [`Event::Focus`].
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum FocusReason {
+    /// An ordinary change, such as the user clicking on another window or alt-tabbing. This is always the reason on
+    /// Windows.
+    Normal,
+
+    /// The focus follows the mouse, because the X server has been told to focus whichever window is under it.
+    Pointer,
+
+    /// A keyboard grab started or ended, such as while the Window Manager's alt-tab switcher or a popup menu is open.
+    /// These usually come in pairs which end with the window focused again, so they can be ignored by applications
+    /// which only care about the user switching away.
+    Grab,
+}
+
+/// The distance of one notch of a mouse wheel, as used by [`Event::Scroll`]. This is the same as Windows'
+/// `WHEEL_DELTA`.
+#[cfg_attr(feature = "nightly-rustdoc", doc(cfg(feature = "input")))]
+#[cfg_attr(not(feature = "nightly-rustdoc"), cfg(feature = "input"))]
+pub const SCROLL_NOTCH: i32 = 120;
+
+/// A compact form for logging, which leaves out the extra brackets around coordinate pairs.
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Event::Focus { focused, reason } => write!(f, "Focus({}, {:?})", focused, reason),
+            Event::Move((x, y)) => write!(f, "Move({}, {})", x, y),
+            Event::RedrawRequested(time) => write!(f, "RedrawRequested({}us)", time),
+            Event::Resize((width, height)) => write!(f, "Resize({}x{})", width, height),
+            #[cfg(feature = "input")]
+            Event::KeyboardDown(key, modifiers) => write_key(f, "KeyboardDown", key, modifiers),
+            #[cfg(feature = "input")]
+            Event::KeyboardRepeat(key, modifiers) => write_key(f, "KeyboardRepeat", key, modifiers),
+            #[cfg(feature = "input")]
+            Event::KeyboardUp(key, modifiers) => write_key(f, "KeyboardUp", key, modifiers),
+            #[cfg(feature = "input")]
+            Event::MouseMove { x, y } => write!(f, "MouseMove({}, {})", x, y),
+            _ => fmt::Debug::fmt(self, f),
+        }
+    }
+}
+
+// Writes a keyboard event such as `KeyboardDown(A) [ctrl]`, leaving out the modifiers if there are none
+#[cfg(feature = "input")]
+fn write_key(f: &mut fmt::Formatter<'_>, name: &str, key: Key, modifiers: KeyModifiers) -> fmt::Result {
+    write!(f, "{}({})", name, key)?;
+    if modifiers != KeyModifiers::default() {
+        write!(f, " [{}]", modifiers)?;
+    }
+    Ok(())
 }
@@ -1,3 +1,5 @@
+use std::fmt;
+
 #[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
 pub enum Key {
     // common
@@ -120,4 +122,292 @@ pub enum MouseButton {
     Left,
     Middle,
     Right,
+    /// The first side button, which usually means "back".
+    X1,
+    /// The second side button, which usually means "forward".
+    X2,
+}
+
+/// The set of modifier keys held down on the keyboard, telling apart the left and right keys of each kind.
+///
+/// This is tracked from the physical key events received by a window, so it reflects which keys are held
+/// rather than what modifiers the system considers active after any remapping.
+#[derive(Copy, Clone, Default, Eq, Hash, PartialEq)]
+pub struct Modifiers {
+    bits: u8,
+}
+
+const MOD_LEFT_SHIFT: u8 = 1 << 0;
+const MOD_RIGHT_SHIFT: u8 = 1 << 1;
+const MOD_LEFT_CONTROL: u8 = 1 << 2;
+const MOD_RIGHT_CONTROL: u8 = 1 << 3;
+const MOD_LEFT_ALT: u8 = 1 << 4;
+const MOD_RIGHT_ALT: u8 = 1 << 5;
+const MOD_LEFT_SUPER: u8 = 1 << 6;
+const MOD_RIGHT_SUPER: u8 = 1 << 7;
+
+const MOD_KEYS: [(u8, Key); 8] = [
+    (MOD_LEFT_CONTROL, Key::LeftControl),
+    (MOD_RIGHT_CONTROL, Key::RightControl),
+    (MOD_LEFT_ALT, Key::LeftAlt),
+    (MOD_RIGHT_ALT, Key::RightAlt),
+    (MOD_LEFT_SHIFT, Key::LeftShift),
+    (MOD_RIGHT_SHIFT, Key::RightShift),
+    (MOD_LEFT_SUPER, Key::LeftSuper),
+    (MOD_RIGHT_SUPER, Key::RightSuper),
+];
+
+impl Modifiers {
+    /// Returns a set of modifiers with no keys held.
+    pub const fn new() -> Self {
+        Self { bits: 0 }
+    }
+
+    /// Whether either shift key is held.
+    pub const fn shift(&self) -> bool {
+        self.bits & (MOD_LEFT_SHIFT | MOD_RIGHT_SHIFT) != 0
+    }
+
+    /// Whether either control key is held.
+    pub const fn control(&self) -> bool {
+        self.bits & (MOD_LEFT_CONTROL | MOD_RIGHT_CONTROL) != 0
+    }
+
+    /// Whether either alt key is held.
+    pub const fn alt(&self) -> bool {
+        self.bits & (MOD_LEFT_ALT | MOD_RIGHT_ALT) != 0
+    }
+
+    /// Whether either super (Windows, Command) key is held.
+    pub const fn super_(&self) -> bool {
+        self.bits & (MOD_LEFT_SUPER | MOD_RIGHT_SUPER) != 0
+    }
+
+    pub const fn left_shift(&self) -> bool {
+        self.bits & MOD_LEFT_SHIFT != 0
+    }
+
+    pub const fn right_shift(&self) -> bool {
+        self.bits & MOD_RIGHT_SHIFT != 0
+    }
+
+    pub const fn left_control(&self) -> bool {
+        self.bits & MOD_LEFT_CONTROL != 0
+    }
+
+    pub const fn right_control(&self) -> bool {
+        self.bits & MOD_RIGHT_CONTROL != 0
+    }
+
+    pub const fn left_alt(&self) -> bool {
+        self.bits & MOD_LEFT_ALT != 0
+    }
+
+    pub const fn right_alt(&self) -> bool {
+        self.bits & MOD_RIGHT_ALT != 0
+    }
+
+    pub const fn left_super(&self) -> bool {
+        self.bits & MOD_LEFT_SUPER != 0
+    }
+
+    pub const fn right_super(&self) -> bool {
+        self.bits & MOD_RIGHT_SUPER != 0
+    }
+
+    /// Iterates over the modifier keys which are held.
+    pub(crate) fn held_keys(self) -> impl Iterator<Item = Key> {
+        MOD_KEYS.iter().filter(move |(bit, _)| self.bits & bit != 0).map(|(_, key)| *key)
+    }
+
+    /// Updates the held state from a key being pressed or released. Non-modifier keys are ignored.
+    pub(crate) fn update(&mut self, key: Key, pressed: bool) {
+        let bit = match MOD_KEYS.iter().find(|(_, k)| *k == key) {
+            Some((bit, _)) => *bit,
+            None => return,
+        };
+        if pressed {
+            self.bits |= bit;
+        } else {
+            self.bits &= !bit;
+        }
+    }
+}
+
+/// The set of mouse buttons held down.
+#[derive(Copy, Clone, Default, Eq, Hash, PartialEq)]
+pub struct MouseButtons {
+    bits: u8,
+}
+
+const MOUSE_BUTTONS: [(u8, MouseButton); 5] = [
+    (1 << 0, MouseButton::Left),
+    (1 << 1, MouseButton::Middle),
+    (1 << 2, MouseButton::Right),
+    (1 << 3, MouseButton::X1),
+    (1 << 4, MouseButton::X2),
+];
+
+impl MouseButtons {
+    /// Returns a set of mouse buttons with none held.
+    pub const fn new() -> Self {
+        Self { bits: 0 }
+    }
+
+    /// Whether `button` is held.
+    pub fn is_pressed(&self, button: MouseButton) -> bool {
+        MOUSE_BUTTONS.iter().any(|(bit, b)| *b == button && self.bits & bit != 0)
+    }
+
+    pub fn left(&self) -> bool {
+        self.is_pressed(MouseButton::Left)
+    }
+
+    pub fn middle(&self) -> bool {
+        self.is_pressed(MouseButton::Middle)
+    }
+
+    pub fn right(&self) -> bool {
+        self.is_pressed(MouseButton::Right)
+    }
+
+    pub fn x1(&self) -> bool {
+        self.is_pressed(MouseButton::X1)
+    }
+
+    pub fn x2(&self) -> bool {
+        self.is_pressed(MouseButton::X2)
+    }
+
+    /// Updates the held state from a button being pressed or released.
+    pub(crate) fn update(&mut self, button: MouseButton, pressed: bool) {
+        if let Some((bit, _)) = MOUSE_BUTTONS.iter().find(|(_, b)| *b == button) {
+            if pressed {
+                self.bits |= bit;
+            } else {
+                self.bits &= !bit;
+            }
+        }
+    }
+}
+
+/// The modifier mask exactly as the platform reported it, for decoding modifiers that [`Modifiers`] doesn't cover.
+///
+/// On X11 this is the XInput `mods.effective` mask, or the core `state` mask if the server doesn't have XInput2, so
+/// bits 0 to 7 are Shift, Lock, Control and Mod1 to Mod5.
+/// What those mean depends on the user's modifier mapping. On Windows, bits 0 to 7 are whether the left and right
+/// Shift, Control, Alt and Windows keys are held (in that order, left first), and bits 8 to 10 are whether Caps Lock,
+/// Num Lock and Scroll Lock are toggled on.
+#[derive(Copy, Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct RawModifiers(pub u32);
+
+/// The modifiers active at the time of a keyboard event, as the platform saw them.
+///
+/// Unlike [`Modifiers`], this doesn't tell apart left and right, but it does include the lock keys and follows the
+/// user's modifier mapping. On X11, Alt is `Mod1`, Num Lock is `Mod2` and Super is `Mod4`, which is how almost every
+/// keyboard layout maps them.
+#[derive(Copy, Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct KeyModifiers {
+    pub shift: bool,
+    pub control: bool,
+    pub alt: bool,
+    pub super_: bool,
+    /// Whether Caps Lock is toggled on.
+    pub caps_lock: bool,
+    /// Whether Num Lock is toggled on.
+    pub num_lock: bool,
+}
+
+/// An identifier for an input device, as reported by [`Event::DeviceAdded`](crate::event::Event::DeviceAdded) and
+/// [`Event::DeviceRemoved`](crate::event::Event::DeviceRemoved).
+///
+/// On X11 this is the XInput2 device ID. The X server may give the ID of a removed device to one added later. Servers
+/// without XInput2 don't report devices at all.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct DeviceId(pub(crate) u64);
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+impl fmt::Display for MouseButton {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// Lists the held keys, such as `Modifiers(LeftControl | RightShift)`.
+impl fmt::Debug for Modifiers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Modifiers(")?;
+        let mut first = true;
+        for (_, key) in MOD_KEYS.iter().filter(|(bit, _)| self.bits & bit != 0) {
+            if !first {
+                f.write_str(" | ")?;
+            }
+            write!(f, "{:?}", key)?;
+            first = false;
+        }
+        f.write_str(")")
+    }
+}
+
+/// Lists the held buttons, such as `MouseButtons(Left | Right)`.
+impl fmt::Debug for MouseButtons {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("MouseButtons(")?;
+        let mut first = true;
+        for (_, button) in MOUSE_BUTTONS.iter().filter(|(bit, _)| self.bits & bit != 0) {
+            if !first {
+                f.write_str(" | ")?;
+            }
+            write!(f, "{:?}", button)?;
+            first = false;
+        }
+        f.write_str(")")
+    }
+}
+
+/// Lists the held kinds of modifier without telling apart left and right, such as `ctrl+shift`, or `none`.
+impl fmt::Display for Modifiers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_held(f, &[
+            (self.control(), "ctrl"),
+            (self.alt(), "alt"),
+            (self.shift(), "shift"),
+            (self.super_(), "super"),
+        ])
+    }
+}
+
+/// Lists the held modifiers and toggled locks in the same way as [`Modifiers`], such as `ctrl+num_lock`, or `none`.
+impl fmt::Display for KeyModifiers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_held(f, &[
+            (self.control, "ctrl"),
+            (self.alt, "alt"),
+            (self.shift, "shift"),
+            (self.super_, "super"),
+            (self.caps_lock, "caps_lock"),
+            (self.num_lock, "num_lock"),
+        ])
+    }
+}
+
+// Writes the names which are held, joined with `+`, or `none`
+fn write_held(f: &mut fmt::Formatter<'_>, names: &[(bool, &str)]) -> fmt::Result {
+    let mut first = true;
+    for (_, name) in names.iter().filter(|(held, _)| *held) {
+        if !first {
+            f.write_str("+")?;
+        }
+        f.write_str(name)?;
+        first = false;
+    }
+    if first {
+        f.write_str("none")?;
+    }
+    Ok(())
 }
@@ -11,6 +11,7 @@ pub mod event;
 #[cfg_attr(feature = "nightly-rustdoc", doc(cfg(feature = "input")))]
 #[cfg_attr(not(feature = "nightly-rustdoc"), cfg(feature = "input"))]
 pub mod input;
+pub mod monitor;
 pub mod platform;
 pub mod window;
 
@@ -22,9 +23,86 @@ mod tests {
 
         test::<crate::error::Error>();
         test::<crate::event::Event>();
+        test::<crate::monitor::Monitor>();
         test::<crate::window::Builder>();
         test::<crate::window::Controls>();
         test::<crate::window::Style>();
         test::<crate::window::Window>();
     }
+
+    #[test]
+    fn events_are_copy() {
+        fn test<T: Copy + Clone + std::fmt::Debug + PartialEq>() {}
+
+        test::<crate::event::Event>();
+        test::<crate::event::FocusReason>();
+        test::<crate::monitor::Monitor>();
+        #[cfg(feature = "input")]
+        {
+            test::<crate::input::DeviceId>();
+            test::<crate::input::Key>();
+            test::<crate::input::KeyModifiers>();
+            test::<crate::input::Modifiers>();
+            test::<crate::input::MouseButton>();
+            test::<crate::input::MouseButtons>();
+        }
+    }
+
+    // Run with `cargo test -- --ignored` where there's a display to talk to, such as an X server under Xvfb
+    #[test]
+    #[ignore = "needs a display"]
+    fn clipboard_round_trip() {
+        let connection = crate::connection::Connection::new().expect("couldn't connect to the display");
+
+        // Big enough that X11 has to send it in chunks with INCR
+        let data: Vec<u8> = (0..4 << 20).map(|i| (i % 251) as u8).collect();
+        connection.set_clipboard_data(&[("application/x-ramen-test".into(), data.clone())]);
+        assert_eq!(connection.clipboard_data("application/x-ramen-test"), Some(data));
+    }
+
+    #[test]
+    fn event_display() {
+        use crate::event::{Event, FocusReason};
+
+        assert_eq!(Event::Resize((800, 600)).to_string(), "Resize(800x600)");
+        assert_eq!(Event::Move((-4, 20)).to_string(), "Move(-4, 20)");
+        assert_eq!(Event::Focus { focused: true, reason: FocusReason::Grab }.to_string(), "Focus(true, Grab)");
+        #[cfg(feature = "input")]
+        {
+            use crate::input::{Key, KeyModifiers};
+
+            assert_eq!(Event::MouseMove { x: -2.5, y: 10.0 }.to_string(), "MouseMove(-2.5, 10)");
+            let ctrl = KeyModifiers { control: true, ..KeyModifiers::default() };
+            assert_eq!(Event::KeyboardDown(Key::A, ctrl).to_string(), "KeyboardDown(A) [ctrl]");
+            let shift_caps = KeyModifiers { shift: true, caps_lock: true, ..KeyModifiers::default() };
+            assert_eq!(Event::KeyboardUp(Key::A, shift_caps).to_string(), "KeyboardUp(A) [shift+caps_lock]");
+            assert_eq!(Event::KeyboardRepeat(Key::A, KeyModifiers::default()).to_string(), "KeyboardRepeat(A)");
+        }
+    }
+
+    #[cfg(feature = "input")]
+    #[test]
+    fn modifiers_fmt() {
+        use crate::input::{Key, Modifiers};
+
+        let mut modifiers = Modifiers::new();
+        assert_eq!(modifiers.to_string(), "none");
+        modifiers.update(Key::RightShift, true);
+        modifiers.update(Key::LeftControl, true);
+        assert_eq!(modifiers.to_string(), "ctrl+shift");
+        assert_eq!(format!("{:?}", modifiers), "Modifiers(LeftControl | RightShift)");
+    }
+
+    #[cfg(feature = "input")]
+    #[test]
+    fn mouse_buttons_track_presses() {
+        use crate::input::{MouseButton, MouseButtons};
+
+        let mut buttons = MouseButtons::new();
+        buttons.update(MouseButton::Right, true);
+        buttons.update(MouseButton::Left, true);
+        buttons.update(MouseButton::Right, false);
+        assert!(buttons.left() && !buttons.right() && !buttons.middle());
+        assert_eq!(format!("{:?}", buttons), "MouseButtons(Left)");
+    }
 }
@@ -54,8 +54,8 @@ pub fn f(connection: Connection) {
                     println!("Closed.");
                     break 'program;
                 },
-                Event::Focus(b) => {
-                    println!("Window focus state: {}", b);
+                Event::Focus { focused, reason } => {
+                    println!("Window focus state: {} ({:?})", focused, reason);
                 },
                 Event::Maximise(v) => {
                     println!("!! Maximise: {}", v);
@@ -73,8 +73,8 @@ pub fn f(connection: Connection) {
                     println!("Window{} visible", if *t { "" } else { " not" });
                 },
                 #[cfg(feature = "input")]
-                Event::KeyboardDown(k) => {
-                    println!("Key down: {:?}", k);
+                Event::KeyboardDown(k, mods) => {
+                    println!("Key down: {:?} ({:?})", k, mods);
                     match k {
                         Key::T => window.set_title("This is a different title"),
                         Key::M => window.set_maximised(true),
@@ -96,11 +96,11 @@ pub fn f(connection: Connection) {
                     }
                 },
                 #[cfg(feature = "input")]
-                Event::KeyboardRepeat(k) => {
+                Event::KeyboardRepeat(k, _) => {
                     println!("Key repeat: {:?}", k);
                 },
                 #[cfg(feature = "input")]
-                Event::KeyboardUp(k) => {
+                Event::KeyboardUp(k, _) => {
                     println!("Key up: {:?}", k);
                 },
                 #[cfg(feature = "input")]
@@ -112,16 +112,12 @@ pub fn f(connection: Connection) {
                     println!("Mouse up: {:?}", k);
                 },
                 #[cfg(feature = "input")]
-                Event::MouseMove(t) => {
-                    println!("Mouse move: {:?}", t);
+                Event::MouseMove { x, y } => {
+                    println!("Mouse move: {}, {}", x, y);
                 },
                 #[cfg(feature = "input")]
-                Event::ScrollUp => {
-                    println!("Mouse scrolled up");
-                },
-                #[cfg(feature = "input")]
-                Event::ScrollDown => {
-                    println!("Mouse scrolled down");
+                Event::Scroll { dx, dy } => {
+                    println!("Mouse scrolled: {}, {}", dx, dy);
                 },
                 #[cfg(feature = "input")]
                 Event::Input(code) => {
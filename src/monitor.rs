@@ -0,0 +1,150 @@
+use crate::{event::Event, window::Rectangle};
+
+/// Identifies a monitor for as long as it stays connected.
+/// 
+/// These stay the same between calls to [`Connection::monitors`](crate::connection::Connection::monitors), so they
+/// can be used to find a monitor again after the layout of the desktop has changed.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct MonitorId(pub(crate) u64);
+
+/// A monitor which is part of the user's desktop, as listed by
+/// [`Connection::monitors`](crate::connection::Connection::monitors).
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Monitor {
+    pub(crate) id: MonitorId,
+    pub(crate) rect: Rectangle,
+    pub(crate) primary: bool,
+    pub(crate) refresh_rate: u32, // in millihertz, or 0 if unknown
+}
+
+impl Monitor {
+    pub fn id(&self) -> MonitorId {
+        self.id
+    }
+
+    /// Returns the area of the desktop shown on this monitor. The position is relative to the top-left of the user's
+    /// desktop, across all monitors.
+    pub fn rect(&self) -> Rectangle {
+        self.rect
+    }
+
+    /// Returns whether this is the user's primary monitor.
+    pub fn is_primary(&self) -> bool {
+        self.primary
+    }
+
+    /// Returns how many times per second this monitor refreshes, such as `59.94`, or `None` if that isn't known.
+    /// 
+    /// On X11, this is worked out from the timings of the monitor's current mode, so it may be slightly off from the
+    /// rate the mode is usually described as. Windows only reports whole numbers, so a 59.94Hz mode is reported as
+    /// `59.0`. The name of a monitor can be found with
+    /// [`Connection::monitor_name`](crate::connection::Connection::monitor_name).
+    pub fn refresh_rate(&self) -> Option<f64> {
+        match self.refresh_rate {
+            0 => None,
+            millihertz => Some(f64::from(millihertz) / 1000.0),
+        }
+    }
+
+    /// Finds the user's primary monitor in a list from
+    /// [`Connection::monitors`](crate::connection::Connection::monitors).
+    /// 
+    /// On X11, the user might not have picked a primary monitor, in which case the first one is returned instead.
+    /// This is only `None` if `monitors` is empty.
+    pub fn primary(monitors: &[Monitor]) -> Option<Monitor> {
+        monitors.iter().find(|monitor| monitor.primary).or_else(|| monitors.first()).copied()
+    }
+}
+
+const fn contains(rect: Rectangle, (x, y): (i32, i32)) -> bool {
+    x >= rect.x as i32
+        && y >= rect.y as i32
+        && x < rect.x as i32 + rect.width as i32
+        && y < rect.y as i32 + rect.height as i32
+}
+
+/// Works out where the top-left of a window should go to move it onto the monitor `to`.
+/// 
+/// The window keeps its position relative to the monitor it's centred on (out of `monitors`), as long as it still fits
+/// entirely on the new monitor. Otherwise, or if it isn't on any monitor, it's centred on the new one.
+pub(crate) fn place_on_monitor(window: Rectangle, monitors: &[Monitor], to: &Monitor) -> (i16, i16) {
+    let (width, height) = (i32::from(window.width), i32::from(window.height));
+    let centre = (i32::from(window.x) + width / 2, i32::from(window.y) + height / 2);
+    let dest = to.rect;
+    let (dest_x, dest_y) = (i32::from(dest.x), i32::from(dest.y));
+
+    if let Some(from) = monitors.iter().find(|m| contains(m.rect, centre)) {
+        let x = dest_x + i32::from(window.x) - i32::from(from.rect.x);
+        let y = dest_y + i32::from(window.y) - i32::from(from.rect.y);
+        if contains(dest, (x, y)) && contains(dest, (x + width - 1, y + height - 1)) {
+            return (x as i16, y as i16)
+        }
+    }
+
+    let x = dest_x + (i32::from(dest.width) - width) / 2;
+    let y = dest_y + (i32::from(dest.height) - height) / 2;
+    (x.clamp(i16::MIN.into(), i16::MAX.into()) as i16, y.clamp(i16::MIN.into(), i16::MAX.into()) as i16)
+}
+
+/// Works out the events describing a change from one list of monitors to another, passing each one to `push`.
+/// Monitors which are still connected, but have changed in some way, are reported as connected again.
+pub(crate) fn monitor_changes(old: &[Monitor], new: &[Monitor], mut push: impl FnMut(Event)) {
+    for monitor in old.iter().filter(|m| !new.iter().any(|n| n.id == m.id)) {
+        push(Event::MonitorDisconnected(monitor.id));
+    }
+    for monitor in new.iter().filter(|m| !old.contains(m)) {
+        push(Event::MonitorConnected(*monitor));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Two monitors side by side, with the primary one on the left
+    const LEFT: Monitor = Monitor {
+        id: MonitorId(1),
+        rect: Rectangle { x: 0, y: 0, width: 1920, height: 1080 },
+        primary: true,
+        refresh_rate: 60000,
+    };
+    const RIGHT: Monitor = Monitor {
+        id: MonitorId(2),
+        rect: Rectangle { x: 1920, y: 0, width: 1280, height: 720 },
+        primary: false,
+        refresh_rate: 60000,
+    };
+
+    #[test]
+    fn monitor_placement() {
+        let monitors = [LEFT, RIGHT];
+        assert_eq!(Monitor::primary(&monitors), Some(LEFT));
+        assert_eq!(Monitor::primary(&[RIGHT]), Some(RIGHT));
+        assert_eq!(LEFT.refresh_rate(), Some(60.0));
+
+        // Keeps its offset from the monitor's corner when it fits
+        let window = Rectangle { x: 100, y: 50, width: 800, height: 600 };
+        assert_eq!(place_on_monitor(window, &monitors, &RIGHT), (2020, 50));
+
+        // Centred when it would hang off the edge
+        let window = Rectangle { x: 1000, y: 400, width: 800, height: 600 };
+        assert_eq!(place_on_monitor(window, &monitors, &RIGHT), (2160, 60));
+    }
+
+    #[test]
+    fn monitor_hotplug() {
+        let changes = |old: &[Monitor], new: &[Monitor]| {
+            let mut events = Vec::new();
+            monitor_changes(old, new, |event| events.push(event));
+            events
+        };
+
+        assert_eq!(changes(&[LEFT], &[LEFT]), []);
+        assert_eq!(changes(&[LEFT], &[LEFT, RIGHT]), [Event::MonitorConnected(RIGHT)]);
+        assert_eq!(changes(&[LEFT, RIGHT], &[LEFT]), [Event::MonitorDisconnected(MonitorId(2))]);
+
+        // A change in resolution is reported as the same monitor connecting again
+        let bigger = Monitor { rect: Rectangle { width: 2560, height: 1440, ..RIGHT.rect }, ..RIGHT };
+        assert_eq!(changes(&[LEFT, RIGHT], &[LEFT, bigger]), [Event::MonitorConnected(bigger)]);
+    }
+}
@@ -1,7 +1,100 @@
 mod ffi;
 mod imp;
 
-pub use self::ffi::xcb_window_t;
+pub use self::ffi::{xcb_colormap_t, xcb_visualid_t, xcb_window_t};
 pub use self::ffi::Display;
+pub use crate::window::Gravity;
 
-pub(crate) use imp::{Connection, Window};
+pub(crate) use imp::{lock_connection, Batch, Connection, Window};
+
+/// An X protocol error received by Xlib, such as one caused by a GL driver's request.
+/// 
+/// ramen replaces Xlib's error handlers, which would otherwise exit the process, unless
+/// [`keep_xlib_error_handlers`] is called first. The most recent error can then be taken with [`take_xlib_error`].
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct XlibError {
+    pub error_code: u8,
+    pub request_code: u8,
+    pub minor_code: u8,
+    /// The XID of the resource the failed request was about, if any.
+    pub resource_id: u32,
+}
+
+/// Stops ramen from replacing Xlib's default error handlers when connecting.
+/// 
+/// The error handler is process-wide, so this has to be called before the first
+/// [`Connection`](crate::connection::Connection) is created. With the defaults, any X protocol error or lost
+/// connection reported to Xlib exits the process.
+pub fn keep_xlib_error_handlers() {
+    imp::keep_xlib_error_handlers()
+}
+
+/// Takes the most recent X protocol error received by Xlib, if there's been one since the last call.
+/// 
+/// Only the most recent error is kept. Errors from ramen's own requests aren't reported here.
+pub fn take_xlib_error() -> Option<XlibError> {
+    imp::take_xlib_error()
+}
+
+/// An EWMH feature which the Window Manager may or may not support.
+/// 
+/// Use [`Connection::supports`](crate::connection::Connection::supports) to check for one.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum NetWmFeature {
+    /// `_NET_ACTIVE_WINDOW`, for asking for a window to be focused.
+    ActiveWindow,
+    /// `_NET_WM_DESKTOP`, for moving a window between virtual desktops.
+    Desktop,
+    /// `_NET_FRAME_EXTENTS`, for finding the size of the Window Manager's decorations.
+    FrameExtents,
+    /// `_NET_WM_MOVERESIZE`, for starting an interactive move or resize.
+    MoveResize,
+    /// `_NET_WM_NAME`, for UTF-8 window titles.
+    Name,
+    /// `_NET_WM_PING`, for detecting unresponsive windows.
+    Ping,
+    /// `_NET_WM_STATE_ABOVE`, for keeping a window above others.
+    StateAbove,
+    /// `_NET_WM_STATE_DEMANDS_ATTENTION`, for flashing a window in the taskbar.
+    StateDemandsAttention,
+    /// `_NET_WM_STATE_FULLSCREEN`, for fullscreen windows.
+    StateFullscreen,
+    /// `_NET_WM_STATE_HIDDEN`, for telling when a window is minimised.
+    StateHidden,
+    /// `_NET_WM_STATE_MAXIMIZED_HORZ`, for maximising a window horizontally.
+    StateMaximizedHorz,
+    /// `_NET_WM_STATE_MAXIMIZED_VERT`, for maximising a window vertically.
+    StateMaximizedVert,
+    /// `_NET_WM_STATE_MODAL`, for modal dialogs.
+    StateModal,
+    /// `_NET_WM_STATE_SKIP_TASKBAR`, for keeping a window out of the taskbar.
+    StateSkipTaskbar,
+    /// `_NET_WM_SYNC_REQUEST`, for synchronising redraws with interactive resizing.
+    SyncRequest,
+    /// `_NET_WM_WINDOW_TYPE_DIALOG`, for marking a window as a dialog.
+    WindowTypeDialog,
+}
+
+impl NetWmFeature {
+    pub(crate) const fn atom_name(self) -> &'static str {
+        match self {
+            Self::ActiveWindow => "_NET_ACTIVE_WINDOW",
+            Self::Desktop => "_NET_WM_DESKTOP",
+            Self::FrameExtents => "_NET_FRAME_EXTENTS",
+            Self::MoveResize => "_NET_WM_MOVERESIZE",
+            Self::Name => "_NET_WM_NAME",
+            Self::Ping => "_NET_WM_PING",
+            Self::StateAbove => "_NET_WM_STATE_ABOVE",
+            Self::StateDemandsAttention => "_NET_WM_STATE_DEMANDS_ATTENTION",
+            Self::StateFullscreen => "_NET_WM_STATE_FULLSCREEN",
+            Self::StateHidden => "_NET_WM_STATE_HIDDEN",
+            Self::StateMaximizedHorz => "_NET_WM_STATE_MAXIMIZED_HORZ",
+            Self::StateMaximizedVert => "_NET_WM_STATE_MAXIMIZED_VERT",
+            Self::StateModal => "_NET_WM_STATE_MODAL",
+            Self::StateSkipTaskbar => "_NET_WM_STATE_SKIP_TASKBAR",
+            Self::SyncRequest => "_NET_WM_SYNC_REQUEST",
+            Self::WindowTypeDialog => "_NET_WM_WINDOW_TYPE_DIALOG",
+        }
+    }
+}
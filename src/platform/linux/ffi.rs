@@ -10,33 +10,61 @@ unsafe fn dlopen(name: *const c_char) -> *mut c_void {
     libc::dlopen(name, libc::RTLD_LOCAL | libc::RTLD_LAZY)
 }
 
+// Added in libX11 1.7, so it's looked up on its own rather than stopping older versions from loading at all.
+// Returns whether it was available. Assumes libX11 is already loaded.
+pub(super) unsafe fn XSetIOErrorExitHandler(dpy: *mut Display, handler: XIOErrorExitHandler, user_data: *mut c_void) -> bool {
+    type SetExitHandler = unsafe extern "C" fn(*mut Display, XIOErrorExitHandler, *mut c_void);
+    for name in [cstr!("libX11.so.6"), cstr!("libX11.so")] {
+        let handle = libc::dlopen(name, libc::RTLD_LOCAL | libc::RTLD_LAZY | libc::RTLD_NOLOAD);
+        if !handle.is_null() {
+            let sym = dlsym(handle, cstr!("XSetIOErrorExitHandler"));
+            let _ = dlerror();
+            let _ = libc::dlclose(handle); // it stays loaded, as we only took another reference to it
+            if sym.is_null() {
+                return false
+            }
+            std::mem::transmute::<*mut c_void, SetExitHandler>(sym)(dpy, handler, user_data);
+            return true
+        }
+    }
+    false
+}
+
 load! {
     pub(super) xlib(libX11) "libX11.so.6", "libX11.so" {
         fn XOpenDisplay(display_name: *const c_char) -> *mut Display;
         fn XDefaultScreen(display: *mut Display) -> c_int;
         fn XCloseDisplay(display: *mut Display) -> c_int;
+        fn XSetErrorHandler(handler: Option<XErrorHandler>) -> Option<XErrorHandler>;
         #[cfg(feature = "input")]
         fn XLookupKeysym(event_struct: *mut XKeyEvent, index: c_int) -> KeySym;
         #[cfg(feature = "input")]
         fn XLookupString(event_struct: *mut XKeyEvent, buffer_return: *mut c_char, bytes_buffer: c_int, keysym_return: *mut KeySym, status_in_out: *mut c_void) -> c_int;
+        #[cfg(feature = "input")]
+        fn XRefreshKeyboardMapping(event_map: *mut XMappingEvent) -> c_int;
+        #[cfg(feature = "input")]
+        fn XKeysymToKeycode(display: *mut Display, keysym: KeySym) -> u8;
+        #[cfg(feature = "input")]
+        fn XkbSetDetectableAutoRepeat(display: *mut Display, detectable: c_int, supported_rtrn: *mut c_int) -> c_int;
     }
     pub(super) xlib_xcb(libX11_xcb) "libX11-xcb.so.1", "libX11-xcb.so" {
         fn XGetXCBConnection(dpy: *mut Display) -> *mut xcb_connection_t;
         fn XSetEventQueueOwner(dpy: *mut Display, owner: EventQueueOwner);
     }
-    pub(super) xcb(libxcb) "libxcb.so.1", "libxcb.so" {
-        //fn xcb_connect(displayname: *const c_char, screenp: *mut c_int) -> *mut xcb_connection_t;
+    pub(super) xcb(libxcb, "RAMEN_LIBXCB_PATH") "libxcb.so.1", "libxcb.so" {
+        fn xcb_connect(displayname: *const c_char, screenp: *mut c_int) -> *mut xcb_connection_t;
         fn xcb_connection_has_error(c: *mut xcb_connection_t) -> c_int;
-        //fn xcb_disconnect(c: *mut xcb_connection_t);
+        fn xcb_disconnect(c: *mut xcb_connection_t);
+        fn xcb_parse_display(name: *const c_char, host: *mut *mut c_char, display: *mut c_int, screen: *mut c_int) -> c_int;
         fn xcb_discard_reply(c: *mut xcb_connection_t, sequence: c_uint);
         fn xcb_get_setup(c: *mut xcb_connection_t) -> *const xcb_setup_t;
         fn xcb_setup_roots_iterator(R: *const xcb_setup_t) -> xcb_screen_iterator_t;
+        fn xcb_setup_vendor(R: *const xcb_setup_t) -> *const c_char;
         fn xcb_screen_next(i: *mut xcb_screen_iterator_t);
 
         fn xcb_flush(c: *mut xcb_connection_t) -> c_int;
         fn xcb_generate_id(c: *mut xcb_connection_t) -> u32;
         fn xcb_request_check(c: *mut xcb_connection_t, sequence: c_uint) -> *mut xcb_generic_error_t;
-        fn xcb_create_colormap(c: *mut xcb_connection_t, alloc: u8, mid: xcb_colormap_t, window: xcb_window_t, visual: xcb_visualid_t) -> c_uint;
         fn xcb_create_window_checked(
             c: *mut xcb_connection_t,
             depth: u8,
@@ -52,6 +80,12 @@ load! {
             value_mask: u32,
             value_list: *const u32,
         ) -> c_uint;
+        fn xcb_change_window_attributes(
+            c: *mut xcb_connection_t,
+            window: xcb_window_t,
+            value_mask: u32,
+            value_list: *const u32,
+        ) -> c_uint;
         fn xcb_configure_window(
             c: *mut xcb_connection_t,
             window: xcb_window_t,
@@ -68,6 +102,13 @@ load! {
             data_len: u32,
             data: *const c_void,
         ) -> c_uint;
+        fn xcb_delete_property(c: *mut xcb_connection_t, window: xcb_window_t, property: xcb_atom_t) -> c_uint;
+        fn xcb_get_geometry(c: *mut xcb_connection_t, drawable: xcb_window_t) -> c_uint;
+        fn xcb_get_geometry_reply(
+            c: *mut xcb_connection_t,
+            cookie: c_uint,
+            err: *mut *mut xcb_generic_error_t,
+        ) -> *mut xcb_get_geometry_reply_t;
         fn xcb_translate_coordinates(
             c: *mut xcb_connection_t,
             src: xcb_window_t,
@@ -81,6 +122,12 @@ load! {
             err: *mut *mut xcb_generic_error_t,
         ) -> *mut xcb_translate_coordinates_reply_t;
         fn xcb_map_window(c: *mut xcb_connection_t, window: xcb_window_t) -> c_uint;
+        fn xcb_set_input_focus_checked(
+            c: *mut xcb_connection_t,
+            revert_to: u8,
+            focus: xcb_window_t,
+            time: xcb_timestamp_t,
+        ) -> c_uint;
         fn xcb_unmap_window(c: *mut xcb_connection_t, window: xcb_window_t) -> c_uint;
         fn xcb_intern_atom(
             c: *mut xcb_connection_t,
@@ -93,6 +140,60 @@ load! {
             sequence: c_uint,
             e: *mut *mut xcb_generic_error_t,
         ) -> *mut xcb_intern_atom_reply_t;
+        fn xcb_get_input_focus(c: *mut xcb_connection_t) -> c_uint;
+        fn xcb_grab_pointer(
+            c: *mut xcb_connection_t,
+            owner_events: u8,
+            grab_window: xcb_window_t,
+            event_mask: u16,
+            pointer_mode: u8,
+            keyboard_mode: u8,
+            confine_to: xcb_window_t,
+            cursor: u32,
+            time: xcb_timestamp_t,
+        ) -> c_uint;
+        fn xcb_grab_pointer_reply(
+            c: *mut xcb_connection_t,
+            cookie: c_uint,
+            e: *mut *mut xcb_generic_error_t,
+        ) -> *mut xcb_grab_pointer_reply_t;
+        fn xcb_ungrab_pointer(c: *mut xcb_connection_t, time: xcb_timestamp_t) -> c_uint;
+        fn xcb_query_pointer(c: *mut xcb_connection_t, window: xcb_window_t) -> c_uint;
+        fn xcb_query_pointer_reply(
+            c: *mut xcb_connection_t,
+            cookie: c_uint,
+            e: *mut *mut xcb_generic_error_t,
+        ) -> *mut xcb_query_pointer_reply_t;
+        #[cfg(feature = "input")]
+        fn xcb_query_keymap(c: *mut xcb_connection_t) -> c_uint;
+        #[cfg(feature = "input")]
+        fn xcb_query_keymap_reply(
+            c: *mut xcb_connection_t,
+            cookie: c_uint,
+            e: *mut *mut xcb_generic_error_t,
+        ) -> *mut xcb_query_keymap_reply_t;
+        fn xcb_get_input_focus_reply(
+            c: *mut xcb_connection_t,
+            cookie: c_uint,
+            e: *mut *mut xcb_generic_error_t,
+        ) -> *mut xcb_get_input_focus_reply_t;
+        fn xcb_get_selection_owner(c: *mut xcb_connection_t, selection: xcb_atom_t) -> c_uint;
+        fn xcb_get_selection_owner_reply(
+            c: *mut xcb_connection_t,
+            cookie: c_uint,
+            e: *mut *mut xcb_generic_error_t,
+        ) -> *mut xcb_get_selection_owner_reply_t;
+        fn xcb_set_selection_owner(c: *mut xcb_connection_t, owner: xcb_window_t, selection: xcb_atom_t, time: xcb_timestamp_t) -> c_uint;
+        fn xcb_convert_selection(
+            c: *mut xcb_connection_t,
+            requestor: xcb_window_t,
+            selection: xcb_atom_t,
+            target: xcb_atom_t,
+            property: xcb_atom_t,
+            time: xcb_timestamp_t,
+        ) -> c_uint;
+        fn xcb_get_maximum_request_length(c: *mut xcb_connection_t) -> u32;
+        fn xcb_get_file_descriptor(c: *mut xcb_connection_t) -> c_int;
         fn xcb_poll_for_event(c: *mut xcb_connection_t) -> *mut xcb_generic_event_t;
         fn xcb_poll_for_queued_event(c: *mut xcb_connection_t) -> *mut xcb_generic_event_t;
         fn xcb_send_event_checked(c: *mut xcb_connection_t, propagate: u8, destination: xcb_window_t, event_mask: u32, event: *const c_char) -> c_uint;
@@ -101,11 +202,79 @@ load! {
         fn xcb_get_property_reply(c: *mut xcb_connection_t, sequence: c_uint, e: *mut *mut xcb_generic_error_t) -> *mut xcb_get_property_reply_t;
         fn xcb_get_property_value(reply: *const xcb_get_property_reply_t) -> *mut c_void;
         fn xcb_get_property_value_length(reply: *const xcb_get_property_reply_t) -> c_int;
-        #[cfg(feature = "input")]
         fn xcb_query_extension(c: *mut xcb_connection_t, name_len: u16, name: *const c_char) -> c_uint;
-        #[cfg(feature = "input")]
         fn xcb_query_extension_reply(c: *mut xcb_connection_t, sequence: c_uint, e: *mut *mut xcb_generic_error_t) -> *mut xcb_query_extension_reply_t;
     }
+    pub(super) xfixes(libxcb_xfixes) "libxcb-xfixes.so.0", "libxcb-xfixes.so" {
+        fn xcb_xfixes_query_version(c: *mut xcb_connection_t, client_major_version: u32, client_minor_version: u32) -> c_uint;
+        fn xcb_xfixes_create_pointer_barrier(
+            c: *mut xcb_connection_t,
+            barrier: xcb_xfixes_barrier_t,
+            window: xcb_window_t,
+            x1: u16,
+            y1: u16,
+            x2: u16,
+            y2: u16,
+            directions: u32,
+            num_devices: u16,
+            devices: *const u16,
+        ) -> c_uint;
+        fn xcb_xfixes_delete_pointer_barrier(c: *mut xcb_connection_t, barrier: xcb_xfixes_barrier_t) -> c_uint;
+    }
+    pub(super) present(libxcb_present) "libxcb-present.so.0", "libxcb-present.so" {
+        fn xcb_present_query_version(c: *mut xcb_connection_t, major_version: u32, minor_version: u32) -> c_uint;
+        fn xcb_present_select_input(c: *mut xcb_connection_t, eid: u32, window: xcb_window_t, event_mask: u32) -> c_uint;
+        fn xcb_present_notify_msc(
+            c: *mut xcb_connection_t,
+            window: xcb_window_t,
+            serial: u32,
+            target_msc: u64,
+            divisor: u64,
+            remainder: u64,
+        ) -> c_uint;
+    }
+    pub(super) randr(libxcb_randr) "libxcb-randr.so.0", "libxcb-randr.so" {
+        fn xcb_randr_query_version(c: *mut xcb_connection_t, major_version: u32, minor_version: u32) -> c_uint;
+        fn xcb_randr_get_screen_resources_current(c: *mut xcb_connection_t, window: xcb_window_t) -> c_uint;
+        fn xcb_randr_select_input(c: *mut xcb_connection_t, window: xcb_window_t, enable: u16) -> c_uint;
+        fn xcb_randr_get_screen_resources_current_reply(
+            c: *mut xcb_connection_t,
+            sequence: c_uint,
+            e: *mut *mut xcb_generic_error_t,
+        ) -> *mut xcb_randr_get_screen_resources_current_reply_t;
+        fn xcb_randr_get_screen_resources_current_outputs(
+            r: *const xcb_randr_get_screen_resources_current_reply_t,
+        ) -> *mut xcb_randr_output_t;
+        fn xcb_randr_get_screen_resources_current_outputs_length(
+            r: *const xcb_randr_get_screen_resources_current_reply_t,
+        ) -> c_int;
+        fn xcb_randr_get_screen_resources_current_modes(
+            r: *const xcb_randr_get_screen_resources_current_reply_t,
+        ) -> *mut xcb_randr_mode_info_t;
+        fn xcb_randr_get_screen_resources_current_modes_length(
+            r: *const xcb_randr_get_screen_resources_current_reply_t,
+        ) -> c_int;
+        fn xcb_randr_get_output_info_name(r: *const xcb_randr_get_output_info_reply_t) -> *mut u8;
+        fn xcb_randr_get_output_info_name_length(r: *const xcb_randr_get_output_info_reply_t) -> c_int;
+        fn xcb_randr_get_output_info(c: *mut xcb_connection_t, output: xcb_randr_output_t, config_timestamp: xcb_timestamp_t) -> c_uint;
+        fn xcb_randr_get_output_info_reply(
+            c: *mut xcb_connection_t,
+            sequence: c_uint,
+            e: *mut *mut xcb_generic_error_t,
+        ) -> *mut xcb_randr_get_output_info_reply_t;
+        fn xcb_randr_get_crtc_info(c: *mut xcb_connection_t, crtc: xcb_randr_crtc_t, config_timestamp: xcb_timestamp_t) -> c_uint;
+        fn xcb_randr_get_crtc_info_reply(
+            c: *mut xcb_connection_t,
+            sequence: c_uint,
+            e: *mut *mut xcb_generic_error_t,
+        ) -> *mut xcb_randr_get_crtc_info_reply_t;
+        fn xcb_randr_get_output_primary(c: *mut xcb_connection_t, window: xcb_window_t) -> c_uint;
+        fn xcb_randr_get_output_primary_reply(
+            c: *mut xcb_connection_t,
+            sequence: c_uint,
+            e: *mut *mut xcb_generic_error_t,
+        ) -> *mut xcb_randr_get_output_primary_reply_t;
+    }
     #[cfg(feature = "input")]
     pub(super) xinput(libxcb_xinput) "libxcb-xinput.so.0", "libxcb-xinput.so" {
         #[cfg(feature = "input")]
@@ -115,27 +284,97 @@ load! {
     pub(super) xkbcommon(libxkbcommon) "libxkbcommon.so.0", "libxkbcommon.so" {
         #[cfg(feature = "input")]
         fn xkb_keysym_to_utf32(keysym: u32) -> u32;
+        #[cfg(feature = "input")]
+        fn xkb_context_new(flags: c_int) -> *mut xkb_context;
+        #[cfg(feature = "input")]
+        fn xkb_context_unref(context: *mut xkb_context);
+        #[cfg(feature = "input")]
+        fn xkb_compose_table_new_from_locale(context: *mut xkb_context, locale: *const c_char, flags: c_int) -> *mut xkb_compose_table;
+        #[cfg(feature = "input")]
+        fn xkb_compose_table_unref(table: *mut xkb_compose_table);
+        #[cfg(feature = "input")]
+        fn xkb_compose_state_new(table: *mut xkb_compose_table, flags: c_int) -> *mut xkb_compose_state;
+        #[cfg(feature = "input")]
+        fn xkb_compose_state_unref(state: *mut xkb_compose_state);
+        #[cfg(feature = "input")]
+        fn xkb_compose_state_feed(state: *mut xkb_compose_state, keysym: u32) -> c_int;
+        #[cfg(feature = "input")]
+        fn xkb_compose_state_reset(state: *mut xkb_compose_state);
+        #[cfg(feature = "input")]
+        fn xkb_compose_state_get_status(state: *mut xkb_compose_state) -> c_int;
+        #[cfg(feature = "input")]
+        fn xkb_compose_state_get_utf8(state: *mut xkb_compose_state, buffer: *mut c_char, size: usize) -> c_int;
+        #[cfg(feature = "input")]
+        fn xkb_compose_state_get_one_sym(state: *mut xkb_compose_state) -> u32;
     }
 }
 
 pub enum Display {}
-pub(super) enum xcb_setup_t {}
+pub(super) type XErrorHandler = unsafe extern "C" fn(*mut Display, *mut XErrorEvent) -> c_int;
+pub(super) type XIOErrorExitHandler = unsafe extern "C" fn(*mut Display, *mut c_void);
+
+#[repr(C)]
+pub(super) struct XErrorEvent {
+    pub(super) r#type: c_int,
+    pub(super) display: *mut Display,
+    pub(super) resourceid: libc::c_ulong,
+    pub(super) serial: libc::c_ulong,
+    pub(super) error_code: u8,
+    pub(super) request_code: u8,
+    pub(super) minor_code: u8,
+}
+// The fixed-size start of the server's setup reply, which is followed by the vendor string and then the screens
+#[repr(C)]
+pub(super) struct xcb_setup_t {
+    pub(super) status: u8,
+    pub(super) pad0: u8,
+    pub(super) protocol_major_version: u16,
+    pub(super) protocol_minor_version: u16,
+    pub(super) length: u16,
+    pub(super) release_number: u32,
+    pub(super) resource_id_base: u32,
+    pub(super) resource_id_mask: u32,
+    pub(super) motion_buffer_size: u32,
+    pub(super) vendor_len: u16,
+    pub(super) maximum_request_length: u16,
+    pub(super) roots_len: u8,
+    pub(super) pixmap_formats_len: u8,
+    pub(super) image_byte_order: u8,
+    pub(super) bitmap_format_bit_order: u8,
+    pub(super) bitmap_format_scanline_unit: u8,
+    pub(super) bitmap_format_scanline_pad: u8,
+    pub(super) min_keycode: u8,
+    pub(super) max_keycode: u8,
+    pub(super) pad1: [u8; 4],
+}
 pub(super) enum xcb_connection_t {}
 
 #[repr(C)]
 #[allow(dead_code)]
 pub(super) enum EventQueueOwner { XlibOwnsEventQueue = 0, XCBOwnsEventQueue }
 pub(super) type xcb_atom_t = u32;
-pub(super) type xcb_colormap_t = u32;
-pub(super) type xcb_visualid_t = u32;
+pub type xcb_colormap_t = u32;
+pub type xcb_visualid_t = u32;
+pub(super) type xcb_xfixes_barrier_t = u32;
 pub type xcb_window_t = u32;
 
 pub(super) const XCB_WINDOW_CLASS_INPUT_OUTPUT: u16 = 1;
+pub(super) const XCB_WINDOW_CLASS_INPUT_ONLY: u16 = 2;
 pub(super) const XCB_COPY_FROM_PARENT: u8 = 0;
-// pub(super) const XCB_KEY_PRESS: u8 = 2;
-// pub(super) const XCB_KEY_RELEASE: u8 = 3;
-// pub(super) const XCB_BUTTON_PRESS: u8 = 4;
-// pub(super) const XCB_BUTTON_RELEASE: u8 = 5;
+#[cfg(feature = "input")]
+pub(super) const XCB_KEY_PRESS: u8 = 2;
+#[cfg(feature = "input")]
+pub(super) const XCB_KEY_RELEASE: u8 = 3;
+#[cfg(feature = "input")]
+pub(super) const XCB_BUTTON_PRESS: u8 = 4;
+#[cfg(feature = "input")]
+pub(super) const XCB_BUTTON_RELEASE: u8 = 5;
+#[cfg(feature = "input")]
+pub(super) const XCB_MOTION_NOTIFY: u8 = 6;
+#[cfg(feature = "input")]
+pub(super) const XCB_ENTER_NOTIFY: u8 = 7;
+#[cfg(feature = "input")]
+pub(super) const XCB_LEAVE_NOTIFY: u8 = 8;
 pub(super) const XCB_FOCUS_IN: u8 = 9;
 pub(super) const XCB_FOCUS_OUT: u8 = 10;
 pub(super) const XCB_UNMAP_NOTIFY: u8 = 18;
@@ -143,8 +382,12 @@ pub(super) const XCB_MAP_NOTIFY: u8 = 19;
 pub(super) const XCB_REPARENT_NOTIFY: u8 = 21;
 pub(super) const XCB_CONFIGURE_NOTIFY: u8 = 22;
 pub(super) const XCB_PROPERTY_NOTIFY: u8 = 28;
+pub(super) const XCB_SELECTION_CLEAR: u8 = 29;
+pub(super) const XCB_SELECTION_REQUEST: u8 = 30;
+pub(super) const XCB_SELECTION_NOTIFY: u8 = 31;
 pub(super) const XCB_CLIENT_MESSAGE: u8 = 33;
 #[cfg(feature = "input")]
+pub(super) const XCB_MAPPING_NOTIFY: u8 = 34;
 pub(super) const XCB_GE_GENERIC: u8 = 35;
 
 pub(super) const XCB_PROP_MODE_REPLACE: u8 = 0;
@@ -152,21 +395,46 @@ pub(super) const XCB_PROP_MODE_REPLACE: u8 = 0;
 //pub(super) const XCB_PROP_MODE_PREPEND: u8 = 2;
 
 //pub(super) const XCB_ATOM_NONE: xcb_atom_t = 0;
+pub(super) const XCB_GET_PROPERTY_TYPE_ANY: xcb_atom_t = 0;
+pub(super) const XCB_CURRENT_TIME: xcb_timestamp_t = 0;
+pub(super) const XCB_INPUT_FOCUS_PARENT: u8 = 2;
+pub(super) const XCB_CONFIG_WINDOW_STACK_MODE: u16 = 64;
+pub(super) const XCB_STACK_MODE_ABOVE: u32 = 0;
+pub(super) const XCB_GRAB_MODE_ASYNC: u8 = 1;
+pub(super) const XCB_GRAB_STATUS_SUCCESS: u8 = 0;
 pub(super) const XCB_ATOM_ATOM: xcb_atom_t = 4;
 pub(super) const XCB_ATOM_CARDINAL: xcb_atom_t = 6;
+pub(super) const XCB_ATOM_RESOURCE_MANAGER: xcb_atom_t = 23;
 pub(super) const XCB_ATOM_STRING: xcb_atom_t = 31;
+pub(super) const XCB_ATOM_WINDOW: xcb_atom_t = 33;
 pub(super) const XCB_ATOM_WM_NAME: xcb_atom_t = 39;
+pub(super) const XCB_ATOM_WM_HINTS: xcb_atom_t = 35;
 pub(super) const XCB_ATOM_WM_CLASS: xcb_atom_t = 67;
+pub(super) const XCB_ATOM_WM_TRANSIENT_FOR: xcb_atom_t = 68;
 
+pub(super) const XCB_CW_BORDER_PIXEL: u32 = 8;
+pub(super) const XCB_CW_BIT_GRAVITY: u32 = 16;
+pub(super) const XCB_CW_BACKING_STORE: u32 = 64;
 pub(super) const XCB_CW_COLORMAP: u32 = 8192;
 pub(super) const XCB_CW_EVENT_MASK: u32 = 2048;
 #[cfg(feature = "input")]
+pub(super) const XCB_EVENT_MASK_KEY_PRESS: u32 = 1;
+#[cfg(feature = "input")]
+pub(super) const XCB_EVENT_MASK_KEY_RELEASE: u32 = 2;
+#[cfg(feature = "input")]
 pub(super) const XCB_EVENT_MASK_BUTTON_PRESS: u32 = 4;
+#[cfg(feature = "input")]
+pub(super) const XCB_EVENT_MASK_BUTTON_RELEASE: u32 = 8;
+#[cfg(feature = "input")]
+pub(super) const XCB_EVENT_MASK_ENTER_WINDOW: u32 = 16;
+#[cfg(feature = "input")]
+pub(super) const XCB_EVENT_MASK_LEAVE_WINDOW: u32 = 32;
+#[cfg(feature = "input")]
+pub(super) const XCB_EVENT_MASK_POINTER_MOTION: u32 = 64;
 pub(super) const XCB_EVENT_MASK_STRUCTURE_NOTIFY: u32 = 131072;
 pub(super) const XCB_EVENT_MASK_SUBSTRUCTURE_NOTIFY: u32 = 524288;
 pub(super) const XCB_EVENT_MASK_SUBSTRUCTURE_REDIRECT: u32 = 1048576;
 pub(super) const XCB_EVENT_MASK_PROPERTY_CHANGE: u32 = 4194304;
-#[cfg(not(feature = "input"))]
 pub(super) const XCB_EVENT_MASK_FOCUS_CHANGE: u32 = 2097152;
 
 pub(super) const XCB_NONE: c_int = 0;
@@ -174,9 +442,22 @@ pub(super) const XCB_ALLOC: c_int = 11;
 pub(super) const XCB_CONN_CLOSED_EXT_NOTSUPPORTED: c_int = 2;
 pub(super) const XCB_CONN_CLOSED_MEM_INSUFFICIENT: c_int = 3;
 
+// The directions in which the pointer is allowed to pass through a barrier
+pub(super) const XCB_XFIXES_BARRIER_DIRECTIONS_POSITIVE_X: u32 = 1;
+pub(super) const XCB_XFIXES_BARRIER_DIRECTIONS_POSITIVE_Y: u32 = 2;
+pub(super) const XCB_XFIXES_BARRIER_DIRECTIONS_NEGATIVE_X: u32 = 4;
+pub(super) const XCB_XFIXES_BARRIER_DIRECTIONS_NEGATIVE_Y: u32 = 8;
+
 pub(super) const ICCCM_SIZE_HINT_P_MIN_SIZE: u32 = 1 << 4;
 pub(super) const ICCCM_SIZE_HINT_P_MAX_SIZE: u32 = 1 << 5;
 pub(super) const ICCCM_SIZE_HINT_BASE_SIZE: u32 = 1 << 8;
+pub(super) const ICCCM_SIZE_HINT_P_WIN_GRAVITY: u32 = 1 << 9;
+
+pub(super) const ICCCM_WM_HINT_STATE: u32 = 1 << 1;
+pub(super) const ICCCM_WM_HINT_X_URGENCY: u32 = 1 << 8;
+
+// The first field of WM_STATE
+pub(super) const ICCCM_ICONIC_STATE: u32 = 3;
 
 #[repr(C)]
 pub(super) struct xcb_generic_error_t {
@@ -240,6 +521,21 @@ pub(super) struct xcb_screen_t {
     pub(super) allowed_depths_len: u8,
 }
 
+#[repr(C)]
+pub(super) struct xcb_get_geometry_reply_t {
+    pub(super) response_type: u8,
+    pub(super) depth: u8,
+    pub(super) sequence: u16,
+    pub(super) length: u32,
+    pub(super) root: xcb_window_t,
+    pub(super) x: i16,
+    pub(super) y: i16,
+    pub(super) width: u16,
+    pub(super) height: u16,
+    pub(super) border_width: u16,
+    pub(super) pad0: [u8; 2],
+}
+
 #[repr(C)]
 pub(super) struct xcb_translate_coordinates_reply_t {
     pub(super) response_type: u8,
@@ -251,6 +547,70 @@ pub(super) struct xcb_translate_coordinates_reply_t {
     pub(super) dst_y: i16,
 }
 
+#[repr(C)]
+pub(super) struct xcb_grab_pointer_reply_t {
+    pub(super) response_type: u8,
+    pub(super) status: u8,
+    pub(super) sequence: u16,
+    pub(super) length: u32,
+}
+
+#[repr(C)]
+pub(super) struct xcb_get_input_focus_reply_t {
+    pub(super) response_type: u8,
+    pub(super) revert_to: u8,
+    pub(super) sequence: u16,
+    pub(super) length: u32,
+    pub(super) focus: xcb_window_t,
+}
+
+#[repr(C)]
+pub(super) struct xcb_query_pointer_reply_t {
+    pub(super) response_type: u8,
+    pub(super) same_screen: u8,
+    pub(super) sequence: u16,
+    pub(super) length: u32,
+    pub(super) root: xcb_window_t,
+    pub(super) child: xcb_window_t,
+    pub(super) root_x: i16,
+    pub(super) root_y: i16,
+    pub(super) win_x: i16,
+    pub(super) win_y: i16,
+    pub(super) mask: u16,
+    pub(super) pad0: [u8; 2],
+}
+
+// Bits in `mask` above, for the first three buttons
+#[cfg(feature = "input")]
+pub(super) const XCB_BUTTON_MASK_1: u16 = 1 << 8;
+#[cfg(feature = "input")]
+pub(super) const XCB_BUTTON_MASK_2: u16 = 1 << 9;
+#[cfg(feature = "input")]
+pub(super) const XCB_BUTTON_MASK_3: u16 = 1 << 10;
+
+// Bits in a core or XInput modifier mask
+#[cfg(feature = "input")]
+pub(super) const XCB_MOD_MASK_SHIFT: u32 = 1 << 0;
+#[cfg(feature = "input")]
+pub(super) const XCB_MOD_MASK_LOCK: u32 = 1 << 1;
+#[cfg(feature = "input")]
+pub(super) const XCB_MOD_MASK_CONTROL: u32 = 1 << 2;
+#[cfg(feature = "input")]
+pub(super) const XCB_MOD_MASK_1: u32 = 1 << 3;
+#[cfg(feature = "input")]
+pub(super) const XCB_MOD_MASK_2: u32 = 1 << 4;
+#[cfg(feature = "input")]
+pub(super) const XCB_MOD_MASK_4: u32 = 1 << 6;
+
+#[repr(C)]
+pub(super) struct xcb_get_selection_owner_reply_t {
+    pub(super) response_type: u8,
+    pub(super) pad0: u8,
+    pub(super) sequence: u16,
+    pub(super) length: u32,
+    pub(super) owner: xcb_window_t,
+}
+
 #[repr(C)]
 pub(super) struct xcb_intern_atom_reply_t {
     pub(super) response_type: u8,
@@ -272,7 +632,7 @@ pub(super) struct xcb_get_property_reply_t {
     pub(super) _pad0: [u8; 12],
 }
 
-#[cfg(feature = "input")]
+#[derive(Clone, Copy)]
 #[repr(C)]
 pub(super) struct xcb_query_extension_reply_t {
     pub(super) response_type: u8,
@@ -304,6 +664,46 @@ pub(super) struct xcb_client_message_event_t {
     pub(super) client_data: ClientData,
 }
 
+// Key presses and releases, button presses and releases, and motion all share this layout, with `detail` being the
+// keycode, the button or nothing respectively
+#[cfg(feature = "input")]
+#[repr(C)]
+pub(super) struct xcb_key_press_event_t {
+    pub(super) response_type: u8,
+    pub(super) detail: u8,
+    pub(super) sequence: u16,
+    pub(super) time: xcb_timestamp_t,
+    pub(super) root: xcb_window_t,
+    pub(super) event: xcb_window_t,
+    pub(super) child: xcb_window_t,
+    pub(super) root_x: i16,
+    pub(super) root_y: i16,
+    pub(super) event_x: i16,
+    pub(super) event_y: i16,
+    pub(super) state: u16,
+    pub(super) same_screen: u8,
+    pub(super) _pad0: u8,
+}
+
+#[cfg(feature = "input")]
+#[repr(C)]
+pub(super) struct xcb_enter_notify_event_t {
+    pub(super) response_type: u8,
+    pub(super) detail: u8,
+    pub(super) sequence: u16,
+    pub(super) time: xcb_timestamp_t,
+    pub(super) root: xcb_window_t,
+    pub(super) event: xcb_window_t,
+    pub(super) child: xcb_window_t,
+    pub(super) root_x: i16,
+    pub(super) root_y: i16,
+    pub(super) event_x: i16,
+    pub(super) event_y: i16,
+    pub(super) state: u16,
+    pub(super) mode: u8,
+    pub(super) same_screen_focus: u8,
+}
+
 #[repr(C)]
 pub(super) struct xcb_focus_in_event_t {
     pub(super) response_type: u8,
@@ -378,6 +778,43 @@ pub(super) struct xcb_property_notify_event_t {
     pub(super) state: u8,
     pub(super) _pad1: [u8; 3],
 }
+#[repr(C)]
+pub(super) struct xcb_selection_clear_event_t {
+    pub(super) response_type: u8,
+    pub(super) _pad0: u8,
+    pub(super) sequence: u16,
+    pub(super) time: xcb_timestamp_t,
+    pub(super) owner: xcb_window_t,
+    pub(super) selection: xcb_atom_t,
+}
+
+#[repr(C)]
+pub(super) struct xcb_selection_request_event_t {
+    pub(super) response_type: u8,
+    pub(super) _pad0: u8,
+    pub(super) sequence: u16,
+    pub(super) time: xcb_timestamp_t,
+    pub(super) owner: xcb_window_t,
+    pub(super) requestor: xcb_window_t,
+    pub(super) selection: xcb_atom_t,
+    pub(super) target: xcb_atom_t,
+    pub(super) property: xcb_atom_t,
+}
+
+// Padded to 32 bytes, since it's sent with SendEvent
+#[repr(C)]
+pub(super) struct xcb_selection_notify_event_t {
+    pub(super) response_type: u8,
+    pub(super) _pad0: u8,
+    pub(super) sequence: u16,
+    pub(super) time: xcb_timestamp_t,
+    pub(super) requestor: xcb_window_t,
+    pub(super) selection: xcb_atom_t,
+    pub(super) target: xcb_atom_t,
+    pub(super) property: xcb_atom_t,
+    pub(super) _pad1: [u8; 8],
+}
+
 pub(super) const XCB_PROPERTY_NEW_VALUE: u8 = 0;
 pub(super) const XCB_PROPERTY_DELETE: u8 = 1;
 pub(super) const XCB_ATOM_WM_NORMAL_HINTS: u32 = 40;
@@ -390,7 +827,6 @@ pub(crate) union ClientData {
     pub(crate) data32: [u32; 5],
 }
 
-#[cfg(feature = "input")]
 #[repr(C)]
 pub(super) struct xcb_ge_generic_event_t {
     pub(super) response_type: u8,
@@ -404,6 +840,158 @@ pub(super) struct xcb_ge_generic_event_t {
 
 pub(super) type xcb_timestamp_t = u32;
 
+pub(super) type xcb_randr_output_t = u32;
+pub(super) type xcb_randr_crtc_t = u32;
+pub(super) type xcb_randr_mode_t = u32;
+
+pub(super) const XCB_RANDR_CONNECTION_CONNECTED: u8 = 0;
+
+pub(super) const XCB_RANDR_MODE_FLAG_INTERLACE: u32 = 16;
+pub(super) const XCB_RANDR_MODE_FLAG_DOUBLE_SCAN: u32 = 32;
+
+pub(super) const XCB_RANDR_NOTIFY_MASK_SCREEN_CHANGE: u16 = 1;
+pub(super) const XCB_RANDR_NOTIFY_MASK_OUTPUT_CHANGE: u16 = 4;
+
+// Relative to the extension's first event code.
+pub(super) const XCB_RANDR_SCREEN_CHANGE_NOTIFY: u8 = 0;
+pub(super) const XCB_RANDR_NOTIFY: u8 = 1;
+
+pub(super) const XCB_RANDR_NOTIFY_OUTPUT_CHANGE: u8 = 1;
+
+#[repr(C)]
+pub(super) struct xcb_randr_screen_change_notify_event_t {
+    pub(super) response_type: u8,
+    pub(super) rotation: u8,
+    pub(super) sequence: u16,
+    pub(super) timestamp: xcb_timestamp_t,
+    pub(super) config_timestamp: xcb_timestamp_t,
+    pub(super) root: xcb_window_t,
+    pub(super) request_window: xcb_window_t,
+    pub(super) size_id: u16,
+    pub(super) subpixel_order: u16,
+    pub(super) width: u16,
+    pub(super) height: u16,
+    pub(super) mwidth: u16,
+    pub(super) mheight: u16,
+}
+
+#[repr(C)]
+pub(super) struct xcb_randr_output_change_notify_event_t {
+    pub(super) response_type: u8,
+    pub(super) sub_code: u8,
+    pub(super) sequence: u16,
+    pub(super) timestamp: xcb_timestamp_t,
+    pub(super) config_timestamp: xcb_timestamp_t,
+    pub(super) window: xcb_window_t,
+    pub(super) output: xcb_randr_output_t,
+    pub(super) crtc: xcb_randr_crtc_t,
+    pub(super) mode: xcb_randr_mode_t,
+    pub(super) rotation: u16,
+    pub(super) connection: u8,
+    pub(super) subpixel_order: u8,
+}
+
+#[repr(C)]
+pub(super) struct xcb_randr_get_screen_resources_current_reply_t {
+    pub(super) response_type: u8,
+    pub(super) _pad0: u8,
+    pub(super) sequence: u16,
+    pub(super) length: u32,
+    pub(super) timestamp: xcb_timestamp_t,
+    pub(super) config_timestamp: xcb_timestamp_t,
+    pub(super) num_crtcs: u16,
+    pub(super) num_outputs: u16,
+    pub(super) num_modes: u16,
+    pub(super) names_len: u16,
+    pub(super) _pad1: [u8; 8],
+}
+
+#[repr(C)]
+pub(super) struct xcb_randr_mode_info_t {
+    pub(super) id: xcb_randr_mode_t,
+    pub(super) width: u16,
+    pub(super) height: u16,
+    pub(super) dot_clock: u32,
+    pub(super) hsync_start: u16,
+    pub(super) hsync_end: u16,
+    pub(super) htotal: u16,
+    pub(super) hskew: u16,
+    pub(super) vsync_start: u16,
+    pub(super) vsync_end: u16,
+    pub(super) vtotal: u16,
+    pub(super) name_len: u16,
+    pub(super) mode_flags: u32,
+}
+
+#[repr(C)]
+pub(super) struct xcb_randr_get_output_info_reply_t {
+    pub(super) response_type: u8,
+    pub(super) status: u8,
+    pub(super) sequence: u16,
+    pub(super) length: u32,
+    pub(super) timestamp: xcb_timestamp_t,
+    pub(super) crtc: xcb_randr_crtc_t,
+    pub(super) mm_width: u32,
+    pub(super) mm_height: u32,
+    pub(super) connection: u8,
+    pub(super) subpixel_order: u8,
+    pub(super) num_crtcs: u16,
+    pub(super) num_modes: u16,
+    pub(super) num_preferred: u16,
+    pub(super) num_clones: u16,
+    pub(super) name_len: u16,
+}
+
+#[repr(C)]
+pub(super) struct xcb_randr_get_crtc_info_reply_t {
+    pub(super) response_type: u8,
+    pub(super) status: u8,
+    pub(super) sequence: u16,
+    pub(super) length: u32,
+    pub(super) timestamp: xcb_timestamp_t,
+    pub(super) x: i16,
+    pub(super) y: i16,
+    pub(super) width: u16,
+    pub(super) height: u16,
+    pub(super) mode: xcb_randr_mode_t,
+    pub(super) rotation: u16,
+    pub(super) rotations: u16,
+    pub(super) num_outputs: u16,
+    pub(super) num_possible_outputs: u16,
+}
+
+#[repr(C)]
+pub(super) struct xcb_randr_get_output_primary_reply_t {
+    pub(super) response_type: u8,
+    pub(super) _pad0: u8,
+    pub(super) sequence: u16,
+    pub(super) length: u32,
+    pub(super) output: xcb_randr_output_t,
+}
+
+pub(super) const XCB_PRESENT_EVENT_MASK_COMPLETE_NOTIFY: u32 = 2;
+pub(super) const XCB_PRESENT_COMPLETE_NOTIFY: u16 = 1;
+pub(super) const XCB_PRESENT_COMPLETE_KIND_PIXMAP: u8 = 0;
+pub(super) const XCB_PRESENT_COMPLETE_KIND_NOTIFY_MSC: u8 = 1;
+
+// XCB packs this one, since the 64-bit fields fall on odd offsets after `full_sequence` gets inserted
+#[repr(C, packed)]
+pub(super) struct xcb_present_complete_notify_event_t {
+    pub(super) response_type: u8,
+    pub(super) extension: u8,
+    pub(super) sequence: u16,
+    pub(super) length: u32,
+    pub(super) event_type: u16,
+    pub(super) kind: u8,
+    pub(super) mode: u8,
+    pub(super) event: u32,
+    pub(super) window: xcb_window_t,
+    pub(super) serial: u32,
+    pub(super) ust: u64,
+    pub(super) full_sequence: u32,
+    pub(super) msc: u64,
+}
+
 #[cfg(feature = "input")]
 mod input {
     use super::*;
@@ -422,7 +1010,7 @@ mod input {
     pub(in super::super) const XCB_INPUT_XI_EVENT_MASK_LEAVE: u32 = 256;
     pub(in super::super) const XCB_INPUT_XI_EVENT_MASK_FOCUS_IN: u32 = 512;
     pub(in super::super) const XCB_INPUT_XI_EVENT_MASK_FOCUS_OUT: u32 = 1024;
-    //pub(in super::super) const XCB_INPUT_XI_EVENT_MASK_HIERARCHY: u32 = 2048;
+    pub(in super::super) const XCB_INPUT_XI_EVENT_MASK_HIERARCHY: u32 = 2048;
     //pub(in super::super) const XCB_INPUT_XI_EVENT_MASK_PROPERTY: u32 = 4096;
     //pub(in super::super) const XCB_INPUT_XI_EVENT_MASK_RAW_KEY_PRESS: u32 = 8192;
     //pub(in super::super) const XCB_INPUT_XI_EVENT_MASK_RAW_KEY_RELEASE: u32 = 16384;
@@ -439,7 +1027,7 @@ mod input {
     //pub(in super::super) const XCB_INPUT_XI_EVENT_MASK_BARRIER_HIT: u32 = 33554432;
     //pub(in super::super) const XCB_INPUT_XI_EVENT_MASK_BARRIER_LEAVE: u32 = 67108864;
 
-    //pub(in super::super) const XCB_INPUT_DEVICE_ALL: u16 = 0;
+    pub(in super::super) const XCB_INPUT_DEVICE_ALL: u16 = 0;
     pub(in super::super) const XCB_INPUT_DEVICE_ALL_MASTER: u16 = 1;
 
     pub(in super::super) const XCB_INPUT_KEY_PRESS: u16 = 2;
@@ -451,9 +1039,15 @@ mod input {
     pub(in super::super) const XCB_INPUT_LEAVE: u16 = 8;
     pub(in super::super) const XCB_INPUT_FOCUS_IN: u16 = 9;
     pub(in super::super) const XCB_INPUT_FOCUS_OUT: u16 = 10;
+    pub(in super::super) const XCB_INPUT_HIERARCHY: u16 = 11;
 
     pub(in super::super) const XCB_INPUT_KEY_EVENT_FLAGS_KEY_REPEAT: u32 = 65536;
 
+    pub(in super::super) const XCB_INPUT_HIERARCHY_MASK_MASTER_ADDED: u32 = 1;
+    pub(in super::super) const XCB_INPUT_HIERARCHY_MASK_MASTER_REMOVED: u32 = 2;
+    pub(in super::super) const XCB_INPUT_HIERARCHY_MASK_SLAVE_ADDED: u32 = 4;
+    pub(in super::super) const XCB_INPUT_HIERARCHY_MASK_SLAVE_REMOVED: u32 = 8;
+
     pub(in super::super) type xcb_input_fp1616_t = i32;
 
     #[repr(C)]
@@ -462,6 +1056,31 @@ mod input {
         pub(in super::super) mask_len: u16,
     }
 
+    #[repr(C)]
+    pub(in super::super) struct xcb_input_hierarchy_event_t {
+        pub(in super::super) response_type: u8,
+        pub(in super::super) extension: u8,
+        pub(in super::super) sequence: u16,
+        pub(in super::super) length: u32,
+        pub(in super::super) event_type: u16,
+        pub(in super::super) deviceid: xcb_input_device_id_t,
+        pub(in super::super) time: xcb_timestamp_t,
+        pub(in super::super) flags: u32,
+        pub(in super::super) num_infos: u16,
+        pub(in super::super) pad0: [u8; 10],
+        pub(in super::super) full_sequence: u32,
+    }
+
+    #[repr(C)]
+    pub(in super::super) struct xcb_input_hierarchy_info_t {
+        pub(in super::super) deviceid: xcb_input_device_id_t,
+        pub(in super::super) attachment: xcb_input_device_id_t,
+        pub(in super::super) r#type: u8,
+        pub(in super::super) enabled: u8,
+        pub(in super::super) pad0: [u8; 2],
+        pub(in super::super) flags: u32,
+    }
+
     #[repr(C)]
     pub(in super::super) struct xcb_input_enter_event_t {
         pub(in super::super) response_type: u8,
@@ -540,6 +1159,29 @@ mod input {
     }
 
     pub(in super::super) type KeySym = c_ulong;
+
+    pub(in super::super) enum xkb_context {}
+    pub(in super::super) enum xkb_compose_table {}
+    pub(in super::super) enum xkb_compose_state {}
+
+    pub(in super::super) const XKB_CONTEXT_NO_FLAGS: c_int = 0;
+    pub(in super::super) const XKB_COMPOSE_COMPILE_NO_FLAGS: c_int = 0;
+    pub(in super::super) const XKB_COMPOSE_STATE_NO_FLAGS: c_int = 0;
+    pub(in super::super) const XKB_COMPOSE_FEED_IGNORED: c_int = 0;
+    pub(in super::super) const XKB_COMPOSE_COMPOSING: c_int = 1;
+    pub(in super::super) const XKB_COMPOSE_COMPOSED: c_int = 2;
+    pub(in super::super) const XKB_COMPOSE_CANCELLED: c_int = 3;
+
+    // One bit per keycode, set while that key is held
+    #[repr(C)]
+    pub(in super::super) struct xcb_query_keymap_reply_t {
+        pub(in super::super) response_type: u8,
+        pub(in super::super) pad0: u8,
+        pub(in super::super) sequence: u16,
+        pub(in super::super) length: u32,
+        pub(in super::super) keys: [u8; 32],
+    }
+
     #[repr(C)]
     pub(in super::super) struct XKeyEvent {
         pub(in super::super) r#type: c_int,
@@ -558,11 +1200,35 @@ mod input {
         pub(in super::super) keycode: c_uint,
         pub(in super::super) same_screen: u8,
     }
+
+    #[repr(C)]
+    pub(in super::super) struct xcb_mapping_notify_event_t {
+        pub(in super::super) response_type: u8,
+        pub(in super::super) _pad0: u8,
+        pub(in super::super) sequence: u16,
+        pub(in super::super) request: u8,
+        pub(in super::super) first_keycode: u8,
+        pub(in super::super) count: u8,
+        pub(in super::super) _pad1: u8,
+    }
+
+    #[repr(C)]
+    pub(in super::super) struct XMappingEvent {
+        pub(in super::super) r#type: c_int,
+        pub(in super::super) serial: c_ulong,
+        pub(in super::super) send_event: c_int,
+        pub(in super::super) display: *mut Display,
+        pub(in super::super) window: c_ulong,
+        pub(in super::super) request: c_int,
+        pub(in super::super) first_keycode: c_int,
+        pub(in super::super) count: c_int,
+    }
 }
 
 
 // Borrows from Xm/MwmUtil.h (see VendorShell, XmNmwmDecorations)
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 #[repr(C)]
 pub(crate) struct MwmHints {
     pub(crate) flags: u32,
@@ -1,8 +1,16 @@
 // TODO: I suppose we'll need some method of deciding at runtime whether to use x11 or wayland? This is just x11
-use crate::{error::Error, event::Event, util::sync::{mutex_lock, Mutex}, connection, window};
-use super::ffi::*;
+use crate::{
+    error::Error,
+    event::{Event, FocusReason},
+    monitor::{self, Monitor, MonitorId},
+    util::sync::{mutex_lock, mutex_try_lock, Mutex, MutexGuard},
+    connection,
+    window::{self, clamp_size, size_limits_valid, CaptionAction, Gravity, Rectangle, WindowId},
+};
+use super::{ffi::*, NetWmFeature, XlibError};
 
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, ops::{Deref, DerefMut}, sync::{Arc, Once}, time::{Duration, Instant}};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU8, Ordering};
 
 /// The initial capacity for any Vec<Event>
 /// Event is around 8 bytes in size, so it's fairly costless for this to be a large starting capacity.
@@ -10,8 +18,143 @@ const QUEUE_SIZE: usize = 256;
 
 pub(crate) struct Connection {
     details: ConnectionDetails,
-    event_buffer: HashMap<xcb_window_t, Vec<*mut xcb_generic_event_t>>,
+    event_buffer: HashMap<xcb_window_t, EventQueue>,
+    client_leader: xcb_window_t,
+    xlib_compatible: bool, // if so, we have our own XCB connection which isn't the Display's
+    clipboard: Clipboard,
     hostname: Option<Vec<c_char>>,
+    application_name: Option<String>,
+}
+
+// Events stored for a window by whichever thread read them off the connection, for the window's next poll
+struct EventQueue {
+    events: Vec<*mut xcb_generic_event_t>,
+    wakeup: c_int, // the window's `EventFd`, which is signalled for each event, for `wait_events_until`
+}
+
+impl EventQueue {
+    unsafe fn push(&mut self, event: *mut xcb_generic_event_t) {
+        self.events.push(event);
+        let one = 1u64;
+        _ = libc::write(self.wakeup, (&one as *const u64).cast(), std::mem::size_of::<u64>());
+    }
+}
+
+// An eventfd, which a window waits on alongside the connection in case another thread stores events for it
+struct EventFd(c_int);
+
+impl EventFd {
+    fn new() -> Option<Self> {
+        let fd = unsafe { libc::eventfd(0, libc::EFD_NONBLOCK | libc::EFD_CLOEXEC) };
+        (fd != -1).then(|| Self(fd))
+    }
+
+    // Resets the count, so that it's only signalled again by events stored after this
+    fn clear(&self) {
+        let mut count = 0u64;
+        _ = unsafe { libc::read(self.0, (&mut count as *mut u64).cast(), std::mem::size_of::<u64>()) };
+    }
+}
+
+impl Drop for EventFd {
+    fn drop(&mut self) {
+        _ = unsafe { libc::close(self.0) };
+    }
+}
+
+// The connection lock, as taken by `lock_connection`
+pub(crate) struct ConnectionGuard<'a>(MutexGuard<'a, Connection>);
+
+// Locks the connection. Waiting for a reply reads any events which arrive in the meantime into xcb's queue, where
+// nothing waiting on the connection's file descriptor would notice them, so they're stored away for their windows
+// as the lock is let go of. That's what wakes up a window waiting in `wait_events_until` on another thread.
+pub(crate) fn lock_connection(connection: &Mutex<Connection>) -> ConnectionGuard<'_> {
+    ConnectionGuard(mutex_lock(connection))
+}
+
+pub(crate) fn try_lock_connection(connection: &Mutex<Connection>) -> Option<ConnectionGuard<'_>> {
+    mutex_try_lock(connection).map(ConnectionGuard)
+}
+
+impl Deref for ConnectionGuard<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        &self.0
+    }
+}
+
+impl DerefMut for ConnectionGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Connection {
+        &mut self.0
+    }
+}
+
+impl Drop for ConnectionGuard<'_> {
+    fn drop(&mut self) {
+        let Connection { details, event_buffer: map, clipboard, .. } = &mut *self.0;
+        unsafe {
+            loop {
+                let event = xcb_poll_for_queued_event(details.connection);
+                if event.is_null() { break }
+                route_event(event, None, details, map, clipboard);
+            }
+        }
+    }
+}
+
+// The clipboard contents we're serving to other clients, if we own the clipboard
+#[derive(Default)]
+struct Clipboard {
+    entries: Vec<(xcb_atom_t, Vec<u8>)>, // by target
+    transfers: Vec<IncrTransfer>,
+}
+
+// A clipboard entry too big for one property, being sent in chunks with the INCR protocol.
+// The requestor deletes the property each time it's read a chunk, which is our cue to write the next one.
+struct IncrTransfer {
+    requestor: xcb_window_t,
+    property: xcb_atom_t,
+    target: xcb_atom_t,
+    data: Vec<u8>,
+    offset: usize,
+    last_active: Instant, // so that transfers abandoned by the requestor can be dropped
+}
+
+// How long a requestor can take to read a chunk before an INCR transfer is abandoned
+const INCR_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Xlib's error handler is process-wide, so the last error it saw is too. The top bit of `XLIB_ERROR_CODES` is set
+// while there's one to take, followed by its error, request and minor codes.
+static XLIB_ERROR_CODES: AtomicU32 = AtomicU32::new(0);
+static XLIB_ERROR_RESOURCE: AtomicU32 = AtomicU32::new(0);
+static KEEP_XLIB_ERROR_HANDLERS: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn keep_xlib_error_handlers() {
+    KEEP_XLIB_ERROR_HANDLERS.store(true, Ordering::Relaxed);
+}
+
+pub(crate) fn take_xlib_error() -> Option<XlibError> {
+    let codes = XLIB_ERROR_CODES.swap(0, Ordering::Acquire);
+    (codes & (1 << 31) != 0).then(|| XlibError {
+        error_code: (codes >> 16) as u8,
+        request_code: (codes >> 8) as u8,
+        minor_code: codes as u8,
+        resource_id: XLIB_ERROR_RESOURCE.load(Ordering::Relaxed),
+    })
+}
+
+unsafe extern "C" fn xlib_error_handler(_display: *mut Display, event: *mut XErrorEvent) -> c_int {
+    let event = &*event;
+    XLIB_ERROR_RESOURCE.store(event.resourceid as u32, Ordering::Relaxed);
+    let codes = u32::from(event.error_code) << 16 | u32::from(event.request_code) << 8 | u32::from(event.minor_code);
+    XLIB_ERROR_CODES.store(1 << 31 | codes, Ordering::Release);
+    0
+}
+
+unsafe extern "C" fn xlib_io_error_exit_handler(_display: *mut Display, _user_data: *mut c_void) {
+    // Returning is enough to stop Xlib from exiting. The connection is dead from here on, and our XCB calls
+    // will see that, which `Window::is_connected` reports.
 }
 
 // Proxy struct for passing Connection details around without the allocated parts
@@ -23,6 +166,31 @@ struct ConnectionDetails {
     screenid: u32,
     atoms: Atoms,
     extensions: Extensions,
+    #[cfg(feature = "input")]
+    compose_table: *mut xkb_compose_table, // null if there isn't one for the user's locale
+}
+
+impl ConnectionDetails {
+    // A copy of these details for another of the display's screens, or `None` if there's no such screen
+    unsafe fn for_screen(&self, screenid: u32) -> Option<Self> {
+        if screenid == self.screenid {
+            return Some(*self)
+        }
+        let mut iter = xcb_setup_roots_iterator(xcb_get_setup(self.connection));
+        if screenid >= iter.rem as u32 {
+            return None
+        }
+        for _ in 0..screenid {
+            xcb_screen_next(&mut iter);
+        }
+        Some(Self { screen: iter.data, screenid, ..*self })
+    }
+
+    // A copy of these details for the screen a window was created on
+    unsafe fn for_window(&self, window: &WindowDetails) -> Self {
+        // The screen was checked when the window was created, and screens don't come and go
+        self.for_screen(window.screenid).unwrap_or(*self)
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -39,30 +207,80 @@ struct Atoms {
     _net_wm_state_maximized_vert: xcb_atom_t,
     _net_wm_state_hidden: xcb_atom_t,
     _motif_wm_hints: xcb_atom_t,
+    wm_window_role: xcb_atom_t,
+    wm_client_leader: xcb_atom_t,
+    sm_client_id: xcb_atom_t,
+    _net_wm_state_modal: xcb_atom_t,
+    wm_state: xcb_atom_t,
+    clipboard: xcb_atom_t,
+    targets: xcb_atom_t,
+    incr: xcb_atom_t,
+    _ramen_selection: xcb_atom_t,
+    _gtk_show_window_menu: xcb_atom_t,
+    _net_wm_desktop: xcb_atom_t,
+    _net_frame_extents: xcb_atom_t,
+    wm_change_state: xcb_atom_t,
+    _net_wm_icon: xcb_atom_t,
+    _net_wm_state_fullscreen: xcb_atom_t,
+    _net_active_window: xcb_atom_t,
+    _net_wm_state_above: xcb_atom_t,
+    _net_wm_window_opacity: xcb_atom_t,
 }
 
 #[derive(Clone, Copy)]
 struct Extensions {
     #[cfg(feature = "input")]
-    xinput: u8,
+    xinput: Option<u8>, // if not, input comes from the core protocol
+    xfixes: bool,
+    present: Option<u8>,
+    randr: Option<u8>,
 }
 
 impl Connection {
     pub(crate) fn new() -> Result<Self, Error> {
+        Self::open(false)
+    }
+
+    pub(crate) fn new_xlib_compatible() -> Result<Self, Error> {
+        Self::open(true)
+    }
+
+    fn open(xlib_compatible: bool) -> Result<Self, Error> {
         unsafe {
+            // libxcb goes first, so that libX11 and libX11-xcb use the same copy as us rather than pulling in their
+            // own, which matters when it's been loaded from `RAMEN_LIBXCB_PATH`
+            libxcb::load()?;
             libX11::load()?;
             libX11_xcb::load()?;
-            libxcb::load()?;
 
             let display = XOpenDisplay(std::ptr::null_mut());
             if display.is_null() {
-                // TODO: Unclear why this could fail when passing nullptr to it. Maybe the system has no screens?
-                // Maybe the underlying connection has failed, but how would we check?
-                return Err(Error::Unknown)
+                return Err(diagnose_open_failure())
+            }
+            if !KEEP_XLIB_ERROR_HANDLERS.load(Ordering::Relaxed) {
+                // Xlib's default handlers exit the process, even for errors caused by other libraries' requests
+                static INSTALL: Once = Once::new();
+                INSTALL.call_once(|| {
+                    let _ = XSetErrorHandler(Some(xlib_error_handler));
+                });
+                // This one is per-display, and needs libX11 1.7 - there's nothing to be done about older versions
+                let _ = XSetIOErrorExitHandler(display, xlib_io_error_exit_handler, std::ptr::null_mut());
             }
             let screen_num = XDefaultScreen(display);
-            let connection = XGetXCBConnection(display);
-            XSetEventQueueOwner(display, EventQueueOwner::XCBOwnsEventQueue);
+            let connection = if xlib_compatible {
+                // Xlib keeps its own event queue, and we talk to the server over a separate connection.
+                // Window IDs are shared across the whole server, so the Display can still be used with our windows.
+                let connection = xcb_connect(std::ptr::null(), std::ptr::null_mut());
+                if xcb_connection_has_error(connection) != 0 {
+                    xcb_disconnect(connection);
+                    let _ = XCloseDisplay(display);
+                    return Err(diagnose_open_failure())
+                }
+                connection
+            } else {
+                XSetEventQueueOwner(display, EventQueueOwner::XCBOwnsEventQueue);
+                XGetXCBConnection(display)
+            };
             let mut iter = xcb_setup_roots_iterator(xcb_get_setup(connection));
             for _ in 0..screen_num {
                 xcb_screen_next(&mut iter);
@@ -70,29 +288,116 @@ impl Connection {
             let screen = iter.data;
             let atoms = Atoms::new(connection)?;
 
-            // Make sure xinput is available
+            // Session managers group an application's windows by their WM_CLIENT_LEADER, so every window we make
+            // points at this one. It's never mapped. ICCCM says the leader should point at itself too.
+            let client_leader = xcb_generate_id(connection);
+            let leader_error = xcb_request_check(connection, xcb_create_window_checked(
+                connection,
+                XCB_COPY_FROM_PARENT,
+                client_leader,
+                (*screen).root,
+                0,
+                0,
+                1,
+                1,
+                0,
+                XCB_WINDOW_CLASS_INPUT_ONLY,
+                XCB_COPY_FROM_PARENT.into(),
+                XCB_CW_EVENT_MASK,
+                &XCB_EVENT_MASK_PROPERTY_CHANGE, // for reading clipboard contents sent with INCR
+            ));
+            if !leader_error.is_null() {
+                free(leader_error.cast());
+                return Err(Error::SystemResources)
+            }
+            _ = xcb_change_property(
+                connection,
+                XCB_PROP_MODE_REPLACE,
+                client_leader,
+                atoms.wm_client_leader,
+                XCB_ATOM_WINDOW,
+                32,
+                1,
+                (&client_leader) as *const _ as _,
+            );
+
+            // The X resources are a property of the root window, so this lets us see the scaling setting change
+            let root_mask = XCB_EVENT_MASK_PROPERTY_CHANGE;
+            _ = xcb_change_window_attributes(connection, (*screen).root, XCB_CW_EVENT_MASK, &root_mask);
+
+            // XInput2 is used for input where it's available. Some bare or remote servers don't have it, in which
+            // case the core protocol's input events are used instead, and there are no device events.
+            #[cfg(feature = "input")]
+            let xi_opcode = query_extension(connection, "XInputExtension")
+                .filter(|_| libxcb_xinput::load().is_ok())
+                .map(|reply| reply.major_opcode);
             #[cfg(feature = "input")]
-            let xi_opcode;
+            let mut compose_table = std::ptr::null_mut();
             #[cfg(feature = "input")]
             {
-                // xcb_query_extension cannot generate errors, so we don't check
-                let xi_name = "XInputExtension";
-                let xi = xcb_query_extension_reply(
-                    connection,
-                    xcb_query_extension(connection, xi_name.bytes().len() as _, xi_name.as_ptr().cast()),
-                    std::ptr::null_mut(),
-                );
-                if xi.is_null() {
-                    return Err(Error::SystemResources)
+                libxkbcommon::load()?;
+
+                // Loading the Compose file takes a moment, so it's done once and shared by every window's sequences
+                let context = xkb_context_new(XKB_CONTEXT_NO_FLAGS);
+                if !context.is_null() {
+                    let locale = compose_locale();
+                    compose_table =
+                        xkb_compose_table_new_from_locale(context, locale.as_ptr(), XKB_COMPOSE_COMPILE_NO_FLAGS);
+                    xkb_context_unref(context);
                 }
-                if (*xi).present == 0 {
-                    return Err(Error::Unsupported)
+
+                if xi_opcode.is_some() {
+                    // Devices being added and removed is only reported on the root window. It's selected once here,
+                    // and then each window is given its own copy of the events, as they're routed.
+                    #[repr(C)]
+                    struct XiMask {
+                        head: xcb_input_event_mask_t,
+                        body: u32,
+                    }
+                    let mut mask = XiMask {
+                        head: xcb_input_event_mask_t {
+                            deviceid: XCB_INPUT_DEVICE_ALL,
+                            mask_len: 1,
+                        },
+                        body: XCB_INPUT_XI_EVENT_MASK_HIERARCHY,
+                    };
+                    xcb_discard_reply(
+                        connection,
+                        xcb_input_xi_select_events_checked(connection, (*screen).root, 1, (&mut mask.head) as _),
+                    );
+                } else {
+                    // Core key events don't say whether they're repeats, and a held key normally sends a release
+                    // before each repeated press. With this, held keys only send presses, so repeats can be told
+                    // apart. It's set through Xlib, so it only applies when Xlib's connection is the one we use.
+                    // Otherwise, `process_event` picks out the release and press pairs by their timestamps.
+                    _ = XkbSetDetectableAutoRepeat(display, 1, std::ptr::null_mut());
                 }
-                xi_opcode = (*xi).major_opcode;
-                free(xi.cast());
+            }
 
-                libxcb_xinput::load()?;
-                libxkbcommon::load()?;
+            // XFixes is only used for pointer barriers, so it's not an error if it's missing
+            let has_xfixes = query_extension(connection, "XFIXES").is_some() && libxcb_xfixes::load().is_ok();
+            if has_xfixes {
+                // The client has to announce its version before making any other XFixes requests
+                // Pointer barriers were added in XFixes 5.0
+                xcb_discard_reply(connection, xcb_xfixes_query_version(connection, 5, 0));
+            }
+
+            // RandR is needed to tell monitors apart, without it we treat the whole screen as one
+            // Version 1.3 added GetScreenResourcesCurrent and GetOutputPrimary
+            // We keep the first event code so hotplug notifications can be recognised
+            let randr_event_base = query_extension(connection, "RANDR")
+                .filter(|_| libxcb_randr::load().is_ok())
+                .map(|reply| reply.first_event);
+            if randr_event_base.is_some() {
+                xcb_discard_reply(connection, xcb_randr_query_version(connection, 1, 3));
+            }
+
+            // Present is only used for frame pacing, so that's optional too
+            let present_opcode = query_extension(connection, "Present")
+                .filter(|_| libxcb_present::load().is_ok())
+                .map(|reply| reply.major_opcode);
+            if present_opcode.is_some() {
+                xcb_discard_reply(connection, xcb_present_query_version(connection, 1, 0));
             }
 
             // Try to get machine's hostname
@@ -131,22 +436,249 @@ impl Connection {
                     extensions: Extensions {
                         #[cfg(feature = "input")]
                         xinput: xi_opcode,
+                        xfixes: has_xfixes,
+                        present: present_opcode,
+                        randr: randr_event_base,
                     },
+                    #[cfg(feature = "input")]
+                    compose_table,
                 },
                 event_buffer: HashMap::new(),
+                client_leader,
+                xlib_compatible,
+                clipboard: Clipboard::default(),
                 hostname,
+                application_name: None,
             })
         }
     }
 
+    pub(crate) fn screen_count(&self) -> i32 {
+        unsafe { xcb_setup_roots_iterator(xcb_get_setup(self.details.connection)).rem }
+    }
+
     pub(crate) fn xscreenid(&self) -> u32 {
         self.details.screenid
     }
 
+    pub(crate) fn server_vendor(&self) -> Option<String> {
+        unsafe {
+            let setup = xcb_get_setup(self.details.connection);
+            let len = usize::from((*setup).vendor_len);
+            let vendor = std::slice::from_raw_parts(xcb_setup_vendor(setup).cast::<u8>(), len);
+            Some(String::from_utf8_lossy(vendor).into_owned())
+        }
+    }
+
+    pub(crate) fn poll_events(&mut self) {
+        let Connection { details, event_buffer: map, clipboard, .. } = self;
+        unsafe { pull_events(None, details, map, clipboard) }
+    }
+
+    pub(crate) fn windows(&self) -> Vec<WindowId> {
+        // Every window has an event queue here for as long as it's open
+        let mut windows: Vec<WindowId> = self.event_buffer.keys().map(|&xid| WindowId(xid.into())).collect();
+        windows.sort_unstable_by_key(|id| id.0);
+        windows
+    }
+
+    pub(crate) fn protocol_version(&self) -> Option<(u16, u16)> {
+        unsafe {
+            let setup = &*xcb_get_setup(self.details.connection);
+            Some((setup.protocol_major_version, setup.protocol_minor_version))
+        }
+    }
+
     pub(crate) fn xdisplay(&self) -> *mut Display {
         self.details.display
     }
 
+    pub(crate) fn fd(&self) -> std::os::unix::io::RawFd {
+        unsafe { xcb_get_file_descriptor(self.details.connection) }
+    }
+
+    pub(crate) fn monitor_name(&self, monitor: MonitorId) -> Option<String> {
+        unsafe { query_monitor_name(&self.details, monitor) }
+    }
+
+    pub(crate) fn monitors(&self) -> Vec<Monitor> {
+        unsafe { query_monitors(&self.details) }
+    }
+
+    pub(crate) fn set_application_name(&mut self, name: &str) {
+        self.application_name = Some(name.to_owned());
+    }
+
+    pub(crate) fn set_sm_client_id(&mut self, id: &str) {
+        unsafe {
+            let c = self.details.connection;
+            _ = xcb_change_property(
+                c,
+                XCB_PROP_MODE_REPLACE,
+                self.client_leader,
+                self.details.atoms.sm_client_id,
+                XCB_ATOM_STRING,
+                8,
+                id.len() as _,
+                id.as_ptr().cast(),
+            );
+            _ = xcb_flush(c);
+        }
+    }
+
+    pub(crate) fn is_compositor_running(&self) -> bool {
+        unsafe {
+            // EWMH says a compositing manager must own the `_NET_WM_CM_Sn` selection, where n is the screen number
+            let c = self.details.connection;
+            let selection = match intern_atom(c, &format!("_NET_WM_CM_S{}", self.details.screenid), false) {
+                Some(atom) => atom,
+                None => return false,
+            };
+            let reply = xcb_get_selection_owner_reply(c, xcb_get_selection_owner(c, selection), std::ptr::null_mut());
+            if reply.is_null() {
+                return false
+            }
+            let owner = (*reply).owner;
+            free(reply.cast());
+            owner != 0
+        }
+    }
+
+    pub(crate) fn window_manager_name(&self) -> Option<String> {
+        unsafe {
+            let c = self.details.connection;
+            let check = intern_atom(c, "_NET_SUPPORTING_WM_CHECK", true)?;
+            let wm_window = |window| {
+                get_property(c, window, check, XCB_ATOM_WINDOW)
+                    .and_then(|value| value.get(..4).map(|x| u32::from_ne_bytes([x[0], x[1], x[2], x[3]])))
+            };
+
+            // The check window has the property pointing at itself too, otherwise it's a stale one left by a dead WM
+            let window = wm_window((*self.details.screen).root)?;
+            if wm_window(window) != Some(window) {
+                return None
+            }
+            let name = get_property(c, window, self.details.atoms._net_wm_name, self.details.atoms.utf8_string)?;
+            Some(String::from_utf8_lossy(&name).into_owned())
+        }
+    }
+
+    pub(crate) fn default_scale_factor(&self) -> f64 {
+        unsafe { query_scale_factor(&self.details) }
+    }
+
+    pub(crate) fn supports(&self, feature: NetWmFeature) -> bool {
+        unsafe {
+            let c = self.details.connection;
+            // An atom which was never interned can't be in the list, so there's no need to create it
+            let (supported, atom) = match (
+                intern_atom(c, "_NET_SUPPORTED", true),
+                intern_atom(c, feature.atom_name(), true),
+            ) {
+                (Some(supported), Some(atom)) => (supported, atom),
+                _ => return false,
+            };
+            get_property(c, (*self.details.screen).root, supported, XCB_ATOM_ATOM).map_or(false, |value| {
+                value.chunks_exact(4).any(|x| u32::from_ne_bytes([x[0], x[1], x[2], x[3]]) == atom)
+            })
+        }
+    }
+
+    pub(crate) fn clipboard_data(&mut self, mime: &str) -> Option<Vec<u8>> {
+        unsafe {
+            let c = self.details.connection;
+            let atoms = self.details.atoms;
+            let leader = self.client_leader;
+            // UTF8_STRING is the one text type every X client can convert to
+            let target = if mime.eq_ignore_ascii_case("text/plain;charset=utf-8") {
+                atoms.utf8_string
+            } else {
+                intern_atom(c, mime, false)?
+            };
+            _ = xcb_convert_selection(c, leader, atoms.clipboard, target, atoms._ramen_selection, XCB_CURRENT_TIME);
+            _ = xcb_flush(c);
+
+            let event = self.wait_for_event(|ev| {
+                (*ev).response_type & !(1 << 7) == XCB_SELECTION_NOTIFY
+                    && (*(ev as *mut xcb_selection_notify_event_t)).requestor == leader
+            })?;
+            let property = (*(event as *mut xcb_selection_notify_event_t)).property;
+            free(event.cast());
+            if property == 0 {
+                // Either nobody owns the clipboard, or the owner can't convert it to this type
+                return None
+            }
+            let (r#type, mut data) = take_property(c, leader, property)?;
+            if r#type != atoms.incr {
+                return Some(data)
+            }
+
+            // An INCR transfer: the first property holds the size, and deleting it (which `take_property` did)
+            // tells the owner to start sending chunks. Each chunk is deleted after reading, and an empty one ends it.
+            let size = data.get(..4).map_or(0, |x| u32::from_ne_bytes([x[0], x[1], x[2], x[3]]));
+            data = Vec::new();
+            let _ = data.try_reserve(size as usize);
+            loop {
+                let event = self.wait_for_event(|ev| {
+                    if (*ev).response_type & !(1 << 7) != XCB_PROPERTY_NOTIFY {
+                        return false
+                    }
+                    let event = &*(ev as *mut xcb_property_notify_event_t);
+                    event.window == leader && event.atom == property && event.state == XCB_PROPERTY_NEW_VALUE
+                })?;
+                free(event.cast());
+                let (_, chunk) = take_property(c, leader, property)?;
+                if chunk.is_empty() {
+                    break Some(data)
+                }
+                data.extend_from_slice(&chunk);
+            }
+        }
+    }
+
+    pub(crate) fn set_clipboard_data(&mut self, entries: &[(String, Vec<u8>)]) {
+        unsafe {
+            let c = self.details.connection;
+            self.clipboard.entries.clear();
+            for (mime, data) in entries {
+                if let Some(target) = intern_atom(c, mime, false) {
+                    self.clipboard.entries.push((target, data.clone()));
+                }
+                // Most X clients only know to ask for UTF8_STRING when they want text
+                if mime.eq_ignore_ascii_case("text/plain;charset=utf-8") {
+                    self.clipboard.entries.push((self.details.atoms.utf8_string, data.clone()));
+                }
+            }
+            _ = xcb_set_selection_owner(c, self.client_leader, self.details.atoms.clipboard, XCB_CURRENT_TIME);
+            _ = xcb_flush(c);
+        }
+    }
+
+    // Waits for an event matching `predicate`, giving up after a second. Any other events are handled or stored
+    // away like in `Window::poll_events`, which also lets us serve our own clipboard while we wait for it.
+    unsafe fn wait_for_event(
+        &mut self,
+        mut predicate: impl FnMut(*mut xcb_generic_event_t) -> bool,
+    ) -> Option<*mut xcb_generic_event_t> {
+        let c = self.details.connection;
+        let deadline = Instant::now() + Duration::from_secs(1);
+        loop {
+            let event = xcb_poll_for_event(c);
+            if event.is_null() {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining == Duration::ZERO || xcb_connection_has_error(c) != 0 {
+                    return None
+                }
+                let mut fd = libc::pollfd { fd: xcb_get_file_descriptor(c), events: libc::POLLIN, revents: 0 };
+                _ = libc::poll(&mut fd, 1, remaining.as_millis() as c_int + 1);
+            } else if predicate(event) {
+                return Some(event)
+            } else {
+                route_event(event, None, &self.details, &mut self.event_buffer, &mut self.clipboard);
+            }
+        }
+    }
+
     // Helper wrapper for `xcb_connection_has_error` for use with `?`. Assumes pointer is valid.
     unsafe fn check(c: *mut xcb_connection_t) -> Result<(), Error> {
         let err = xcb_connection_has_error(c);
@@ -159,12 +691,261 @@ impl Connection {
     }
 }
 
+// XOpenDisplay doesn't report why it failed, so this works out the most likely reason by reaching for the server
+// ourselves. If the socket accepts us, the server must have rejected the connection setup, which is almost always auth.
+unsafe fn diagnose_open_failure() -> Error {
+    match std::env::var_os("DISPLAY") {
+        Some(display) if !display.is_empty() => (),
+        _ => return Error::NoDisplay,
+    }
+    let mut host: *mut c_char = std::ptr::null_mut();
+    let (mut display, mut screen): (c_int, c_int) = (0, 0);
+    if xcb_parse_display(std::ptr::null(), &mut host, &mut display, &mut screen) == 0 {
+        return Error::Invalid
+    }
+    let host_string = std::ffi::CStr::from_ptr(host).to_string_lossy().into_owned();
+    free(host.cast());
+
+    // A "protocol/" prefix is left on the host by xcb_parse_display
+    let host = host_string.rsplit('/').next().unwrap_or_default();
+    let reachable = if host.is_empty() || host == "unix" {
+        std::os::unix::net::UnixStream::connect(format!("/tmp/.X11-unix/X{}", display)).is_ok()
+    } else {
+        match u16::try_from(display).ok().and_then(|d| d.checked_add(6000)) {
+            Some(port) => std::net::TcpStream::connect((host, port)).is_ok(),
+            None => false,
+        }
+    };
+    if reachable { Error::AuthFailed } else { Error::ConnectionRefused }
+}
+
+// Lists the monitors making up the screen, using the CRTC each connected output is being shown on
+unsafe fn query_monitors(details: &ConnectionDetails) -> Vec<Monitor> {
+    let c = details.connection;
+    let screen = &*details.screen;
+    let whole_screen = Monitor {
+        id: MonitorId(0),
+        rect: Rectangle { x: 0, y: 0, width: screen.width_in_pixels, height: screen.height_in_pixels },
+        primary: true,
+        refresh_rate: 0,
+    };
+    if details.extensions.randr.is_none() {
+        return vec![whole_screen]
+    }
+
+    let resources_cookie = xcb_randr_get_screen_resources_current(c, screen.root);
+    let primary_cookie = xcb_randr_get_output_primary(c, screen.root);
+    let resources = xcb_randr_get_screen_resources_current_reply(c, resources_cookie, std::ptr::null_mut());
+    let primary_reply = xcb_randr_get_output_primary_reply(c, primary_cookie, std::ptr::null_mut());
+    let primary = if !primary_reply.is_null() {
+        let output = (*primary_reply).output;
+        free(primary_reply.cast());
+        output
+    } else {
+        0
+    };
+    if resources.is_null() {
+        return vec![whole_screen]
+    }
+
+    let timestamp = (*resources).config_timestamp;
+    let outputs = std::slice::from_raw_parts(
+        xcb_randr_get_screen_resources_current_outputs(resources),
+        xcb_randr_get_screen_resources_current_outputs_length(resources) as usize,
+    );
+    let refresh_rates: HashMap<xcb_randr_mode_t, u32> = std::slice::from_raw_parts(
+        xcb_randr_get_screen_resources_current_modes(resources),
+        xcb_randr_get_screen_resources_current_modes_length(resources) as usize,
+    )
+    .iter()
+    .map(|mode| (mode.id, mode_refresh_rate(mode)))
+    .collect();
+    // All the requests go out before waiting on any replies, so this is one round-trip per stage rather than per output
+    let cookies: Vec<c_uint> = outputs.iter().map(|&output| xcb_randr_get_output_info(c, output, timestamp)).collect();
+    let mut crtcs = Vec::with_capacity(outputs.len());
+    for (&output, cookie) in outputs.iter().zip(cookies) {
+        let info = xcb_randr_get_output_info_reply(c, cookie, std::ptr::null_mut());
+        if info.is_null() {
+            continue
+        }
+        if (*info).connection == XCB_RANDR_CONNECTION_CONNECTED && (*info).crtc != 0 {
+            crtcs.push((output, xcb_randr_get_crtc_info(c, (*info).crtc, timestamp)));
+        }
+        free(info.cast());
+    }
+    free(resources.cast());
+
+    let mut monitors = Vec::with_capacity(crtcs.len());
+    for (output, cookie) in crtcs {
+        let crtc = xcb_randr_get_crtc_info_reply(c, cookie, std::ptr::null_mut());
+        if crtc.is_null() {
+            continue
+        }
+        let crtc_ = &*crtc;
+        monitors.push(Monitor {
+            id: MonitorId(output.into()),
+            rect: Rectangle { x: crtc_.x, y: crtc_.y, width: crtc_.width, height: crtc_.height },
+            primary: output == primary,
+            refresh_rate: refresh_rates.get(&crtc_.mode).copied().unwrap_or(0),
+        });
+        free(crtc.cast());
+    }
+    if monitors.is_empty() {
+        monitors.push(whole_screen);
+    }
+    monitors
+}
+
+// The refresh rate of a RandR mode in millihertz, or 0 if its timings don't say
+fn mode_refresh_rate(mode: &xcb_randr_mode_info_t) -> u32 {
+    let mut clock = u64::from(mode.dot_clock) * 1000;
+    let mut dots = u64::from(mode.htotal) * u64::from(mode.vtotal);
+    if mode.mode_flags & XCB_RANDR_MODE_FLAG_DOUBLE_SCAN != 0 {
+        dots *= 2;
+    }
+    // Each refresh only draws every other line
+    if mode.mode_flags & XCB_RANDR_MODE_FLAG_INTERLACE != 0 {
+        clock *= 2;
+    }
+    if dots == 0 {
+        return 0
+    }
+    ((clock + dots / 2) / dots) as u32
+}
+
+// The name of the RandR output a monitor is shown on, which is what its ID is
+unsafe fn query_monitor_name(details: &ConnectionDetails, monitor: MonitorId) -> Option<String> {
+    // Without RandR, the whole screen is listed as a monitor with ID 0, which isn't an output
+    if details.extensions.randr.is_none() || monitor.0 == 0 {
+        return None
+    }
+    let c = details.connection;
+    let cookie = xcb_randr_get_output_info(c, monitor.0 as xcb_randr_output_t, XCB_CURRENT_TIME);
+    let info = xcb_randr_get_output_info_reply(c, cookie, std::ptr::null_mut());
+    if info.is_null() {
+        return None
+    }
+    // Disconnected outputs keep their names, but they aren't monitors anymore
+    let name = if (*info).connection == XCB_RANDR_CONNECTION_CONNECTED {
+        let name = std::slice::from_raw_parts(
+            xcb_randr_get_output_info_name(info),
+            xcb_randr_get_output_info_name_length(info) as usize,
+        );
+        Some(String::from_utf8_lossy(name).into_owned()).filter(|name| !name.is_empty())
+    } else {
+        None
+    };
+    free(info.cast());
+    name
+}
+
+// Interns an atom by name. If `only_if_exists` is set, `None` is returned when the atom hasn't been created yet.
+unsafe fn intern_atom(c: *mut xcb_connection_t, name: &str, only_if_exists: bool) -> Option<xcb_atom_t> {
+    let reply = xcb_intern_atom_reply(
+        c,
+        xcb_intern_atom(c, only_if_exists.into(), name.len() as u16, name.as_ptr().cast()),
+        std::ptr::null_mut(),
+    );
+    if reply.is_null() {
+        return None
+    }
+    let atom = (*reply).atom;
+    free(reply.cast());
+    if atom != 0 { Some(atom) } else { None }
+}
+
+// Reads and deletes a whole property of any type, returning its type and value. Returns `None` if it isn't set.
+unsafe fn take_property(
+    c: *mut xcb_connection_t,
+    window: xcb_window_t,
+    property: xcb_atom_t,
+) -> Option<(xcb_atom_t, Vec<u8>)> {
+    let cookie = xcb_get_property(c, 1, window, property, XCB_GET_PROPERTY_TYPE_ANY, 0, !0);
+    let reply = xcb_get_property_reply(c, cookie, std::ptr::null_mut());
+    if reply.is_null() {
+        return None
+    }
+    let value = if (*reply).r#type != 0 {
+        let len = usize::try_from(xcb_get_property_value_length(reply)).unwrap_or(0);
+        Some(((*reply).r#type, std::slice::from_raw_parts(xcb_get_property_value(reply) as *const u8, len).to_vec()))
+    } else {
+        None
+    };
+    free(reply.cast());
+    value
+}
+
+// Reads a whole property, returning `None` if it isn't set or has the wrong type.
+unsafe fn get_property(
+    c: *mut xcb_connection_t,
+    window: xcb_window_t,
+    property: xcb_atom_t,
+    r#type: xcb_atom_t,
+) -> Option<Vec<u8>> {
+    let reply = xcb_get_property_reply(c, xcb_get_property(c, 0, window, property, r#type, 0, !0), std::ptr::null_mut());
+    if reply.is_null() {
+        return None
+    }
+    let value = if (*reply).r#type == r#type {
+        let len = usize::try_from(xcb_get_property_value_length(reply)).unwrap_or(0);
+        Some(std::slice::from_raw_parts(xcb_get_property_value(reply) as *const u8, len).to_vec())
+    } else {
+        None
+    };
+    free(reply.cast());
+    value
+}
+
+// Desktop environments publish the user's scaling setting as `Xft.dpi` in the X resources, which is what toolkits
+// go by, since the physical sizes RandR reports for monitors are too often wrong to rely on
+unsafe fn query_scale_factor(details: &ConnectionDetails) -> f64 {
+    let root = (*details.screen).root;
+    get_property(details.connection, root, XCB_ATOM_RESOURCE_MANAGER, XCB_ATOM_STRING)
+        .and_then(|resources| xft_dpi(&String::from_utf8_lossy(&resources)))
+        .map_or(1.0, |dpi| dpi / 96.0)
+}
+
+// Finds the `Xft.dpi` setting in the contents of a `RESOURCE_MANAGER` property, if it's there and makes sense.
+fn xft_dpi(resources: &str) -> Option<f64> {
+    resources.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        if name.trim() != "Xft.dpi" {
+            return None
+        }
+        value.trim().parse::<f64>().ok().filter(|dpi| *dpi > 0.0)
+    })
+}
+
+// Queries the server for an extension by name, returning `None` if it isn't present.
+// xcb_query_extension cannot generate errors, so we don't check
+unsafe fn query_extension(c: *mut xcb_connection_t, name: &str) -> Option<xcb_query_extension_reply_t> {
+    let reply = xcb_query_extension_reply(
+        c,
+        xcb_query_extension(c, name.len() as _, name.as_ptr().cast()),
+        std::ptr::null_mut(),
+    );
+    if reply.is_null() {
+        return None
+    }
+    let result = *reply;
+    free(reply.cast());
+    if result.present != 0 { Some(result) } else { None }
+}
+
 impl Drop for Connection {
     fn drop(&mut self) {
         let _ = self.details.extensions;
         unsafe {
+            let _ = xcb_destroy_window(self.details.connection, self.client_leader);
             let _ = xcb_flush(self.details.connection);
+            if self.xlib_compatible {
+                xcb_disconnect(self.details.connection);
+            }
             let _ = XCloseDisplay(self.details.display);
+            #[cfg(feature = "input")]
+            if !self.details.compose_table.is_null() {
+                xkb_compose_table_unref(self.details.compose_table);
+            }
         }
     }
 }
@@ -173,7 +954,7 @@ unsafe impl Send for Connection {}
 
 impl Atoms {
     unsafe fn new(connection: *mut xcb_connection_t) -> Result<Self, Error> {
-        const N_ATOMS: usize = 12;
+        const N_ATOMS: usize = 30;
         let mut atom_replies = [0 as c_uint; N_ATOMS];
         let mut atoms = [0 as xcb_atom_t; N_ATOMS];
         macro_rules! atom {
@@ -193,6 +974,24 @@ impl Atoms {
         atom!(9, "_NET_WM_STATE_MAXIMIZED_VERT");
         atom!(10, "_NET_WM_STATE_HIDDEN");
         atom!(11, "_MOTIF_WM_HINTS");
+        atom!(12, "WM_WINDOW_ROLE");
+        atom!(13, "WM_CLIENT_LEADER");
+        atom!(14, "SM_CLIENT_ID");
+        atom!(15, "_NET_WM_STATE_MODAL");
+        atom!(16, "WM_STATE");
+        atom!(17, "CLIPBOARD");
+        atom!(18, "TARGETS");
+        atom!(19, "INCR");
+        atom!(20, "_RAMEN_SELECTION");
+        atom!(21, "_GTK_SHOW_WINDOW_MENU");
+        atom!(22, "_NET_WM_DESKTOP");
+        atom!(23, "_NET_FRAME_EXTENTS");
+        atom!(24, "WM_CHANGE_STATE");
+        atom!(25, "_NET_WM_ICON");
+        atom!(26, "_NET_WM_STATE_FULLSCREEN");
+        atom!(27, "_NET_ACTIVE_WINDOW");
+        atom!(28, "_NET_WM_STATE_ABOVE");
+        atom!(29, "_NET_WM_WINDOW_OPACITY");
         for (r, seq) in atoms.iter_mut().zip(atom_replies.into_iter()) {
             let mut err: *mut xcb_generic_error_t = std::ptr::null_mut();
             let reply = xcb_intern_atom_reply(connection, seq, &mut err);
@@ -219,6 +1018,24 @@ impl Atoms {
             _net_wm_state_maximized_vert: atoms[9],
             _net_wm_state_hidden: atoms[10],
             _motif_wm_hints: atoms[11],
+            wm_window_role: atoms[12],
+            wm_client_leader: atoms[13],
+            sm_client_id: atoms[14],
+            _net_wm_state_modal: atoms[15],
+            wm_state: atoms[16],
+            clipboard: atoms[17],
+            targets: atoms[18],
+            incr: atoms[19],
+            _ramen_selection: atoms[20],
+            _gtk_show_window_menu: atoms[21],
+            _net_wm_desktop: atoms[22],
+            _net_frame_extents: atoms[23],
+            wm_change_state: atoms[24],
+            _net_wm_icon: atoms[25],
+            _net_wm_state_fullscreen: atoms[26],
+            _net_active_window: atoms[27],
+            _net_wm_state_above: atoms[28],
+            _net_wm_window_opacity: atoms[29],
         })
     }
 }
@@ -237,16 +1054,98 @@ pub(crate) struct WindowDetails {
     position: (i16, i16),
     size: (u16, u16),
     state_maximised: (bool, bool), // horz vert
-    state_minimised: bool,
+    state_net_hidden: bool, // _NET_WM_STATE_HIDDEN, minimised along with `state_icccm_iconic`
+    state_icccm_iconic: bool, // WM_STATE's IconicState, for Window Managers which don't set the above
+    state_fullscreen: bool,
+    size_limits: Mutex<SizeLimits>,
+    win_gravity: AtomicU8, // a `Gravity`, as last set by `set_size_with_anchor`
+    #[cfg(feature = "input")]
+    modifiers: Modifiers,
+    #[cfg(feature = "input")]
+    raw_modifiers: RawModifiers,
+    #[cfg(feature = "input")]
+    mouse_buttons: MouseButtons,
+    #[cfg(feature = "input")]
+    key_repeat: AtomicBool,
+    #[cfg(feature = "input")]
+    text_input: AtomicBool,
+    #[cfg(feature = "input")]
+    compose: ComposeState,
+    #[cfg(feature = "input")]
+    resync_input: AtomicBool,
+    #[cfg(feature = "input")]
+    keys_held: [u8; 32], // bits by keycode, like QueryKeymap's, only kept up to date without XInput2
+    #[cfg(feature = "input")]
+    pending_release: Option<(u8, xcb_timestamp_t, u16)>, // keycode, time and state of a core release held back
+    #[cfg(feature = "input")]
+    cursor_inside: bool,
+    cursor_barriers: Mutex<Option<[xcb_xfixes_barrier_t; 4]>>,
+    cursor_confined: AtomicBool, // as last set by `set_cursor_confined`, whether or not it's grabbed right now
+    pointer_grabbed: AtomicBool,
+    focused: bool,
+    frame_pacing: Mutex<FramePacing>,
+    monitors: Vec<Monitor>, // as of the last RandR notification, for telling what's changed
+    depth: u8,
+    visual: xcb_visualid_t,
+    screenid: u32,
+    scale_factor: f64, // as of the last change to the X resources
+    offscreen: bool,
+    initial_geometry: bool, // whether the first `poll_events` still has to report the size and position
+    wakeup: EventFd, // outlives the window's queue, which is taken out of the connection when the window's dropped
 }
 
-impl Window {
-    pub(crate) fn new(builder: window::Builder) -> Result<Self, Error> {
+impl WindowDetails {
+    fn minimised(&self) -> bool {
+        self.state_net_hidden || self.state_icccm_iconic
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct SizeLimits {
+    min: Option<(u16, u16)>,
+    max: Option<(u16, u16)>,
+    base: Option<(u16, u16)>,
+}
+
+#[derive(Default)]
+struct FramePacing {
+    enabled: bool,
+    armed: bool, // whether a NotifyMSC request is in flight, so we never have two running at once
+    last_ust: u64,
+    last_msc: u64,
+    frame_time: u64, // microseconds between vblanks, as last measured by either kind of completion, or 0
+    last_present: (u64, u64), // the UST and MSC of the last presented pixmap
+}
+
+// The average time between vblanks from two completions, in microseconds, or 0 if it can't be told
+fn vblank_interval((last_ust, last_msc): (u64, u64), (ust, msc): (u64, u64)) -> u64 {
+    match msc.checked_sub(last_msc) {
+        Some(frames) if frames != 0 && last_msc != 0 => ust.saturating_sub(last_ust) / frames,
+        _ => 0,
+    }
+}
+
+impl Window {
+    pub(crate) fn new(builder: window::Builder) -> Result<Self, Error> {
         unsafe {
-            let mut connection_mtx = mutex_lock(&builder.connection.0);
+            let mut connection_mtx = lock_connection(&builder.connection.0);
             let connection: &mut Connection = &mut *connection_mtx;
             let c = connection.details.connection;
             let hostname = connection.hostname.as_ref();
+            let details = match builder.screen {
+                None => connection.details,
+                Some(index) => u32::try_from(index).ok()
+                    .and_then(|index| connection.details.for_screen(index))
+                    .ok_or(Error::Invalid)?,
+            };
+
+            // These are checked before the window is made, so that there's nothing to clean up
+            if let Some((_, width, height)) = &builder.icon {
+                if !icon_fits_request(c, *width, *height) {
+                    return Err(Error::Invalid)
+                }
+            }
+            let wakeup = EventFd::new().ok_or(Error::SystemResources)?;
 
             // TODO: copy these from the builder when they're in there
             let (x, y) = builder.position.unwrap_or((0, 0));
@@ -260,24 +1159,13 @@ impl Window {
                 return Err(Error::SystemResources);
             }
 
-            // Clear the event queue, in case any events remain in it intended for a previous object with this xid we just claimed
-            let event = xcb_poll_for_event(c);
-            if !event.is_null() {
-                if let Some(window) = get_event_window(event, &connection.details) {
-                    if let Some(queue) = connection.event_buffer.get_mut(&window) {
-                        queue.push(event);
-                    }
-                }
-            }
-            loop {
-                let event = xcb_poll_for_queued_event(c);
-                if event.is_null() { break }
-                if let Some(window) = get_event_window(event, &connection.details) {
-                    if let Some(queue) = connection.event_buffer.get_mut(&window) {
-                        queue.push(event);
-                    }
-                }
-            }
+            // Clear the event queue, in case any events remain in it intended for a previous object with this xid we just
+            // claimed. Everything else is stored for the window it's for, through the same `pull_events` that
+            // `poll_events` uses, so events for our other windows aren't lost. Every live window's queue exists by
+            // now, as they're inserted before `Window::new` returns, and we've held the connection lock since.
+            // This window's queue is inserted once it's made, and nothing reads events off the connection before
+            // then, so none of its own events are dropped either.
+            pull_events(None, &connection.details, &mut connection.event_buffer, &mut connection.clipboard);
 
             // Create the new X window
             const REGULAR_MASK: u32 = XCB_EVENT_MASK_STRUCTURE_NOTIFY | XCB_EVENT_MASK_PROPERTY_CHANGE;
@@ -286,54 +1174,76 @@ impl Window {
             const EVENT_MASK: u32 = XCB_EVENT_MASK_BUTTON_PRESS | REGULAR_MASK;
             #[cfg(not(feature = "input"))]
             const EVENT_MASK: u32 = XCB_EVENT_MASK_FOCUS_CHANGE | REGULAR_MASK;
-            const VALUE_MASK: u32 = XCB_CW_EVENT_MASK;
+            // Without XInput2, the rest of the input has to come from the core protocol too
+            #[cfg(feature = "input")]
+            const CORE_INPUT_MASK: u32 = XCB_EVENT_MASK_KEY_PRESS | XCB_EVENT_MASK_KEY_RELEASE
+                | XCB_EVENT_MASK_BUTTON_RELEASE | XCB_EVENT_MASK_ENTER_WINDOW | XCB_EVENT_MASK_LEAVE_WINDOW
+                | XCB_EVENT_MASK_POINTER_MOTION | XCB_EVENT_MASK_FOCUS_CHANGE;
+            #[cfg(feature = "input")]
+            let event_mask = match connection.details.extensions.xinput {
+                Some(_) => EVENT_MASK,
+                None => EVENT_MASK | CORE_INPUT_MASK,
+            };
+            #[cfg(not(feature = "input"))]
+            let event_mask = EVENT_MASK;
+            const VALUE_MASK: u32 = XCB_CW_BIT_GRAVITY | XCB_CW_BACKING_STORE | XCB_CW_EVENT_MASK;
+            const BACKING_STORE_WHEN_MAPPED: u32 = 1;
 
-            let cmap = builder.visual.map(|vi| {
-                let cmap = xcb_generate_id(c);
-                _ = xcb_create_colormap(c, 0, cmap, (*connection.details.screen).root, vi);
-                _ = xcb_flush(c);
-                cmap
-            });
-            let value_mask = if cmap.is_some() { VALUE_MASK|XCB_CW_COLORMAP } else { VALUE_MASK };
-            let value_list = if let Some(c) = cmap { (&[EVENT_MASK, c]).to_vec() } else { (&[EVENT_MASK]).to_vec() };
+            // Copying from the parent would give the root window's depth and visual, but they're useful to know
+            let (visual, depth) = match builder.visual {
+                Some((visual, depth, _)) => (visual, depth),
+                None => ((*details.screen).root_visual, builder.depth.unwrap_or((*details.screen).root_depth)),
+            };
+
+            // Values are in the same order as their bits in the mask
+            let (value_mask, value_list) = match builder.visual {
+                // A border pixel is needed too, or a depth other than the root's will fail with `Match`
+                Some((_, _, colourmap)) => (
+                    XCB_CW_BORDER_PIXEL | VALUE_MASK | XCB_CW_COLORMAP,
+                    vec![0, builder.bit_gravity as u32, BACKING_STORE_WHEN_MAPPED, event_mask, colourmap],
+                ),
+                None => (VALUE_MASK, vec![builder.bit_gravity as u32, BACKING_STORE_WHEN_MAPPED, event_mask]),
+            };
 
             let create_error = xcb_request_check(c, xcb_create_window_checked(
                 c,
-                builder.depth.unwrap_or(XCB_COPY_FROM_PARENT as _),
+                depth,
                 xid,
-                (*connection.details.screen).root,
+                (*details.screen).root,
                 x,
                 y,
                 width,
                 height,
                 0,
                 XCB_WINDOW_CLASS_INPUT_OUTPUT,
-                builder.visual.unwrap_or(XCB_COPY_FROM_PARENT.into()),
+                visual,
                 value_mask,
                 &value_list[0],
             ));
             if !create_error.is_null() {
                 // Reasons CreateWindow may fail are:
                 // Alloc - maps to Error::SystemResources
-                // Colormap - we don't currently pass a colormap
+                // Colormap - only if the user's colormap doesn't exist
                 // Cursor - we do not pass a Cursor
                 // IDChoice - we got our ID straight from xcb_generate_id and didn't use it for anything else
-                // Match - bad configuration of user params, so maps to Error::Invalid
+                // Match - bad configuration of user params, such as a depth that doesn't suit the visual
                 // Pixmap - we don't currently pass a pixmap
-                // Value - bad value for a user param, so maps to Error::Invalid
+                // Value - bad value for a user param
                 // Window - we just created that XID so that's not possible
-                let errno = (*create_error).error_code;
+                // Anything but Alloc is most likely the user's fault, so the details are passed on to help find it
+                let err = &*create_error;
+                let (code, major, minor) = (err.error_code, err.major_code, err.minor_code);
                 free(create_error.cast());
-                if errno as c_int == XCB_ALLOC {
+                if code as c_int == XCB_ALLOC {
                     return Err(Error::SystemResources);
                 } else {
-                    return Err(Error::Invalid);
+                    return Err(Error::X11 { major, minor, code });
                 }
             }
 
             // Select xinput events
             #[cfg(feature = "input")]
-            {
+            if connection.details.extensions.xinput.is_some() {
                 // xcb_input_xi_select_events cannot generate errors so we use _checked and discard it
                 #[repr(C)]
                 struct XiMask {
@@ -353,94 +1263,196 @@ impl Window {
                 xcb_discard_reply(c, xcb_input_xi_select_events_checked(c, xid, 1, (&mut mask.head) as _));
             }
 
-            // Setup WM_PROTOCOLS
-            let window_atoms = [
-                connection.details.atoms.wm_delete_window,
-                connection.details.atoms._net_wm_ping,
-            ];
-            let _ = xcb_change_property(
-                c,
-                XCB_PROP_MODE_REPLACE,
-                xid,
-                connection.details.atoms.wm_protocols,
-                XCB_ATOM_ATOM,
-                32,
-                window_atoms.len() as _,
-                (&window_atoms) as *const xcb_window_t as _,
-            );
-
-            // Try to write the requested window title to the WM_NAME and _NET_WM_NAME properties
-            // Note: multibyte characters won't render correctly in WM_NAME, but any modern and worthwhile WM will
-            // prioritise using _NET_WM_NAME which is UTF-8 as standard, that's why it's better to write both.
-            let title = builder.title.as_ref();
-            internal_set_title(c, xid, &connection.details.atoms, title);
-
-            // If hostname is known, get PID of current process and write that to _NET_WM_PID
-            // But don't write either of these properties if hostname is not known, because:
-            // "If _NET_WM_PID is set, the ICCCM-specified property WM_CLIENT_MACHINE MUST also be set." - EWMH spec
-            if let Some(hostname) = hostname {
-                let pid = getpid();
+            // None of the Window Manager properties are written for an offscreen window, since it's never mapped
+            if !builder.offscreen {
+                // Setup WM_PROTOCOLS
+                let window_atoms = [
+                    connection.details.atoms.wm_delete_window,
+                    connection.details.atoms._net_wm_ping,
+                ];
                 let _ = xcb_change_property(
                     c,
                     XCB_PROP_MODE_REPLACE,
                     xid,
-                    connection.details.atoms._net_wm_pid,
-                    XCB_ATOM_CARDINAL,
+                    connection.details.atoms.wm_protocols,
+                    XCB_ATOM_ATOM,
                     32,
-                    1,
-                    (&pid) as *const i32 as _,
+                    window_atoms.len() as _,
+                    (&window_atoms) as *const xcb_window_t as _,
                 );
 
-                let _ = xcb_change_property(
+                // Try to write the requested window title to the WM_NAME and _NET_WM_NAME properties
+                // Note: multibyte characters won't render correctly in WM_NAME, but any modern and worthwhile WM will
+                // prioritise using _NET_WM_NAME which is UTF-8 as standard, that's why it's better to write both.
+                let title = builder.title.as_ref();
+                internal_set_title(c, xid, &connection.details.atoms, title);
+
+                // If hostname is known, get PID of current process and write that to _NET_WM_PID
+                // But don't write either of these properties if hostname is not known, because:
+                // "If _NET_WM_PID is set, the ICCCM-specified property WM_CLIENT_MACHINE MUST also be set." - EWMH spec
+                // For the same reason, the user turning this off skips both of them
+                if let Some(hostname) = hostname.filter(|_| builder.process_info) {
+                    let pid = builder.pid_override.map_or_else(|| getpid(), |pid| pid as i32);
+                    let _ = xcb_change_property(
+                        c,
+                        XCB_PROP_MODE_REPLACE,
+                        xid,
+                        connection.details.atoms._net_wm_pid,
+                        XCB_ATOM_CARDINAL,
+                        32,
+                        1,
+                        (&pid) as *const i32 as _,
+                    );
+
+                    let _ = xcb_change_property(
+                        c,
+                        XCB_PROP_MODE_REPLACE,
+                        xid,
+                        connection.details.atoms.wm_client_machine,
+                        XCB_ATOM_STRING,
+                        8,
+                        hostname.len() as _,
+                        hostname.as_ptr().cast(),
+                    );
+                }
+
+                // Set class name. ICCCM lets the user override the instance name with `RESOURCE_NAME`,
+                // so that comes first
+                let mut instance = "unknown".to_string();
+                if let Some(name) = std::env::var_os("RESOURCE_NAME") {
+                    instance = name.as_os_str().to_string_lossy().into_owned();
+                } else if let Some(name) = connection.application_name.as_ref() {
+                    instance = name.clone();
+                } else {
+                    if let Some(argv0) = std::env::args_os().next() {
+                        let path = std::path::Path::new(argv0.as_os_str());
+                        if let Some(basename) = path.file_name() {
+                            instance = basename.to_string_lossy().into_owned();
+                        }
+                    }
+                }
+                let wm_class = format!("{}\0{}\0", instance, builder.class_name.as_ref());
+                _ = xcb_change_property(
                     c,
                     XCB_PROP_MODE_REPLACE,
                     xid,
-                    connection.details.atoms.wm_client_machine,
+                    XCB_ATOM_WM_CLASS,
                     XCB_ATOM_STRING,
                     8,
-                    hostname.len() as _,
-                    hostname.as_ptr().cast(),
+                    wm_class.len() as _,
+                    wm_class.as_ptr().cast(),
                 );
-            }
 
-            // Set class name
-            let mut instance = "unknown".to_string();
-            if let Some(name) = std::env::var_os("RESOURCE_NAME") {
-                instance = name.as_os_str().to_string_lossy().into_owned();
-            } else {
-                if let Some(argv0) = std::env::args_os().next() {
-                    let path = std::path::Path::new(argv0.as_os_str());
-                    if let Some(basename) = path.file_name() {
-                        instance = basename.to_string_lossy().into_owned();
-                    }
+                if let Some((rgba, width, height)) = &builder.icon {
+                    set_net_wm_icon(c, &connection.details.atoms, xid, rgba, *width, *height);
+                }
+
+                // Set session management properties
+                if let Some(role) = builder.window_role.as_deref() {
+                    _ = xcb_change_property(
+                        c,
+                        XCB_PROP_MODE_REPLACE,
+                        xid,
+                        connection.details.atoms.wm_window_role,
+                        XCB_ATOM_STRING,
+                        8,
+                        role.len() as _,
+                        role.as_ptr().cast(),
+                    );
+                }
+                _ = xcb_change_property(
+                    c,
+                    XCB_PROP_MODE_REPLACE,
+                    xid,
+                    connection.details.atoms.wm_client_leader,
+                    XCB_ATOM_WINDOW,
+                    32,
+                    1,
+                    (&connection.client_leader) as *const _ as _,
+                );
+
+                // Dialogs are marked transient for their parent
+                if let Some(parent) = builder.modal_for {
+                    _ = xcb_change_property(
+                        c,
+                        XCB_PROP_MODE_REPLACE,
+                        xid,
+                        XCB_ATOM_WM_TRANSIENT_FOR,
+                        XCB_ATOM_WINDOW,
+                        32,
+                        1,
+                        (&parent) as *const _ as _,
+                    );
+                }
+
+                // The initial state can be set directly before mapping, which saves the Window Manager showing the
+                // window normally first and then changing it
+                let mut initial_state = Vec::with_capacity(5);
+                if builder.modal_for.is_some() {
+                    initial_state.push(connection.details.atoms._net_wm_state_modal);
+                }
+                if builder.maximised && builder.style.visible {
+                    initial_state.push(connection.details.atoms._net_wm_state_maximized_horz);
+                    initial_state.push(connection.details.atoms._net_wm_state_maximized_vert);
+                }
+                if builder.fullscreen && builder.style.visible {
+                    initial_state.push(connection.details.atoms._net_wm_state_fullscreen);
+                }
+                if builder.always_on_top {
+                    initial_state.push(connection.details.atoms._net_wm_state_above);
+                }
+                if !initial_state.is_empty() {
+                    _ = xcb_change_property(
+                        c,
+                        XCB_PROP_MODE_REPLACE,
+                        xid,
+                        connection.details.atoms._net_wm_state,
+                        XCB_ATOM_ATOM,
+                        32,
+                        initial_state.len() as u32,
+                        initial_state.as_ptr().cast(),
+                    );
+                }
+                if builder.minimised && builder.style.visible {
+                    // The flags say which of WM_HINTS' nine fields are set, and the third is the initial state
+                    let mut hints = [0u32; 9];
+                    hints[0] = ICCCM_WM_HINT_STATE;
+                    hints[2] = ICCCM_ICONIC_STATE;
+                    _ = xcb_change_property(
+                        c,
+                        XCB_PROP_MODE_REPLACE,
+                        xid,
+                        XCB_ATOM_WM_HINTS,
+                        XCB_ATOM_WM_HINTS,
+                        32,
+                        hints.len() as u32,
+                        hints.as_ptr().cast(),
+                    );
                 }
             }
-            let wm_class = format!("{}\0{}\0", instance, builder.class_name.as_ref());
-            _ = xcb_change_property(
-                c,
-                XCB_PROP_MODE_REPLACE,
-                xid,
-                XCB_ATOM_WM_CLASS,
-                XCB_ATOM_STRING,
-                8,
-                wm_class.len() as _,
-                wm_class.as_ptr().cast(),
-            );
+
+            // Subscribe to Present's completion events, which drive `Event::RedrawRequested` and `Event::PresentComplete`
+            if connection.details.extensions.present.is_some() {
+                let eid = xcb_generate_id(c);
+                _ = xcb_present_select_input(c, eid, xid, XCB_PRESENT_EVENT_MASK_COMPLETE_NOTIFY);
+            }
+
+            // Subscribe to RandR's configuration changes, which drive the monitor hotplug events
+            if connection.details.extensions.randr.is_some() {
+                let mask = XCB_RANDR_NOTIFY_MASK_SCREEN_CHANGE | XCB_RANDR_NOTIFY_MASK_OUTPUT_CHANGE;
+                _ = xcb_randr_select_input(c, xid, mask);
+            }
 
             // Map window to screen
-            if builder.style.visible {
+            if builder.style.visible && !builder.offscreen {
                 let _ = xcb_map_window(c, xid);
-
-                // Set maximised (this needs to be done after map)
-                if builder.maximised {
-                    internal_set_maximised(c, xid, &connection.details, true);
-                }
             }
 
             // Now we'll insert an entry into the EVENT_QUEUE hashmap for this window we've created.
             // We do this even if the queue probably won't be used, as it's the soundest way to ensure
             // memory gets cleaned up.
-            let _ = connection.event_buffer.insert(xid, Vec::with_capacity(QUEUE_SIZE));
+            let queue = EventQueue { events: Vec::with_capacity(QUEUE_SIZE), wakeup: wakeup.0 };
+            let _ = connection.event_buffer.insert(xid, queue);
 
             // TODO: This "returns <= 0 on error", how is that value significant? Is it -EINVAL type thing?
             if xcb_flush(c) <= 0 {
@@ -448,7 +1460,9 @@ impl Window {
                 return Err(Error::Unknown)
             }
 
-            let root = (*connection.details.screen).root;
+            let root = (*details.screen).root;
+            let monitors = query_monitors(&details);
+            let scale_factor = query_scale_factor(&connection.details);
             std::mem::drop(connection_mtx);
 
             let window = Window {
@@ -461,202 +1475,1090 @@ impl Window {
                     style: Arc::new(Mutex::new(builder.style)),
                     size: (width, height),
                     state_maximised: (false, false),
-                    state_minimised: false,
+                    state_net_hidden: false,
+                    state_icccm_iconic: false,
+                    state_fullscreen: false,
+                    size_limits: Mutex::new(SizeLimits {
+                        min: builder.min_size,
+                        max: builder.max_size,
+                        base: None,
+                    }),
+                    win_gravity: AtomicU8::new(Gravity::NorthWest as u8),
+                    #[cfg(feature = "input")]
+                    modifiers: Modifiers::new(),
+                    #[cfg(feature = "input")]
+                    raw_modifiers: RawModifiers::default(),
+                    #[cfg(feature = "input")]
+                    mouse_buttons: MouseButtons::new(),
+                    #[cfg(feature = "input")]
+                    key_repeat: AtomicBool::new(true),
+                    #[cfg(feature = "input")]
+                    text_input: AtomicBool::new(true),
+                    #[cfg(feature = "input")]
+                    compose: ComposeState::new(details.compose_table),
+                    #[cfg(feature = "input")]
+                    resync_input: AtomicBool::new(false),
+                    #[cfg(feature = "input")]
+                    keys_held: [0; 32],
+                    #[cfg(feature = "input")]
+                    pending_release: None,
+                    #[cfg(feature = "input")]
+                    cursor_inside: false,
+                    cursor_barriers: Mutex::new(None),
+                    cursor_confined: AtomicBool::new(false),
+                    pointer_grabbed: AtomicBool::new(false),
+                    focused: false,
+                    frame_pacing: Mutex::new(FramePacing::default()),
+                    monitors,
+                    depth,
+                    visual,
+                    screenid: details.screenid,
+                    scale_factor,
+                    offscreen: builder.offscreen,
+                    initial_geometry: true,
+                    wakeup,
+                },
+            };
+
+            // These are Window Manager properties too, like the ones skipped above
+            if !window.details.offscreen {
+                let connection_mtx = lock_connection(&window.connection.0);
+                let fields = MWM_HINTS_FUNCTIONS | MWM_HINTS_DECORATIONS;
+                set_mwm_hints(c, &connection_mtx.details, &window.details, fields);
+                set_wm_normal_hints(c, &window.details, window.details.size);
+                std::mem::drop(connection_mtx);
+            }
+
+            Ok(window)
+        }
+    }
+
+    pub(crate) fn events(&self) -> &[Event] {
+        &self.details.event_buffer
+    }
+
+    pub(crate) fn drain_events(&mut self) -> Vec<Event> {
+        self.details.event_buffer.drain(..).collect()
+    }
+
+    pub(crate) fn poll_events(&mut self) {
+        // First: lock the global event queue, which is used as backup storage for events
+        // which have been pulled but are not immediately relevant
+        let mut connection = lock_connection(&self.connection.0);
+        unsafe { Self::poll_events_locked(&mut self.details, &mut connection) }
+    }
+
+    // Polls events like `poll_events`, unless another thread is using the connection
+    pub(crate) fn try_poll_events(&mut self) -> bool {
+        match try_lock_connection(&self.connection.0) {
+            Some(mut connection) => {
+                unsafe { Self::poll_events_locked(&mut self.details, &mut connection) };
+                true
+            },
+            None => false,
+        }
+    }
+
+    // The body of `poll_events`, for once the connection is locked
+    unsafe fn poll_events_locked(window_details: &mut WindowDetails, connection: &mut Connection) {
+        let Connection {
+            details: connection_details,
+            event_buffer: map,
+            clipboard,
+            ..
+        } = connection;
+        let c = connection_details.connection;
+
+        // Clear our event buffer of the previous set of events
+        window_details.event_buffer.clear();
+
+        // Fill our event buffer with any events which may have been stored in the global event queue,
+        // also clearing them from the global queue
+        // Note: this queue SHOULD always exist, but it's possible some bad or malicious user code might get a
+        // `None` result, so it's better to check and take no action if there's no queue to copy from...
+        if let Some(queue) = map.get_mut(&window_details.handle) {
+            for event in queue.events.iter().copied() {
+                process_event(event, window_details, connection_details);
+            }
+            queue.events.clear();
+        }
+
+        pull_events(Some(&mut *window_details), connection_details, map, clipboard);
+
+        // The press which would make this part of a repeat is sent along with it, so if it isn't here by now,
+        // it isn't coming
+        #[cfg(feature = "input")]
+        if let Some((keycode, _, state)) = window_details.pending_release.take() {
+            release_core_key(window_details, &connection_details.for_window(window_details), keycode, state);
+        }
+
+        // ConfigureNotify only reports changes, so the geometry the Window Manager granted at creation would
+        // otherwise go unreported if it happened to be the one requested. Whatever wasn't reported is asked for,
+        // since a reparenting Window Manager may have moved the window without a ConfigureNotify for it yet.
+        if std::mem::take(&mut window_details.initial_geometry) {
+            let buffer = &window_details.event_buffer;
+            let resized = buffer.iter().any(|event| matches!(event, Event::Resize(_)));
+            let moved = buffer.iter().any(|event| matches!(event, Event::Move(_)));
+            if !(resized && moved) {
+                let root = (*connection_details.for_window(window_details).screen).root;
+                if let Some((position, size)) = query_geometry(c, window_details.handle, root) {
+                    window_details.position = position;
+                    window_details.size = size;
+                }
+            }
+            let mut initial = Vec::with_capacity(2);
+            if !resized {
+                initial.push(Event::Resize(window_details.size));
+            }
+            if !moved {
+                initial.push(Event::Move(window_details.position));
+            }
+            _ = window_details.event_buffer.splice(0..0, initial);
+        }
+
+        #[cfg(feature = "input")]
+        if window_details.resync_input.swap(false, Ordering::Relaxed) {
+            resync_input(connection_details, window_details);
+        }
+
+        // Also retries a grab which failed because someone else had the pointer grabbed, such as the Window
+        // Manager while it handles the click that focused us
+        update_pointer_grab(c, window_details);
+    }
+
+    // Polls events until there's at least one, or until `deadline` passes
+    pub(crate) fn wait_events_until(&mut self, deadline: Option<Instant>) {
+        loop {
+            // Another window's thread may read our events off the connection and store them in our queue, which
+            // signals this. It's cleared before polling, so anything stored after the poll still wakes us up.
+            self.details.wakeup.clear();
+            self.poll_events();
+            if !self.details.event_buffer.is_empty() {
+                return
+            }
+            let timeout = match deadline {
+                Some(deadline) => match deadline.saturating_duration_since(Instant::now()) {
+                    Duration::ZERO => return,
+                    remaining => remaining.as_millis().min(c_int::MAX as u128 - 1) as c_int + 1,
                 },
+                None => -1,
+            };
+            let fd = {
+                let connection = lock_connection(&self.connection.0);
+                unsafe {
+                    if xcb_connection_has_error(connection.details.connection) != 0 {
+                        return
+                    }
+                    xcb_get_file_descriptor(connection.details.connection)
+                }
             };
+            let mut fds = [
+                libc::pollfd { fd, events: libc::POLLIN, revents: 0 },
+                libc::pollfd { fd: self.details.wakeup.0, events: libc::POLLIN, revents: 0 },
+            ];
+            unsafe {
+                _ = libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout);
+            }
+        }
+    }
+
+    pub(crate) fn set_cursor_confine_rect(&self, rect: Option<Rectangle>) {
+        let mut connection_ = lock_connection(&self.connection.0);
+        let connection = &mut connection_;
+        if !connection.details.extensions.xfixes {
+            return
+        }
+        let c = connection.details.connection;
+        let mut barriers = mutex_lock(&self.details.cursor_barriers);
+        unsafe {
+            if let Some(old) = barriers.take() {
+                for barrier in old {
+                    _ = xcb_xfixes_delete_pointer_barrier(c, barrier);
+                }
+            }
+            if let Some(Rectangle { x, y, width, height }) = rect {
+                // Barriers are lines on the root window, so we box the area in with four of them,
+                // each one letting the pointer pass through only in the direction heading into the box
+                let root = (*connection.details.for_window(&self.details).screen).root;
+                let (x1, y1) = (x.max(0) as u16, y.max(0) as u16);
+                let (x2, y2) = (x1.saturating_add(width), y1.saturating_add(height));
+                let edges = [
+                    (x1, y1, x1, y2, XCB_XFIXES_BARRIER_DIRECTIONS_POSITIVE_X),
+                    (x2, y1, x2, y2, XCB_XFIXES_BARRIER_DIRECTIONS_NEGATIVE_X),
+                    (x1, y1, x2, y1, XCB_XFIXES_BARRIER_DIRECTIONS_POSITIVE_Y),
+                    (x1, y2, x2, y2, XCB_XFIXES_BARRIER_DIRECTIONS_NEGATIVE_Y),
+                ];
+                let mut ids = [0; 4];
+                for (id, (x1, y1, x2, y2, directions)) in ids.iter_mut().zip(edges) {
+                    *id = xcb_generate_id(c);
+                    _ = xcb_xfixes_create_pointer_barrier(c, *id, root, x1, y1, x2, y2, directions, 0, std::ptr::null());
+                }
+                *barriers = Some(ids);
+            }
+            _ = xcb_flush(c);
+        }
+    }
+
+    pub(crate) fn set_cursor_confined(&self, confined: bool) {
+        self.details.cursor_confined.store(confined, Ordering::Relaxed);
+        let connection = lock_connection(&self.connection.0);
+        unsafe {
+            update_pointer_grab(connection.details.connection, &self.details);
+        }
+    }
+
+    pub(crate) fn trigger_caption_action(&self, action: CaptionAction) {
+        let connection = lock_connection(&self.connection.0);
+        unsafe {
+            let details = connection.details.for_window(&self.details);
+            let c = details.connection;
+            match action {
+                CaptionAction::Maximise => internal_set_maximised(c, self.details.handle, &details, true),
+                CaptionAction::Restore => internal_set_maximised(c, self.details.handle, &details, false),
+                CaptionAction::Menu => {
+                    // The menu is shown where the pointer is, as if the titlebar had been right-clicked
+                    let cookie = xcb_query_pointer(c, self.details.handle);
+                    let reply = xcb_query_pointer_reply(c, cookie, std::ptr::null_mut());
+                    if reply.is_null() {
+                        return
+                    }
+                    let (x, y) = ((*reply).root_x, (*reply).root_y);
+                    free(reply.cast());
+                    const VIRTUAL_CORE_POINTER: u32 = 2; // the XInput device ID GTK would send
+                    let client_message = xcb_client_message_event_t {
+                        response_type: XCB_CLIENT_MESSAGE,
+                        format: 32,
+                        sequence: 0,
+                        window: self.details.handle,
+                        r#type: details.atoms._gtk_show_window_menu,
+                        client_data: ClientData { data32: [VIRTUAL_CORE_POINTER, x as u32, y as u32, 0, 0] },
+                    };
+                    xcb_discard_reply(c, xcb_send_event_checked(
+                        c,
+                        0,
+                        (*details.screen).root,
+                        XCB_EVENT_MASK_SUBSTRUCTURE_NOTIFY | XCB_EVENT_MASK_SUBSTRUCTURE_REDIRECT,
+                        (&client_message as *const _) as *const i8,
+                    ));
+                },
+            }
+            _ = xcb_flush(c);
+        }
+    }
+
+    pub(crate) fn desktop(&self) -> Option<u32> {
+        let connection = lock_connection(&self.connection.0);
+        unsafe {
+            let (c, atom) = (connection.details.connection, connection.details.atoms._net_wm_desktop);
+            let value = get_property(c, self.details.handle, atom, XCB_ATOM_CARDINAL)?;
+            Some(u32::from_ne_bytes(value.get(..4)?.try_into().ok()?))
+        }
+    }
+
+    pub(crate) fn set_desktop(&self, index: u32) {
+        let connection = lock_connection(&self.connection.0);
+        unsafe {
+            let details = connection.details.for_window(&self.details);
+            let c = details.connection;
+            let atoms = &details.atoms;
+            if get_property(c, self.details.handle, atoms.wm_state, atoms.wm_state).is_none() {
+                // Without a `WM_STATE`, the window is withdrawn, and the spec has us set the property ourselves for
+                // the Window Manager to read when it's mapped
+                _ = xcb_change_property(
+                    c,
+                    XCB_PROP_MODE_REPLACE,
+                    self.details.handle,
+                    atoms._net_wm_desktop,
+                    XCB_ATOM_CARDINAL,
+                    32,
+                    1,
+                    (&index as *const u32).cast(),
+                );
+            } else {
+                const SOURCE_APPLICATION: u32 = 1;
+                let client_message = xcb_client_message_event_t {
+                    response_type: XCB_CLIENT_MESSAGE,
+                    format: 32,
+                    sequence: 0,
+                    window: self.details.handle,
+                    r#type: atoms._net_wm_desktop,
+                    client_data: ClientData { data32: [index, SOURCE_APPLICATION, 0, 0, 0] },
+                };
+                xcb_discard_reply(c, xcb_send_event_checked(
+                    c,
+                    0,
+                    (*details.screen).root,
+                    XCB_EVENT_MASK_SUBSTRUCTURE_NOTIFY | XCB_EVENT_MASK_SUBSTRUCTURE_REDIRECT,
+                    (&client_message as *const _) as *const i8,
+                ));
+            }
+            _ = xcb_flush(c);
+        }
+    }
+
+    pub(crate) fn move_to_monitor(&self, monitor: &Monitor) {
+        let monitors = lock_connection(&self.connection.0).monitors();
+        let (x, y) = self.details.position;
+        let (width, height) = self.details.size;
+        let position = monitor::place_on_monitor(Rectangle { x, y, width, height }, &monitors, monitor);
+        self.set_position(position);
+    }
+
+    pub(crate) fn set_frame_pacing(&self, enabled: bool) {
+        let connection = lock_connection(&self.connection.0);
+        if connection.details.extensions.present.is_none() {
+            return
+        }
+        let mut pacing = mutex_lock(&self.details.frame_pacing);
+        pacing.enabled = enabled;
+        if enabled && !pacing.armed {
+            // Asks for a notification at the next vblank, the rest are requested as each one arrives
+            let c = connection.details.connection;
+            unsafe {
+                _ = xcb_present_notify_msc(c, self.details.handle, 0, 0, 1, 0);
+                _ = xcb_flush(c);
+            }
+            pacing.armed = true;
+        }
+    }
+
+    pub(crate) fn last_frame_time(&self) -> Option<Duration> {
+        let pacing = mutex_lock(&self.details.frame_pacing);
+        (pacing.frame_time != 0).then(|| Duration::from_micros(pacing.frame_time))
+    }
+
+    pub(crate) fn request_focus(&self) {
+        if self.details.offscreen {
+            return
+        }
+        let connection = lock_connection(&self.connection.0);
+        unsafe {
+            let details = connection.details.for_window(&self.details);
+            let c = details.connection;
+            if connection.supports(NetWmFeature::ActiveWindow) {
+                // The 1 says the request is from a normal application, rather than a pager acting for the user,
+                // which is what Window Managers go by when deciding whether to allow it
+                let client_message = xcb_client_message_event_t {
+                    response_type: XCB_CLIENT_MESSAGE,
+                    format: 32,
+                    sequence: 0,
+                    window: self.details.handle,
+                    r#type: details.atoms._net_active_window,
+                    client_data: ClientData { data32: [1, XCB_CURRENT_TIME, 0, 0, 0] },
+                };
+                xcb_discard_reply(c, xcb_send_event_checked(
+                    c,
+                    0,
+                    (*details.screen).root,
+                    XCB_EVENT_MASK_SUBSTRUCTURE_NOTIFY | XCB_EVENT_MASK_SUBSTRUCTURE_REDIRECT,
+                    (&client_message as *const _) as *const i8,
+                ));
+            } else {
+                // With nothing to ask, the window is raised and focused directly. The server refuses to focus a
+                // window which isn't viewable, so that error is thrown away.
+                let stack_mode = XCB_STACK_MODE_ABOVE;
+                let mask = XCB_CONFIG_WINDOW_STACK_MODE;
+                _ = xcb_configure_window(c, self.details.handle, mask, (&stack_mode as *const u32).cast());
+                xcb_discard_reply(
+                    c,
+                    xcb_set_input_focus_checked(c, XCB_INPUT_FOCUS_PARENT, self.details.handle, XCB_CURRENT_TIME),
+                );
+            }
+            _ = xcb_flush(c);
+        }
+    }
+
+    pub(crate) fn set_opacity(&self, opacity: f32) {
+        let connection = lock_connection(&self.connection.0);
+        let c = connection.details.connection;
+        let atom = connection.details.atoms._net_wm_window_opacity;
+        unsafe {
+            // Compositors treat a missing property as opaque, and can skip blending the window altogether then
+            if opacity >= 1.0 {
+                _ = xcb_delete_property(c, self.details.handle, atom);
+            } else {
+                let value = opacity_cardinal(opacity);
+                _ = xcb_change_property(
+                    c,
+                    XCB_PROP_MODE_REPLACE,
+                    self.details.handle,
+                    atom,
+                    XCB_ATOM_CARDINAL,
+                    32,
+                    1,
+                    (&value as *const u32).cast(),
+                );
+            }
+            _ = xcb_flush(c);
+        }
+    }
+
+    pub(crate) fn set_urgent(&self, urgent: bool) {
+        let connection = lock_connection(&self.connection.0);
+        let c = connection.details.connection;
+        unsafe {
+            // WM_HINTS has nine fields, and the flags say which are set, so the others have to be kept as they are
+            let mut hints = [0u32; 9];
+            if let Some(value) = get_property(c, self.details.handle, XCB_ATOM_WM_HINTS, XCB_ATOM_WM_HINTS) {
+                for (field, x) in hints.iter_mut().zip(value.chunks_exact(4)) {
+                    *field = u32::from_ne_bytes([x[0], x[1], x[2], x[3]]);
+                }
+            }
+            if urgent {
+                hints[0] |= ICCCM_WM_HINT_X_URGENCY;
+            } else {
+                hints[0] &= !ICCCM_WM_HINT_X_URGENCY;
+            }
+            _ = xcb_change_property(
+                c,
+                XCB_PROP_MODE_REPLACE,
+                self.details.handle,
+                XCB_ATOM_WM_HINTS,
+                XCB_ATOM_WM_HINTS,
+                32,
+                hints.len() as u32,
+                hints.as_ptr().cast(),
+            );
+            _ = xcb_flush(c);
+        }
+    }
+
+    #[cfg(feature = "input")]
+    pub(crate) fn set_key_repeat(&self, enabled: bool) {
+        self.details.key_repeat.store(enabled, Ordering::Relaxed);
+    }
+
+    #[cfg(feature = "input")]
+    pub(crate) fn set_text_input(&self, enabled: bool) {
+        self.details.text_input.store(enabled, Ordering::Relaxed);
+    }
+
+    #[cfg(feature = "input")]
+    pub(crate) fn resync_input_state(&self) {
+        // The events are generated by the next `poll_events`, which is the only thing allowed to push them
+        self.details.resync_input.store(true, Ordering::Relaxed);
+    }
+
+    pub(crate) fn keep_alive(&self) {
+        unsafe {
+            let mut connection_ = lock_connection(&self.connection.0);
+            let Connection {
+                details: connection_details,
+                event_buffer: map,
+                clipboard,
+                ..
+            } = &mut *connection_;
+            let c = connection_details.connection;
+            let _ = xcb_flush(c);
+
+            // Pings are answered straight away, and everything else is stored for `poll_events` to process later.
+            // Pings don't cause any `Event`s, so nothing is lost by taking them out of the queue here.
+            let mut event = xcb_poll_for_event(c);
+            while !event.is_null() {
+                if is_ping(event, connection_details) {
+                    // Pings are only sent to our own windows, which are almost certainly all on this one's screen
+                    let details = connection_details.for_window(&self.details);
+                    answer_ping(&mut *(event as *mut xcb_client_message_event_t), &details);
+                    free(event.cast());
+                } else {
+                    route_event(event, None, connection_details, map, clipboard);
+                }
+                event = xcb_poll_for_queued_event(c);
+            }
+        }
+    }
+
+    pub(crate) fn sync(&self) {
+        let connection = lock_connection(&self.connection.0);
+        let c = connection.details.connection;
+        unsafe {
+            // This is what `xcb_aux_sync` does: any request with a reply will do, and GetInputFocus is the cheapest.
+            // The server handles requests in order, so once the reply arrives everything before it has been handled.
+            let reply = xcb_get_input_focus_reply(c, xcb_get_input_focus(c), std::ptr::null_mut());
+            free(reply.cast());
+        }
+    }
+
+    pub(crate) fn batch(&self) -> Batch<'_> {
+        Batch { window: self, requests: Vec::new(), mwm_fields: 0, normal_hints_size: None }
+    }
+
+    pub(crate) fn set_maximised(&self, maximised: bool) {
+        self.batch().set_maximised(maximised)
+    }
+
+    pub(crate) fn set_always_on_top(&self, always_on_top: bool) {
+        self.batch().set_always_on_top(always_on_top)
+    }
+
+    pub(crate) fn set_fullscreen(&self, fullscreen: bool) {
+        self.batch().set_fullscreen(fullscreen)
+    }
+
+    pub(crate) fn set_minimised(&self, minimised: bool) {
+        self.batch().set_minimised(minimised)
+    }
+
+    pub(crate) fn set_max_size(&self, size: Option<(u16, u16)>) -> Result<(), Error> {
+        self.batch().set_max_size(size)
+    }
+
+    pub(crate) fn set_min_size(&self, size: Option<(u16, u16)>) -> Result<(), Error> {
+        self.batch().set_min_size(size)
+    }
+
+    pub(crate) fn set_base_size(&self, size: Option<(u16, u16)>) {
+        self.batch().set_base_size(size)
+    }
+
+    pub(crate) fn set_position(&self, position: (i16, i16)) {
+        self.batch().set_position(position)
+    }
+
+    pub(crate) fn set_borderless(&self, borderless: bool) {
+        self.batch().set_borderless(borderless)
+    }
+
+    pub(crate) fn set_resizable(&self, resizable: bool) {
+        self.batch().set_resizable(resizable)
+    }
+
+    pub(crate) fn set_size(&self, size: (u16, u16)) {
+        self.batch().set_size(size)
+    }
+
+    pub(crate) fn set_size_with_anchor(&self, size: (u16, u16), anchor: Gravity) {
+        self.batch().set_size_with_anchor(size, anchor)
+    }
+
+    pub(crate) fn set_title(&self, title: &str) {
+        self.batch().set_title(title)
+    }
+
+    pub(crate) fn set_icon(&self, rgba: &[u8], width: u32, height: u32) -> Result<(), Error> {
+        let connection = lock_connection(&self.connection.0);
+        let c = connection.details.connection;
+        unsafe {
+            if !icon_fits_request(c, width, height) {
+                return Err(Error::Invalid)
+            }
+            // Like the title, the icon is a Window Manager property, which offscreen windows never have
+            if !self.details.offscreen {
+                set_net_wm_icon(c, &connection.details.atoms, self.details.handle, rgba, width, height);
+                _ = xcb_flush(c);
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn set_visible(&self, visible: bool) {
+        self.batch().set_visible(visible)
+    }
+
+    pub(crate) fn set_withdrawn(&self, withdrawn: bool) {
+        if self.details.offscreen {
+            return
+        }
+        let connection = lock_connection(&self.connection.0);
+        let c = connection.details.connection;
+        unsafe {
+            if withdrawn {
+                // Normal or Iconic -> Withdrawn (ICCCM 4.1.4). An Iconic window is already unmapped, so unmapping it
+                // makes no UnmapNotify for the Window Manager, which is why the spec asks for a synthetic one too.
+                _ = xcb_unmap_window(c, self.details.handle);
+                let unmap_notify = xcb_unmap_notify_event_t {
+                    response_type: XCB_UNMAP_NOTIFY,
+                    _pad0: 0,
+                    sequence: 0,
+                    event: (*connection.details.screen).root,
+                    window: self.details.handle,
+                    from_configure: false.into(),
+                    _pad1: [0; 3],
+                };
+                xcb_discard_reply(c, xcb_send_event_checked(
+                    c,
+                    false.into(),
+                    (*connection.details.screen).root,
+                    XCB_EVENT_MASK_SUBSTRUCTURE_NOTIFY | XCB_EVENT_MASK_SUBSTRUCTURE_REDIRECT,
+                    (&unmap_notify as *const _) as *const i8,
+                ));
+            } else {
+                // Withdrawn -> Normal, or Iconic if that's the `initial_state` in `WM_HINTS`
+                _ = xcb_map_window(c, self.details.handle);
+            }
+            _ = xcb_flush(c);
+        }
+    }
+
+    #[cfg(feature = "input")]
+    pub(crate) fn modifiers(&self) -> Modifiers {
+        self.details.modifiers
+    }
+
+    #[cfg(feature = "input")]
+    pub(crate) fn raw_modifiers(&self) -> RawModifiers {
+        self.details.raw_modifiers
+    }
+
+    #[cfg(feature = "input")]
+    pub(crate) fn mouse_buttons(&self) -> MouseButtons {
+        self.details.mouse_buttons
+    }
+
+    #[cfg(feature = "input")]
+    pub(crate) fn cursor_inside(&self) -> bool {
+        self.details.cursor_inside
+    }
+
+    pub(crate) fn is_connected(&self) -> bool {
+        let connection = lock_connection(&self.connection.0);
+        unsafe { xcb_connection_has_error(connection.details.connection) == 0 }
+    }
+
+    pub(crate) fn is_minimised(&self) -> bool {
+        self.details.minimised()
+    }
+
+    pub(crate) fn scale_factor(&self) -> f64 {
+        // X11 has no per-window scaling, so every window shares the desktop's setting
+        self.details.scale_factor
+    }
+
+    pub(crate) fn position(&self) -> (i16, i16) {
+        self.details.position
+    }
+
+    pub(crate) fn size(&self) -> (u16, u16) {
+        self.details.size
+    }
+
+    pub(crate) fn depth(&self) -> u8 {
+        self.details.depth
+    }
+
+    pub(crate) fn visual_id(&self) -> xcb_visualid_t {
+        self.details.visual
+    }
+
+    pub(crate) fn xid(&self) -> xcb_window_t {
+        self.details.handle
+    }
+
+    #[cfg(feature = "raw-window-handle")]
+    pub(crate) fn xcb_connection(&self) -> *mut c_void {
+        lock_connection(&self.connection.0).details.connection.cast()
+    }
+
+    #[cfg(feature = "raw-window-handle")]
+    pub(crate) fn screenid(&self) -> u32 {
+        self.details.screenid
+    }
 
-            window.set_borderless(builder.style.borderless);
-            set_wm_normal_hints(c, &window.details, window.details.size);
+    pub(crate) fn id(&self) -> WindowId {
+        WindowId(self.details.handle.into())
+    }
+}
 
-            Ok(window)
+// Requests made through this are held back and sent together when it's dropped, taking the connection lock once.
+// Anything which needs a round trip to the server, like the frame extents or whether the window is managed yet, is
+// only asked for once then. The hint properties are merged too, since several setters rewrite the same ones and
+// `set_mwm_hints` round-trips. The connection isn't held between calls, so that user code running during a batch can
+// still use other windows.
+pub(crate) struct Batch<'a> {
+    window: &'a Window,
+    requests: Vec<Request>,
+    mwm_fields: u32,
+    normal_hints_size: Option<(u16, u16)>,
+}
+
+// A request held back by a `Batch`, with everything it needs that doesn't involve the server worked out already
+enum Request {
+    Minimised(bool),
+    NetWmState(NetWmState, bool),
+    Position((i32, i32), Gravity),     // before the frame offset for the gravity is taken away
+    Size((u16, u16)),
+    SizeWithAnchor((i32, i32), (u16, u16), Gravity), // the position is before the frame offset, like `Position`
+    Title(String),
+    Visible(bool),
+}
+
+// The parts of _NET_WM_STATE that can be set, which each need one or two atoms changing
+#[derive(Clone, Copy)]
+enum NetWmState {
+    Maximised,
+    AlwaysOnTop,
+    Fullscreen,
+}
+
+impl NetWmState {
+    // The second atom is 0 when there's only one, as in a _NET_WM_STATE client message
+    fn atoms(self, atoms: &Atoms) -> [xcb_atom_t; 2] {
+        match self {
+            Self::Maximised => [atoms._net_wm_state_maximized_horz, atoms._net_wm_state_maximized_vert],
+            Self::AlwaysOnTop => [atoms._net_wm_state_above, 0],
+            Self::Fullscreen => [atoms._net_wm_state_fullscreen, 0],
         }
     }
+}
 
-    pub(crate) fn events(&self) -> &[Event] {
-        &self.details.event_buffer
+impl Batch<'_> {
+    pub(crate) fn set_borderless(&mut self, borderless: bool) {
+        mutex_lock(&self.window.details.style).borderless = borderless;
+        self.mwm_fields |= MWM_HINTS_DECORATIONS;
     }
 
-    pub(crate) fn poll_events(&mut self) {
-        unsafe {
-            let window_details = &mut self.details;
-            // First: lock the global event queue, which is used as backup storage for events
-            // which have been pulled but are not immediately relevant
-            let mut connection_ = mutex_lock(&self.connection.0);
-            let Connection {
-                details: connection_details,
-                event_buffer: map,
-                ..
-            } = &mut *connection_;
-            let c = connection_details.connection;
+    pub(crate) fn set_maximised(&mut self, maximised: bool) {
+        self.set_net_wm_state(NetWmState::Maximised, maximised);
+    }
 
-            // Clear our event buffer of the previous set of events
-            window_details.event_buffer.clear();
+    pub(crate) fn set_always_on_top(&mut self, always_on_top: bool) {
+        self.set_net_wm_state(NetWmState::AlwaysOnTop, always_on_top);
+    }
 
-            // Fill our event buffer with any events which may have been stored in the global event queue,
-            // also clearing them from the global queue
-            // Note: this queue SHOULD always exist, but it's possible some bad or malicious user code might get a
-            // `None` result, so it's better to check and take no action if there's no queue to copy from...
-            if let Some(queue) = map.get_mut(&window_details.handle) {
-                for event in queue.iter().copied() {
-                    process_event(event, window_details, connection_details);
-                }
-                queue.clear();
-            }
+    pub(crate) fn set_fullscreen(&mut self, fullscreen: bool) {
+        self.set_net_wm_state(NetWmState::Fullscreen, fullscreen);
+    }
 
-            // Deliver stuff (polling won't flush out)
-            let _ = xcb_flush(c);
+    // _NET_WM_STATE is a Window Manager property, which offscreen windows never have
+    fn set_net_wm_state(&mut self, state: NetWmState, enabled: bool) {
+        if !self.window.details.offscreen {
+            self.requests.push(Request::NetWmState(state, enabled));
+        }
+    }
 
-            // Call `poll_event` once, which populates XCB's internal linked list from the connection
-            let event = xcb_poll_for_event(c);
-            if !event.is_null() {
-                if let Some(window) = get_event_window(event, connection_details) {
-                    if window == window_details.handle {
-                        process_event(event, window_details, connection_details);
-                    } else if let Some(queue) = map.get_mut(&window) {
-                        queue.push(event);
-                    }
-                }
-            }
-            // Now repeatedly call `poll_for_queued_event` to drain xcb's queue without any new events arriving in it
-            loop {
-                let event = xcb_poll_for_queued_event(c);
-                if event.is_null() { break }
-                if let Some(window) = get_event_window(event, connection_details) {
-                    if window == window_details.handle {
-                        process_event(event, window_details, connection_details);
-                    } else if let Some(queue) = map.get_mut(&window) {
-                        queue.push(event);
-                    }
-                }
-            }
+    pub(crate) fn set_minimised(&mut self, minimised: bool) {
+        if !self.window.details.offscreen {
+            self.requests.push(Request::Minimised(minimised));
         }
     }
 
-    pub(crate) fn set_maximised(&self, maximised: bool) {
-        let mut connection_ = mutex_lock(&self.connection.0);
-        let connection = &mut connection_;
-        unsafe {
-            internal_set_maximised(connection.details.connection, self.details.handle, &connection.details, maximised);
+    pub(crate) fn set_max_size(&mut self, size: Option<(u16, u16)>) -> Result<(), Error> {
+        {
+            let mut limits = mutex_lock(&self.window.details.size_limits);
+            if !size_limits_valid(limits.min, size) {
+                return Err(Error::Invalid)
+            }
+            limits.max = size;
         }
+        self.apply_size_limits();
+        Ok(())
     }
 
-    pub(crate) fn set_position(&self, (x, y): (i16, i16)) {
-        let mut connection_ = mutex_lock(&self.connection.0);
-        let connection = &mut connection_;
-        // TODO how does negative stuff interact here with xcb? how is it MEANT TO?
-        let xy = [x as u32, y as u32];
-        unsafe {
-            _ = xcb_configure_window(connection.details.connection, self.details.handle, 1|2, xy.as_ptr().cast());
+    pub(crate) fn set_min_size(&mut self, size: Option<(u16, u16)>) -> Result<(), Error> {
+        {
+            let mut limits = mutex_lock(&self.window.details.size_limits);
+            if !size_limits_valid(size, limits.max) {
+                return Err(Error::Invalid)
+            }
+            limits.min = size;
         }
+        self.apply_size_limits();
+        Ok(())
     }
 
-    pub(crate) fn set_borderless(&self, borderless: bool) {
-        let mut connection_ = mutex_lock(&self.connection.0);
-        let connection = &mut connection_;
-        let mut g = mutex_lock(&self.details.style);
-        g.borderless = borderless;
-        std::mem::drop(g);
-        unsafe { set_mwm_hints(connection.details.connection, &connection.details, &self.details) };
+    pub(crate) fn set_base_size(&mut self, size: Option<(u16, u16)>) {
+        mutex_lock(&self.window.details.size_limits).base = size;
+        // Doesn't limit the size, so there's nothing to clamp
+        self.normal_hints_size = Some(self.normal_hints_size.unwrap_or(self.window.details.size));
     }
 
-    pub(crate) fn set_resizable(&self, resizable: bool) {
-        let mut connection_ = mutex_lock(&self.connection.0);
-        let connection = &mut connection_;
-        let mut g = mutex_lock(&self.details.style);
-        g.resizable = resizable;
-        std::mem::drop(g);
-        unsafe {
-            set_mwm_hints(connection.details.connection, &connection.details, &self.details);
-            set_wm_normal_hints(connection.details.connection, &self.details, self.details.size);
+    // Rewrites the normal hints with the new limits, and resizes the window if it's now outside of them
+    fn apply_size_limits(&mut self) {
+        let current = self.normal_hints_size.unwrap_or(self.window.details.size);
+        let SizeLimits { min, max, .. } = *mutex_lock(&self.window.details.size_limits);
+        if clamp_size(current, min, max) != current {
+            self.set_size(current);
+        } else {
+            self.normal_hints_size = Some(current);
         }
     }
 
-    pub(crate) fn set_size(&self, (width, height): (u16, u16)) {
-        let mut connection_ = mutex_lock(&self.connection.0);
-        let connection = &mut connection_;
-        let wh = [width as u32, height as u32];
-        unsafe {
-            _ = xcb_configure_window(connection.details.connection, self.details.handle, 4|8, wh.as_ptr().cast());
-            set_wm_normal_hints(connection.details.connection, &self.details, (width, height));
+    pub(crate) fn set_position(&mut self, (x, y): (i16, i16)) {
+        let gravity = Gravity::from_u8(self.window.details.win_gravity.load(Ordering::Relaxed));
+        self.requests.push(Request::Position((x.into(), y.into()), gravity));
+    }
+
+    pub(crate) fn set_resizable(&mut self, resizable: bool) {
+        mutex_lock(&self.window.details.style).resizable = resizable;
+        self.mwm_fields |= MWM_HINTS_FUNCTIONS | MWM_HINTS_DECORATIONS;
+        self.normal_hints_size = Some(self.normal_hints_size.unwrap_or(self.window.details.size));
+    }
+
+    pub(crate) fn set_size(&mut self, size: (u16, u16)) {
+        let SizeLimits { min, max, .. } = *mutex_lock(&self.window.details.size_limits);
+        // A window can't be 0 pixels wide or high, the server would answer with a `Value` error
+        let (width, height) = clamp_size(size, min, max);
+        let (width, height) = (width.max(1), height.max(1));
+        self.requests.push(Request::Size((width, height)));
+        self.normal_hints_size = Some((width, height));
+    }
+
+    pub(crate) fn set_size_with_anchor(&mut self, size: (u16, u16), anchor: Gravity) {
+        // Forget isn't a valid window gravity, it means "unmap" instead
+        let anchor = if anchor == Gravity::Forget { Gravity::NorthWest } else { anchor };
+        let SizeLimits { min, max, .. } = *mutex_lock(&self.window.details.size_limits);
+        let (width, height) = clamp_size(size, min, max);
+        let (width, height) = (width.max(1), height.max(1));
+        let (x, y) = self.window.details.position;
+        let (old_width, old_height) = self.window.details.size;
+        let (dx, dy) = anchor.offset((old_width.into(), old_height.into()), (width.into(), height.into()));
+        self.window.details.win_gravity.store(anchor as u8, Ordering::Relaxed);
+        let position = (i32::from(x) + dx, i32::from(y) + dy);
+        self.requests.push(Request::SizeWithAnchor(position, (width, height), anchor));
+        self.normal_hints_size = Some((width, height));
+    }
+
+    pub(crate) fn set_title(&mut self, title: &str) {
+        // The title is a Window Manager property, which offscreen windows never have
+        if !self.window.details.offscreen {
+            self.requests.push(Request::Title(title.to_owned()));
         }
     }
 
-    pub(crate) fn set_title(&self, title: &str) {
-        let mut connection_ = mutex_lock(&self.connection.0);
-        let connection = &mut connection_;
-        unsafe {
-            internal_set_title(connection.details.connection, self.details.handle, &connection.details.atoms, title);
+    pub(crate) fn set_visible(&mut self, visible: bool) {
+        if !self.window.details.offscreen {
+            self.requests.push(Request::Visible(visible));
         }
     }
+}
 
-    pub(crate) fn set_visible(&self, visible: bool) {
-        let mut connection_ = mutex_lock(&self.connection.0);
-        let connection = &mut connection_;
+impl Drop for Batch<'_> {
+    fn drop(&mut self) {
+        let connection = lock_connection(&self.window.connection.0);
+        let details = unsafe { connection.details.for_window(&self.window.details) };
+        let c = details.connection;
+        let xid = self.window.details.handle;
+        // Each of these is only asked for once it's needed, and then only once
+        let mut extents = None;
+        let mut managed = None;
+        let mut withdrawn_state: Option<Vec<xcb_atom_t>> = None; // _NET_WM_STATE, if it's being edited directly
         unsafe {
-            if visible {
-                _ = xcb_map_window(connection.details.connection, self.details.handle);
-            } else {
-                _ = xcb_unmap_window(connection.details.connection, self.details.handle);
+            for request in self.requests.drain(..) {
+                match request {
+                    Request::Minimised(true) => {
+                        // Normal -> Iconic (ICCCM 4.1.4) is asked of the Window Manager, which unmaps the window itself
+                        let client_message = xcb_client_message_event_t {
+                            response_type: XCB_CLIENT_MESSAGE,
+                            format: 32,
+                            sequence: 0,
+                            window: xid,
+                            r#type: details.atoms.wm_change_state,
+                            client_data: ClientData { data32: [ICCCM_ICONIC_STATE, 0, 0, 0, 0] },
+                        };
+                        xcb_discard_reply(c, xcb_send_event_checked(
+                            c,
+                            0,
+                            (*details.screen).root,
+                            XCB_EVENT_MASK_SUBSTRUCTURE_NOTIFY | XCB_EVENT_MASK_SUBSTRUCTURE_REDIRECT,
+                            (&client_message as *const _) as *const i8,
+                        ));
+                    },
+                    // Iconic -> Normal is just mapping the window again, which does nothing if it's already mapped.
+                    // The Window Manager reads _NET_WM_STATE when the window is mapped, so any edits go in first.
+                    Request::Minimised(false) | Request::Visible(true) => {
+                        if let Some(state) = withdrawn_state.take() {
+                            set_net_wm_state(c, xid, &details.atoms, &state);
+                        }
+                        _ = xcb_map_window(c, xid);
+                    },
+                    Request::NetWmState(state, enabled) => {
+                        let state = state.atoms(&details.atoms);
+                        if *managed.get_or_insert_with(|| is_managed(c, xid, &details.atoms)) {
+                            send_net_wm_state(c, xid, &details, &state, enabled);
+                        } else {
+                            let atoms = &details.atoms;
+                            let current = withdrawn_state.get_or_insert_with(|| get_net_wm_state(c, xid, atoms));
+                            edit_net_wm_state(current, &state, enabled);
+                        }
+                    },
+                    Request::Position((x, y), gravity) => {
+                        let extents = *extents.get_or_insert_with(|| frame_extents(c, &details.atoms, xid));
+                        let (dx, dy) = frame_offset(extents, gravity);
+                        // Positions are INT16 on the wire, sign-extended into the 32-bit value list
+                        let xy = [(x - dx) as u32, (y - dy) as u32];
+                        _ = xcb_configure_window(c, xid, 1|2, xy.as_ptr().cast());
+                    },
+                    Request::Size((width, height)) => {
+                        let wh = [width as u32, height as u32];
+                        _ = xcb_configure_window(c, xid, 4|8, wh.as_ptr().cast());
+                    },
+                    Request::SizeWithAnchor((x, y), (width, height), anchor) => {
+                        let extents = *extents.get_or_insert_with(|| frame_extents(c, &details.atoms, xid));
+                        let (fx, fy) = frame_offset(extents, anchor);
+                        let values = [(x - fx) as u32, (y - fy) as u32, width as u32, height as u32];
+                        // The Window Manager places its frame around the request according to the gravity, so that
+                        // goes first
+                        if !self.window.details.offscreen {
+                            set_wm_normal_hints(c, &self.window.details, (width, height));
+                        }
+                        _ = xcb_configure_window(c, xid, 1|2|4|8, values.as_ptr().cast());
+                    },
+                    Request::Title(title) => internal_set_title(c, xid, &details.atoms, &title),
+                    // Only a plain unmap, without the synthetic UnmapNotify of `set_withdrawn`. Unmapping a Normal
+                    // window usually withdraws it, but unmapping an Iconic one is invisible to the Window Manager, so
+                    // it stays Iconic and is restored to where it was. The server ignores mapping a window which is
+                    // already mapped and unmapping one which isn't, so there's no need to track which it is.
+                    Request::Visible(false) => _ = xcb_unmap_window(c, xid),
+                }
+            }
+            if let Some(state) = withdrawn_state {
+                set_net_wm_state(c, xid, &details.atoms, &state);
+            }
+            // Like the title, these are Window Manager properties, which offscreen windows never have
+            if !self.window.details.offscreen {
+                if self.mwm_fields != 0 {
+                    set_mwm_hints(c, &connection.details, &self.window.details, self.mwm_fields);
+                }
+                if let Some(size) = self.normal_hints_size {
+                    set_wm_normal_hints(c, &self.window.details, size);
+                }
             }
+            _ = xcb_flush(c);
         }
     }
-
-    pub(crate) fn xid(&self) -> xcb_window_t {
-        self.details.handle
-    }
 }
 
 impl Drop for Window {
     fn drop(&mut self) {
-        let mut connection_ = mutex_lock(&self.connection.0);
+        let mut connection_ = lock_connection(&self.connection.0);
         let connection = &mut connection_;
         unsafe {
+            if let Some(barriers) = mutex_lock(&self.details.cursor_barriers).take() {
+                for barrier in barriers {
+                    _ = xcb_xfixes_delete_pointer_barrier(connection.details.connection, barrier);
+                }
+            }
             let _ = xcb_destroy_window(connection.details.connection, self.details.handle);
             let _ = xcb_flush(connection.details.connection);
+            // Nothing will poll this queue again, and anything arriving later is freed as an unrouted event
+            if let Some(queue) = connection.event_buffer.remove(&self.details.handle) {
+                for event in queue.events {
+                    free(event.cast());
+                }
+            }
         }
     }
 }
 
+// Asks the server for the window's real position, relative to the root window like ConfigureNotify's is made to be,
+// and size. This is a round trip, so it's only for when there's no ConfigureNotify to go by.
+unsafe fn query_geometry(
+    c: *mut xcb_connection_t,
+    xid: xcb_window_t,
+    root: xcb_window_t,
+) -> Option<((i16, i16), (u16, u16))> {
+    let geometry_cookie = xcb_get_geometry(c, xid);
+    let translate_cookie = xcb_translate_coordinates(c, xid, root, 0, 0);
+    let geometry = xcb_get_geometry_reply(c, geometry_cookie, std::ptr::null_mut());
+    let translate = xcb_translate_coordinates_reply(c, translate_cookie, std::ptr::null_mut());
+    let result = match (geometry.is_null(), translate.is_null()) {
+        (false, false) => Some((((*translate).dst_x, (*translate).dst_y), ((*geometry).width, (*geometry).height))),
+        _ => None,
+    };
+    free(geometry.cast());
+    free(translate.cast());
+    result
+}
+
+// Reads the frame's left, right, top and bottom widths, which the Window Manager sets once it's framed the window.
+// They're all 0 until then.
+unsafe fn frame_extents(c: *mut xcb_connection_t, atoms: &Atoms, xid: xcb_window_t) -> (i32, i32, i32, i32) {
+    let extents = match get_property(c, xid, atoms._net_frame_extents, XCB_ATOM_CARDINAL) {
+        Some(value) if value.len() >= 16 => value,
+        _ => return (0, 0, 0, 0),
+    };
+    let extent = |i: usize| u32::from_ne_bytes([extents[i], extents[i + 1], extents[i + 2], extents[i + 3]]) as i32;
+    (extent(0), extent(4), extent(8), extent(12))
+}
+
+// How far from the position in a ConfigureWindow request the Window Manager will put the inner area, for a window
+// with the given gravity. ICCCM 4.1.2.3 has the frame placed so that the gravity's reference point is where it
+// would be without a frame, so apart from with Static gravity, the frame's size has to be taken into account.
+fn frame_offset((left, right, top, bottom): (i32, i32, i32, i32), gravity: Gravity) -> (i32, i32) {
+    if gravity == Gravity::Static {
+        return (0, 0)
+    }
+    let (ox, oy) = gravity.offset((left + right, top + bottom), (0, 0));
+    (left - ox, top - oy)
+}
+
+// Rewrites the `fields` (MWM_HINTS_FUNCTIONS and/or MWM_HINTS_DECORATIONS) of _MOTIF_WM_HINTS from the window's style.
+// The existing property is read back first so that any fields not being updated are kept as they were.
 unsafe fn set_mwm_hints(
     c: *mut xcb_connection_t,
     cdetails: &ConnectionDetails,
     wdetails: &WindowDetails,
+    fields: u32,
 ) {
     let g = mutex_lock(&wdetails.style);
     let style = *g;
     std::mem::drop(g);
-    let mut hints = MwmHints {
-        flags: MWM_HINTS_FUNCTIONS | MWM_HINTS_DECORATIONS,
+
+    let mut existing = None;
+    let prop = xcb_get_property_reply(c, xcb_get_property(
+        c,
+        0,
+        wdetails.handle,
+        cdetails.atoms._motif_wm_hints,
+        cdetails.atoms._motif_wm_hints,
+        0,
+        (std::mem::size_of::<MwmHints>() / 4) as u32,
+    ), std::ptr::null_mut());
+    if !prop.is_null() {
+        if (*prop).format == 32 && xcb_get_property_value_length(prop) as usize >= std::mem::size_of::<MwmHints>() {
+            existing = Some(std::ptr::read_unaligned(xcb_get_property_value(prop) as *const MwmHints));
+        }
+        free(prop.cast());
+    }
+
+    let hints = merge_mwm_hints(existing, &style, fields);
+    _ = xcb_change_property(
+        c,
+        XCB_PROP_MODE_REPLACE,
+        wdetails.handle,
+        cdetails.atoms._motif_wm_hints,
+        cdetails.atoms._motif_wm_hints,
+        32,
+        std::mem::size_of_val(&hints) as u32 / 4,
+        (&hints) as *const _ as _,
+    );
+}
+
+fn merge_mwm_hints(existing: Option<MwmHints>, style: &window::Style, fields: u32) -> MwmHints {
+    let mut hints = existing.unwrap_or(MwmHints {
+        flags: 0,
         functions: 0,
         decorations: 0,
         input_mode: 0,
         status: 0,
-    };
-    if !style.borderless {
-        hints.decorations |= MWM_DECOR_BORDER;
-        hints.decorations |= MWM_DECOR_TITLE;
-        hints.functions |= MWM_FUNC_MOVE;
+    });
+    if fields & MWM_HINTS_FUNCTIONS != 0 {
+        hints.flags |= MWM_HINTS_FUNCTIONS;
+        hints.functions = MWM_FUNC_MOVE;
         if style.resizable {
-            hints.decorations |= MWM_DECOR_RESIZEH;
             hints.functions |= MWM_FUNC_RESIZE;
         }
         if let Some(controls) = style.controls {
-            hints.decorations |= MWM_DECOR_MENU;
             if controls.minimise { hints.functions |= MWM_FUNC_MINIMIZE; }
             if controls.maximise && style.resizable { hints.functions |= MWM_FUNC_MAXIMIZE; }
             if controls.close { hints.functions |= MWM_FUNC_CLOSE; }
         }
     }
-    _ = xcb_change_property(
-        c,
-        XCB_PROP_MODE_REPLACE,
-        wdetails.handle,
-        cdetails.atoms._motif_wm_hints,
-        cdetails.atoms._motif_wm_hints,
-        32,
-        std::mem::size_of_val(&hints) as u32 / 4,
-        (&hints) as *const _ as _,
-    );
+    if fields & MWM_HINTS_DECORATIONS != 0 {
+        hints.flags |= MWM_HINTS_DECORATIONS;
+        hints.decorations = 0;
+        if !style.borderless {
+            hints.decorations |= MWM_DECOR_BORDER;
+            hints.decorations |= MWM_DECOR_TITLE;
+            if style.resizable {
+                hints.decorations |= MWM_DECOR_RESIZEH;
+            }
+            if style.controls.is_some() {
+                hints.decorations |= MWM_DECOR_MENU;
+            }
+        }
+    }
+    hints
 }
 
 unsafe fn set_wm_normal_hints(c: *mut xcb_connection_t, details: &WindowDetails, size: (u16, u16)) {
@@ -664,6 +2566,7 @@ unsafe fn set_wm_normal_hints(c: *mut xcb_connection_t, details: &WindowDetails,
     let g = mutex_lock(&details.style);
     let resizable = g.resizable;
     std::mem::drop(g);
+    let SizeLimits { min, max, base } = *mutex_lock(&details.size_limits);
     if !resizable {
         hints.flags |= ICCCM_SIZE_HINT_P_MIN_SIZE;
         hints.flags |= ICCCM_SIZE_HINT_P_MAX_SIZE;
@@ -672,13 +2575,26 @@ unsafe fn set_wm_normal_hints(c: *mut xcb_connection_t, details: &WindowDetails,
         hints.max_width = size.0 as _;
         hints.max_height = size.1 as _;
     } else {
+        let (min_width, min_height) = min.unwrap_or((1, 1));
         hints.flags |= ICCCM_SIZE_HINT_P_MIN_SIZE;
-        hints.min_width = 1;
-        hints.min_height = 1;
+        hints.min_width = min_width as _;
+        hints.min_height = min_height as _;
+        if let Some((max_width, max_height)) = max {
+            hints.flags |= ICCCM_SIZE_HINT_P_MAX_SIZE;
+            hints.max_width = max_width as _;
+            hints.max_height = max_height as _;
+        }
     }
+    // Without a base size of its own, the current size is used, which is what the hints have always said
+    let (base_width, base_height) = base.unwrap_or(size);
     hints.flags |= ICCCM_SIZE_HINT_BASE_SIZE;
-    hints.base_width = size.0 as _;
-    hints.base_height = size.1 as _;
+    hints.base_width = base_width as _;
+    hints.base_height = base_height as _;
+    let win_gravity = details.win_gravity.load(Ordering::Relaxed);
+    if win_gravity != Gravity::NorthWest as u8 {
+        hints.flags |= ICCCM_SIZE_HINT_P_WIN_GRAVITY;
+        hints.win_gravity = win_gravity.into();
+    }
     _ = xcb_change_property(
         c,
         XCB_PROP_MODE_REPLACE,
@@ -691,39 +2607,357 @@ unsafe fn set_wm_normal_hints(c: *mut xcb_connection_t, details: &WindowDetails,
     );
 }
 
+// Checks whether an event is a `_NET_WM_PING` from the Window Manager, which has to be answered.
+unsafe fn is_ping(ev: *mut xcb_generic_event_t, details: &ConnectionDetails) -> bool {
+    if (*ev).response_type & !(1 << 7) != XCB_CLIENT_MESSAGE {
+        return false
+    }
+    let event = &*(ev as *mut xcb_client_message_event_t);
+    event.r#type == details.atoms.wm_protocols
+        && event.format == 32
+        && event.client_data.data32[0] == details.atoms._net_wm_ping
+}
+
+// Answers a `_NET_WM_PING` by sending it back to the root window.
+unsafe fn answer_ping(event: &mut xcb_client_message_event_t, details: &ConnectionDetails) {
+    // data32[2] contains the window xid, that might be useful for something?
+    event.window = (*details.screen).root;
+    xcb_discard_reply(details.connection, xcb_send_event_checked(
+        details.connection,
+        false.into(),
+        event.window,
+        XCB_EVENT_MASK_SUBSTRUCTURE_NOTIFY | XCB_EVENT_MASK_SUBSTRUCTURE_REDIRECT,
+        (event as *const _) as *const i8,
+    ));
+    let _ = xcb_flush(details.connection); // Makes sure the event is processed before we free it
+}
+
+// Serves a request for the clipboard contents from another client, or from ourselves.
+unsafe fn answer_selection_request(
+    event: &xcb_selection_request_event_t,
+    details: &ConnectionDetails,
+    clipboard: &mut Clipboard,
+) {
+    let c = details.connection;
+    // Clients from before ICCCM 2.0 don't give a property, and expect the target to be used as one
+    let property = if event.property != 0 { event.property } else { event.target };
+    let served = if event.selection != details.atoms.clipboard {
+        false
+    } else if event.target == details.atoms.targets {
+        let targets: Vec<xcb_atom_t> = std::iter::once(details.atoms.targets)
+            .chain(clipboard.entries.iter().map(|(target, _)| *target))
+            .collect();
+        _ = xcb_change_property(
+            c,
+            XCB_PROP_MODE_REPLACE,
+            event.requestor,
+            property,
+            XCB_ATOM_ATOM,
+            32,
+            targets.len() as u32,
+            targets.as_ptr().cast(),
+        );
+        true
+    } else {
+        match clipboard.entries.iter().find(|(target, _)| *target == event.target) {
+            Some((_, data)) if data.len() > incr_chunk_len(c) => {
+                // Too big for one go, so the requestor is told the size and the data follows in chunks.
+                // We need to see the requestor's PropertyNotify events for that, which carry on to other clients.
+                let now = Instant::now();
+                clipboard.transfers.retain(|transfer| {
+                    now.duration_since(transfer.last_active) < INCR_TIMEOUT
+                        && !(transfer.requestor == event.requestor && transfer.property == property)
+                });
+                _ = xcb_change_window_attributes(c, event.requestor, XCB_CW_EVENT_MASK, &XCB_EVENT_MASK_PROPERTY_CHANGE);
+                let len = u32::try_from(data.len()).unwrap_or(u32::MAX);
+                _ = xcb_change_property(
+                    c,
+                    XCB_PROP_MODE_REPLACE,
+                    event.requestor,
+                    property,
+                    details.atoms.incr,
+                    32,
+                    1,
+                    (&len) as *const u32 as _,
+                );
+                clipboard.transfers.push(IncrTransfer {
+                    requestor: event.requestor,
+                    property,
+                    target: event.target,
+                    data: data.clone(),
+                    offset: 0,
+                    last_active: now,
+                });
+                true
+            },
+            Some((_, data)) => {
+                _ = xcb_change_property(
+                    c,
+                    XCB_PROP_MODE_REPLACE,
+                    event.requestor,
+                    property,
+                    event.target,
+                    8,
+                    data.len() as u32,
+                    data.as_ptr().cast(),
+                );
+                true
+            },
+            _ => false,
+        }
+    };
+
+    let notify = xcb_selection_notify_event_t {
+        response_type: XCB_SELECTION_NOTIFY,
+        _pad0: 0,
+        sequence: 0,
+        time: event.time,
+        requestor: event.requestor,
+        selection: event.selection,
+        target: event.target,
+        property: if served { property } else { 0 }, // no property tells the requestor it was refused
+        _pad1: [0; 8],
+    };
+    xcb_discard_reply(c, xcb_send_event_checked(c, 0, event.requestor, 0, (&notify) as *const _ as *const c_char));
+    _ = xcb_flush(c);
+}
+
+// Writes the next chunk of an INCR transfer, after the requestor has deleted the last one.
+// Returns `false` once the transfer is finished, which is marked by writing an empty chunk.
+unsafe fn continue_incr_transfer(c: *mut xcb_connection_t, transfer: &mut IncrTransfer) -> bool {
+    let end = transfer.data.len().min(transfer.offset + incr_chunk_len(c));
+    let chunk = &transfer.data[transfer.offset..end];
+    _ = xcb_change_property(
+        c,
+        XCB_PROP_MODE_REPLACE,
+        transfer.requestor,
+        transfer.property,
+        transfer.target,
+        8,
+        chunk.len() as u32,
+        chunk.as_ptr().cast(),
+    );
+    _ = xcb_flush(c);
+    transfer.offset = end;
+    transfer.last_active = Instant::now();
+    !chunk.is_empty()
+}
+
+// The largest clipboard entry we'll send in one property, in bytes. Anything bigger is sent with INCR.
+unsafe fn incr_chunk_len(c: *mut xcb_connection_t) -> usize {
+    // The maximum request length is in 4-byte units, and ChangeProperty's header takes up 24 bytes of it.
+    // Even with BIG-REQUESTS, very large properties are best avoided, as they hold up the server.
+    (xcb_get_maximum_request_length(c) as usize * 4).saturating_sub(24).min(1 << 18)
+}
+
+// Reads whatever events have arrived on the connection and routes each of them with `route_event`.
+// Anything which takes events off the connection should use this, unless it has to pick some of them out first,
+// so that the rest are always stored away in the same way.
+unsafe fn pull_events(
+    mut window: Option<&mut WindowDetails>,
+    details: &ConnectionDetails,
+    map: &mut HashMap<xcb_window_t, EventQueue>,
+    clipboard: &mut Clipboard,
+) {
+    let c = details.connection;
+
+    // Deliver stuff (polling won't flush out)
+    let _ = xcb_flush(c);
+
+    // Call `poll_event` once, which populates XCB's internal linked list from the connection
+    let event = xcb_poll_for_event(c);
+    if !event.is_null() {
+        route_event(event, window.as_deref_mut(), details, map, clipboard);
+    }
+    // Now repeatedly call `poll_for_queued_event` to drain xcb's queue without any new events arriving in it
+    loop {
+        let event = xcb_poll_for_queued_event(c);
+        if event.is_null() { break }
+        route_event(event, window.as_deref_mut(), details, map, clipboard);
+    }
+}
+
+// Processes an event if it's for `window`, or stores it in the queue of the window it's for.
+// Events which aren't for any of our windows are freed, the same as `process_event` frees the ones it handles.
+// With no `window`, every event goes to a queue, to be processed when its window next polls.
+unsafe fn route_event(
+    ev: *mut xcb_generic_event_t,
+    window: Option<&mut WindowDetails>,
+    details: &ConnectionDetails,
+    map: &mut HashMap<xcb_window_t, EventQueue>,
+    clipboard: &mut Clipboard,
+) {
+    if let Some(len) = broadcast_len(ev, details) {
+        // These aren't for any one window, so every window's queue gets a copy
+        let own = window.as_ref().map(|window| window.handle);
+        for (_, queue) in map.iter_mut().filter(|(xid, _)| Some(**xid) != own) {
+            let copy = libc::malloc(len).cast::<xcb_generic_event_t>();
+            if !copy.is_null() {
+                std::ptr::copy_nonoverlapping(ev.cast::<u8>(), copy.cast::<u8>(), len);
+                queue.push(copy);
+            }
+        }
+        return match window {
+            Some(window) => process_event(ev, window, details),
+            None => free(ev.cast()),
+        }
+    }
+    match (get_event_window(ev, details, clipboard), window) {
+        (Some(xid), Some(window)) if xid == window.handle => process_event(ev, window, details),
+        (xid, _) => match xid.and_then(|xid| map.get_mut(&xid)) {
+            Some(queue) => queue.push(ev),
+            None => free(ev.cast()),
+        },
+    }
+}
+
+// If an event is one which every window should get, such as a hierarchy change, this is how long it is.
+unsafe fn broadcast_len(ev: *mut xcb_generic_event_t, details: &ConnectionDetails) -> Option<usize> {
+    #[cfg(feature = "input")]
+    if is_hierarchy_event(ev, details) {
+        return Some(32 + (*(ev as *mut xcb_ge_generic_event_t)).length as usize * 4 + 4)
+    }
+    if is_resource_change(ev, details) {
+        return Some(std::mem::size_of::<xcb_generic_event_t>())
+    }
+    None
+}
+
+// Whether an event is a change to the X resources, which are stored on the root window of the default screen.
+unsafe fn is_resource_change(ev: *mut xcb_generic_event_t, details: &ConnectionDetails) -> bool {
+    let event = &*(ev as *mut xcb_property_notify_event_t);
+    (event.response_type & !(1 << 7)) == XCB_PROPERTY_NOTIFY
+        && event.window == (*details.screen).root
+        && event.atom == XCB_ATOM_RESOURCE_MANAGER
+}
+
+// Whether an event is an XInput2 hierarchy change, which is reported on the root window.
+#[cfg(feature = "input")]
+unsafe fn is_hierarchy_event(ev: *mut xcb_generic_event_t, details: &ConnectionDetails) -> bool {
+    let event = &*(ev as *mut xcb_ge_generic_event_t);
+    (event.response_type & !(1 << 7)) == XCB_GE_GENERIC
+        && Some(event.extension) == details.extensions.xinput
+        && (event.event_type & !(1 << 7)) == XCB_INPUT_HIERARCHY
+}
+
 // Gets the window an event is destined for, if any. `None` results should be discarded.
-unsafe fn get_event_window(ev: *mut xcb_generic_event_t, details: &ConnectionDetails) -> Option<xcb_window_t> {
-    #[cfg(not(feature = "input"))]
-    { _ = details }
+// Events which affect the whole connection are also handled here, since every event passes through exactly once.
+unsafe fn get_event_window(
+    ev: *mut xcb_generic_event_t,
+    details: &ConnectionDetails,
+    clipboard: &mut Clipboard,
+) -> Option<xcb_window_t> {
     match (*ev).response_type & !(1 << 7) {
+        XCB_SELECTION_REQUEST => {
+            answer_selection_request(&*(ev as *mut xcb_selection_request_event_t), details, clipboard);
+            None
+        },
+        XCB_SELECTION_CLEAR => {
+            // Another client has taken the clipboard, so there's nothing left for us to serve
+            if (*(ev as *mut xcb_selection_clear_event_t)).selection == details.atoms.clipboard {
+                clipboard.entries.clear();
+            }
+            None
+        },
+        #[cfg(feature = "input")]
+        XCB_MAPPING_NOTIFY => {
+            // The keyboard layout changed, so Xlib's cached keymap has to be refreshed or
+            // XLookupKeysym will keep decoding keys with the old one
+            let event = &*(ev as *mut xcb_mapping_notify_event_t);
+            let mut xevent = XMappingEvent {
+                r#type: XCB_MAPPING_NOTIFY.into(),
+                serial: 0,
+                send_event: 0,
+                display: details.display,
+                window: 0,
+                request: event.request.into(),
+                first_keycode: event.first_keycode.into(),
+                count: event.count.into(),
+            };
+            _ = XRefreshKeyboardMapping(&mut xevent);
+            None
+        },
+        // Buttons come from XInput2 if it's there. The core mask is still selected since ButtonPress can only be
+        // selected by one client per window, and the server shouldn't send core presses alongside XI2 ones, but any
+        // that do arrive are dropped here rather than reported twice. Without XInput2, these are our input events.
+        #[cfg(feature = "input")]
+        XCB_KEY_PRESS | XCB_KEY_RELEASE | XCB_BUTTON_PRESS | XCB_BUTTON_RELEASE | XCB_MOTION_NOTIFY
+            | XCB_ENTER_NOTIFY | XCB_LEAVE_NOTIFY => match details.extensions.xinput {
+                Some(_) => None,
+                // Enter and leave events have the window in the same place as the others
+                None => Some((*(ev as *mut xcb_key_press_event_t)).event),
+            },
         XCB_CLIENT_MESSAGE => Some((*(ev as *mut xcb_client_message_event_t)).window),
         XCB_FOCUS_IN | XCB_FOCUS_OUT => Some((*(ev as *mut xcb_focus_in_event_t)).event),
         XCB_MAP_NOTIFY => Some((*(ev as *mut xcb_map_notify_event_t)).window),
         XCB_UNMAP_NOTIFY => Some((*(ev as *mut xcb_unmap_notify_event_t)).window),
         XCB_REPARENT_NOTIFY => Some((*(ev as *mut xcb_reparent_notify_event_t)).window),
         XCB_CONFIGURE_NOTIFY => Some((*(ev as *mut xcb_configure_notify_event_t)).window),
-        XCB_PROPERTY_NOTIFY => Some((*(ev as *mut xcb_property_notify_event_t)).window),
-        #[cfg(feature = "input")]
+        XCB_PROPERTY_NOTIFY => {
+            let event = &*(ev as *mut xcb_property_notify_event_t);
+            let transfer = clipboard.transfers.iter().position(|transfer| {
+                transfer.requestor == event.window && transfer.property == event.atom
+            });
+            match transfer {
+                Some(i) if event.state == XCB_PROPERTY_DELETE => {
+                    if !continue_incr_transfer(details.connection, &mut clipboard.transfers[i]) {
+                        _ = clipboard.transfers.swap_remove(i);
+                    }
+                    None
+                },
+                _ => Some(event.window),
+            }
+        },
         XCB_GE_GENERIC => {
             let event = &*(ev as *mut xcb_ge_generic_event_t);
-            if event.extension == details.extensions.xinput {
-                match event.event_type & !(1 << 7) {
+            match event.extension {
+                ext if Some(ext) == details.extensions.present => match event.event_type {
+                    XCB_PRESENT_COMPLETE_NOTIFY
+                        => Some(std::ptr::read_unaligned(ev as *const xcb_present_complete_notify_event_t).window),
+                    _ => None,
+                },
+                #[cfg(feature = "input")]
+                ext if Some(ext) == details.extensions.xinput => match event.event_type & !(1 << 7) {
                     XCB_INPUT_KEY_PRESS | XCB_INPUT_KEY_RELEASE | XCB_INPUT_BUTTON_PRESS | XCB_INPUT_BUTTON_RELEASE | XCB_INPUT_MOTION
                         => Some((*(ev as *mut xcb_input_button_press_event_t)).event),
                     XCB_INPUT_ENTER | XCB_INPUT_LEAVE | XCB_INPUT_FOCUS_IN | XCB_INPUT_FOCUS_OUT
                         => Some((*(ev as *mut xcb_input_enter_event_t)).event),
                     _ => None,
-                }
-            } else {
-                None
+                },
+                _ => None,
             }
         },
+        ty if Some(ty) == details.extensions.randr.map(|base| base + XCB_RANDR_SCREEN_CHANGE_NOTIFY)
+            => Some((*(ev as *mut xcb_randr_screen_change_notify_event_t)).request_window),
+        ty if Some(ty) == details.extensions.randr.map(|base| base + XCB_RANDR_NOTIFY) => {
+            let event = &*(ev as *mut xcb_randr_output_change_notify_event_t);
+            if event.sub_code == XCB_RANDR_NOTIFY_OUTPUT_CHANGE { Some(event.window) } else { None }
+        },
         _ => None,
     }
 }
 
 // This function assumes the given event is destined for the given Window - check first with get_event_window
 unsafe fn process_event(ev: *mut xcb_generic_event_t, window: &mut WindowDetails, details: &ConnectionDetails) {
+    // This has to be checked against the default screen, where the X resources are read from
+    if is_resource_change(ev, details) {
+        let scale_factor = query_scale_factor(details);
+        if scale_factor != window.scale_factor {
+            window.scale_factor = scale_factor;
+            window.event_buffer.push(Event::ScaleFactorChanged(scale_factor));
+        }
+        return free(ev.cast())
+    }
+    let details = &details.for_window(window);
+    #[cfg(feature = "input")]
+    if let Some((keycode, time, state)) = window.pending_release.take() {
+        let event = &*(ev as *mut xcb_key_press_event_t);
+        let is_press = (event.response_type & !(1 << 7)) == XCB_KEY_PRESS;
+        let repeat = is_press && event.detail == keycode && event.time == time;
+        if !repeat {
+            release_core_key(window, details, keycode, state);
+        }
+    }
     let is_send_event = ((*ev).response_type >> 7) != 0;
     match (*ev).response_type & !(1 << 7) {
         XCB_CLIENT_MESSAGE => {
@@ -732,22 +2966,59 @@ unsafe fn process_event(ev: *mut xcb_generic_event_t, window: &mut WindowDetails
                 if event.client_data.data32[0] == details.atoms.wm_delete_window {
                     window.event_buffer.push(Event::CloseRequest)
                 } else if event.client_data.data32[0] == details.atoms._net_wm_ping {
-                    // data32[2] contains the window xid, that might be useful for something?
-                    event.window = (*details.screen).root;
-                    xcb_discard_reply(details.connection, xcb_send_event_checked(
-                        details.connection,
-                        false.into(),
-                        event.window,
-                        XCB_EVENT_MASK_SUBSTRUCTURE_NOTIFY | XCB_EVENT_MASK_SUBSTRUCTURE_REDIRECT,
-                        (event as *const _) as *const i8,
-                    ));
-                    let _ = xcb_flush(details.connection); // Makes sure the event is processed before we free it
+                    answer_ping(event, details);
                 }
             }
         },
         e @ XCB_FOCUS_IN | e @ XCB_FOCUS_OUT => {
-            let state = e == XCB_FOCUS_IN;
-            window.event_buffer.push(Event::Focus(state));
+            let event = &*(ev as *mut xcb_focus_in_event_t);
+            // With the input feature, these are only selected when XInput2 isn't there to report focus itself
+            #[cfg(feature = "input")]
+            if e == XCB_FOCUS_IN {
+                // Keys and buttons may have been let go of while some other window had focus
+                resync_input(details, window);
+            }
+            set_focus(window, e == XCB_FOCUS_IN, event.mode, event.detail);
+        },
+        // The rest of the core input events are only selected when XInput2 isn't available
+        #[cfg(feature = "input")]
+        XCB_KEY_PRESS => {
+            let event = &*(ev as *mut xcb_key_press_event_t);
+            // These don't say whether they're repeats, but with detectable autorepeat, a repeat is simply another
+            // press of a key that's already down. Without it, the release before it has been held back, so the
+            // key is still down here too.
+            let (index, bit) = (usize::from(event.detail / 8), 1 << (event.detail % 8));
+            let repeat = window.keys_held[index] & bit != 0;
+            window.keys_held[index] |= bit;
+            process_key(window, details, event.detail.into(), event.state.into(), true, repeat);
+        },
+        #[cfg(feature = "input")]
+        XCB_KEY_RELEASE => {
+            // Without detectable autorepeat, a repeat is sent as a release followed straight away by a press with
+            // the same time, so this waits for the next event to find out whether the key was really let go of
+            let event = &*(ev as *mut xcb_key_press_event_t);
+            window.pending_release = Some((event.detail, event.time, event.state));
+        },
+        #[cfg(feature = "input")]
+        e @ XCB_BUTTON_PRESS | e @ XCB_BUTTON_RELEASE => {
+            let event = &*(ev as *mut xcb_key_press_event_t);
+            process_button(window, event.detail.into(), event.state.into(), e == XCB_BUTTON_PRESS);
+        },
+        #[cfg(feature = "input")]
+        XCB_MOTION_NOTIFY => {
+            let event = &*(ev as *mut xcb_key_press_event_t);
+            let (x, y) = core_position(event.event_x, event.event_y);
+            window.event_buffer.push(Event::MouseMove { x, y });
+        },
+        #[cfg(feature = "input")]
+        XCB_ENTER_NOTIFY => {
+            let event = &*(ev as *mut xcb_enter_notify_event_t);
+            process_enter(window, details, core_position(event.event_x, event.event_y));
+        },
+        #[cfg(feature = "input")]
+        XCB_LEAVE_NOTIFY => {
+            window.cursor_inside = false;
+            window.event_buffer.push(Event::MouseLeave);
         },
         XCB_REPARENT_NOTIFY => {
             let event = &*(ev as *mut xcb_reparent_notify_event_t);
@@ -800,176 +3071,487 @@ unsafe fn process_event(ev: *mut xcb_generic_event_t, window: &mut WindowDetails
                             0,
                             !0,
                         ), std::ptr::null_mut());
-                        if prop.is_null() {
-                            return;
-                        }
-                        if (*prop).r#type != XCB_ATOM_ATOM || (*prop).format != 32 {
-                            free(prop.cast());
-                            return;
-                        }
-                        let len = xcb_get_property_value_length(prop);
-                        let data = xcb_get_property_value(prop) as *const xcb_atom_t;
-                        let len = match usize::try_from(len / 4) {
-                            Ok(l) => l,
-                            _ => {
-                                free(prop.cast());
-                                return
-                            },
-                        };
-                        let data_slice = std::slice::from_raw_parts(data, len);
-                        let minimised = data_slice.iter().any(|x| *x == details.atoms._net_wm_state_hidden);
-                        let maximised = (
-                            data_slice.iter().any(|x| *x == details.atoms._net_wm_state_maximized_horz),
-                            data_slice.iter().any(|x| *x == details.atoms._net_wm_state_maximized_vert),
-                        );
-                        free(prop.cast());
-
-                        if minimised && !window.state_minimised {
-                            if window.state_maximised == (true, true) {
-                                window.event_buffer.push(Event::Maximise(false));
-                            }
-                            window.event_buffer.push(Event::Minimise(true));
-                        } else if !minimised && window.state_minimised {
-                            window.event_buffer.push(Event::Minimise(false));
-                            if maximised == (true, true) {
-                                window.event_buffer.push(Event::Maximise(true));
-                            }
-                        } else if !minimised {
-                            if maximised == (true, true) && window.state_maximised != (true, true) {
-                                window.event_buffer.push(Event::Maximise(true));
-                            } else if maximised != (true, true) && window.state_maximised == (true, true) {
-                                window.event_buffer.push(Event::Maximise(false));
+                        // Not returning early here, as the event still has to be freed at the end
+                        if !prop.is_null() {
+                            let len = usize::try_from(xcb_get_property_value_length(prop) / 4).ok();
+                            let len = len.filter(|_| (*prop).r#type == XCB_ATOM_ATOM && (*prop).format == 32);
+                            if let Some(len) = len {
+                                let data = xcb_get_property_value(prop) as *const xcb_atom_t;
+                                let data_slice = std::slice::from_raw_parts(data, len);
+                                let hidden = data_slice.contains(&details.atoms._net_wm_state_hidden);
+                                let maximised = (
+                                    data_slice.contains(&details.atoms._net_wm_state_maximized_horz),
+                                    data_slice.contains(&details.atoms._net_wm_state_maximized_vert),
+                                );
+                                let fullscreen = data_slice.contains(&details.atoms._net_wm_state_fullscreen);
+                                update_net_wm_state(window, hidden, maximised, fullscreen);
                             }
+                            free(prop.cast());
                         }
-
-                        window.state_maximised = maximised;
-                        window.state_minimised = minimised;
                     },
                     XCB_PROPERTY_DELETE => {
                         // The whole state property got deleted for some reason?
-                        if window.state_minimised {
-                            window.event_buffer.push(Event::Minimise(false));
-                        } else if window.state_maximised == (true, true) {
-                            window.event_buffer.push(Event::Maximise(false));
-                        }
-                        window.state_minimised = false;
-                        window.state_maximised = (false, false);
+                        update_net_wm_state(window, false, (false, false), false);
                     },
                     _ => (),
                 }
+            } else if event.atom == details.atoms.wm_state && event.state == XCB_PROPERTY_NEW_VALUE {
+                // Window Managers without EWMH support only report iconification through ICCCM's WM_STATE,
+                // so this is checked as well as _NET_WM_STATE_HIDDEN. The window is minimised if either says so.
+                let prop = xcb_get_property_reply(details.connection, xcb_get_property(
+                    details.connection,
+                    0,
+                    window.handle,
+                    details.atoms.wm_state,
+                    details.atoms.wm_state,
+                    0,
+                    1,
+                ), std::ptr::null_mut());
+                // Not returning early here, as the event still has to be freed at the end
+                if !prop.is_null() {
+                    let state = if (*prop).format == 32 && xcb_get_property_value_length(prop) >= 4 {
+                        Some(*(xcb_get_property_value(prop) as *const u32))
+                    } else {
+                        None
+                    };
+                    free(prop.cast());
+                    if let Some(state) = state {
+                        set_icccm_iconic(window, state == ICCCM_ICONIC_STATE);
+                    }
+                }
             }
         },
-        XCB_MAP_NOTIFY => window.event_buffer.push(Event::Visible(true)),
-        XCB_UNMAP_NOTIFY => window.event_buffer.push(Event::Visible(false)),
-        #[cfg(feature = "input")]
+        XCB_MAP_NOTIFY => {
+            // A window can be mapped under the mouse without it moving, which doesn't always come with an Enter
+            #[cfg(feature = "input")]
+            {
+                window.cursor_inside = query_pointer(details.connection, window).1;
+            }
+            window.event_buffer.push(Event::Visible(true))
+        },
+        XCB_UNMAP_NOTIFY => {
+            #[cfg(feature = "input")]
+            {
+                window.cursor_inside = false;
+            }
+            window.event_buffer.push(Event::Visible(false))
+        },
         XCB_GE_GENERIC => {
             let event = &*(ev as *mut xcb_ge_generic_event_t);
-            if event.extension == details.extensions.xinput {
-                match event.event_type & !(1 << 7) {
-                    e @ XCB_INPUT_KEY_PRESS | e @ XCB_INPUT_KEY_RELEASE => {
-                        let is_press = e == XCB_INPUT_KEY_PRESS;
-                        let event = &*(ev as *mut xcb_input_key_press_event_t);
-                        let mut xevent = XKeyEvent {
-                            r#type: 2,
-                            serial: 0,
-                            send_event: 0,
-                            display: details.display,
-                            window: 0,
-                            root: 0,
-                            subwindow: 0,
-                            time: 0,
-                            x: 0,
-                            y: 0,
-                            x_root: 0,
-                            y_root: 0,
-                            state: event.mods.effective,
-                            keycode: event.detail,
-                            same_screen: 0,
-                        };
-                        let repeat = (event.flags & XCB_INPUT_KEY_EVENT_FLAGS_KEY_REPEAT) != 0;
-                        let f = if is_press {
-                            if repeat { Event::KeyboardRepeat } else { Event::KeyboardDown }
-                        } else {
-                            Event::KeyboardUp
-                        };
-                        let unmodified_keysym = XLookupKeysym(&mut xevent, 0);
-                        let mut modified_keysym: KeySym = 0;
-                        let _ = XLookupString(
-                            &mut xevent,
-                            std::ptr::null_mut(),
-                            0,
-                            &mut modified_keysym,
-                            std::ptr::null_mut(),
-                        );
-
-                        if let Some(k) = keysym_to_key(unmodified_keysym, modified_keysym) {
-                            window.event_buffer.push(f(k));
+            match event.extension {
+                ext if Some(ext) == details.extensions.present && event.event_type == XCB_PRESENT_COMPLETE_NOTIFY => {
+                    let event = std::ptr::read_unaligned(ev as *const xcb_present_complete_notify_event_t);
+                    let mut pacing = mutex_lock(&window.frame_pacing);
+                    if event.kind == XCB_PRESENT_COMPLETE_KIND_PIXMAP {
+                        // Whoever presented picked their own serial, so any connection's pixmaps are reported
+                        let (serial, ust, msc) = (event.serial, event.ust, event.msc); // copied out of the packed struct
+                        window.event_buffer.push(Event::PresentComplete { serial, ust, msc });
+                        let interval = vblank_interval(pacing.last_present, (ust, msc));
+                        if interval != 0 {
+                            pacing.frame_time = interval;
                         }
-
-                        if is_press {
-                            if let Ok(utf32) = u32::try_from(modified_keysym) {
-                                if let Some(ch) = char::from_u32(xkb_keysym_to_utf32(utf32)) {
-                                    if ch != '\0' {
-                                        window.event_buffer.push(Event::Input(ch));
-                                    }
-                                }
+                        pacing.last_present = (ust, msc);
+                    }
+                    if event.kind == XCB_PRESENT_COMPLETE_KIND_NOTIFY_MSC && pacing.armed {
+                        pacing.armed = false;
+                        if pacing.enabled {
+                            // Present only reports when the last vblank happened, so the next one is extrapolated
+                            // from the previous notification. MSCs may have been skipped between the two.
+                            let interval = vblank_interval((pacing.last_ust, pacing.last_msc), (event.ust, event.msc));
+                            if interval != 0 {
+                                pacing.frame_time = interval;
                             }
+                            pacing.last_ust = event.ust;
+                            pacing.last_msc = event.msc;
+                            window.event_buffer.push(Event::RedrawRequested(event.ust + interval));
+                            _ = xcb_present_notify_msc(details.connection, window.handle, 0, event.msc + 1, 0, 0);
+                            pacing.armed = true;
+                        } else {
+                            pacing.last_msc = 0;
                         }
+                    }
+                },
+                #[cfg(feature = "input")]
+                ext if Some(ext) == details.extensions.xinput => match event.event_type & !(1 << 7) {
+                    e @ XCB_INPUT_KEY_PRESS | e @ XCB_INPUT_KEY_RELEASE => {
+                        let event = &*(ev as *mut xcb_input_key_press_event_t);
+                        let repeat = (event.flags & XCB_INPUT_KEY_EVENT_FLAGS_KEY_REPEAT) != 0;
+                        let is_press = e == XCB_INPUT_KEY_PRESS;
+                        process_key(window, details, event.detail, event.mods.effective, is_press, repeat);
                     },
                     e @ XCB_INPUT_BUTTON_PRESS | e @ XCB_INPUT_BUTTON_RELEASE => {
-                        use crate::input::MouseButton;
                         let event = &*(ev as *mut xcb_input_button_press_event_t);
-                        let f = if e == XCB_INPUT_BUTTON_PRESS { Event::MouseDown } else { Event::MouseUp };
-                        match event.detail {
-                            1 => window.event_buffer.push(f(MouseButton::Left)),
-                            2 => window.event_buffer.push(f(MouseButton::Middle)),
-                            3 => window.event_buffer.push(f(MouseButton::Right)),
-                            4 if e == XCB_INPUT_BUTTON_PRESS => window.event_buffer.push(Event::ScrollUp),
-                            5 if e == XCB_INPUT_BUTTON_PRESS => window.event_buffer.push(Event::ScrollDown),
-                            _ => (),
-                        }
+                        process_button(window, event.detail, event.mods.effective, e == XCB_INPUT_BUTTON_PRESS);
                     },
                     XCB_INPUT_MOTION => {
                         let event = &*(ev as *mut xcb_input_motion_event_t);
-                        window.event_buffer.push(Event::MouseMove(((event.event_x >> 16) as _, (event.event_y >> 16) as _)))
+                        let (x, y) = fp1616_position(event.event_x, event.event_y);
+                        window.event_buffer.push(Event::MouseMove { x, y })
                     },
                     XCB_INPUT_ENTER => {
-                        let _event = &*(ev as *mut xcb_input_enter_event_t);
-                        window.event_buffer.push(Event::MouseEnter);
+                        let event = &*(ev as *mut xcb_input_enter_event_t);
+                        process_enter(window, details, fp1616_position(event.event_x, event.event_y));
                     },
                     XCB_INPUT_LEAVE => {
                         let _event = &*(ev as *mut xcb_input_leave_event_t);
+                        window.cursor_inside = false;
                         window.event_buffer.push(Event::MouseLeave);
                     },
                     e @ XCB_INPUT_FOCUS_IN | e @ XCB_INPUT_FOCUS_OUT => {
-                        let state = e == XCB_INPUT_FOCUS_IN;
-                        window.event_buffer.push(Event::Focus(state))
+                        let event = &*(ev as *mut xcb_input_enter_event_t);
+                        if e == XCB_INPUT_FOCUS_IN {
+                            // Keys and buttons may have been let go of while some other window had focus
+                            resync_input(details, window);
+                        }
+                        set_focus(window, e == XCB_INPUT_FOCUS_IN, event.mode, event.detail);
+                    },
+                    XCB_INPUT_HIERARCHY => {
+                        let event = &*(ev as *mut xcb_input_hierarchy_event_t);
+                        let infos = std::slice::from_raw_parts(
+                            (ev as *const u8).add(36).cast::<xcb_input_hierarchy_info_t>(),
+                            event.num_infos.into(),
+                        );
+                        const ADDED: u32 = XCB_INPUT_HIERARCHY_MASK_MASTER_ADDED | XCB_INPUT_HIERARCHY_MASK_SLAVE_ADDED;
+                        const REMOVED: u32 =
+                            XCB_INPUT_HIERARCHY_MASK_MASTER_REMOVED | XCB_INPUT_HIERARCHY_MASK_SLAVE_REMOVED;
+                        for info in infos {
+                            let id = DeviceId(info.deviceid.into());
+                            if info.flags & ADDED != 0 {
+                                window.event_buffer.push(Event::DeviceAdded(id));
+                            }
+                            if info.flags & REMOVED != 0 {
+                                window.event_buffer.push(Event::DeviceRemoved(id));
+                            }
+                        }
                     },
                     _ => (),
-                }
+                },
+                _ => (),
             }
         },
+        ty if details.extensions.randr.map_or(false, |base| {
+            ty == base + XCB_RANDR_SCREEN_CHANGE_NOTIFY || ty == base + XCB_RANDR_NOTIFY
+        }) => {
+            // One change usually comes with several notifications, so rather than decode them
+            // we compare the current layout against the one from last time
+            let monitors = query_monitors(details);
+            monitor::monitor_changes(&window.monitors, &monitors, |event| window.event_buffer.push(event));
+            window.monitors = monitors;
+        },
         _ => (),
     };
     free(ev.cast());
 }
 
+// Reports a key being pressed or released, and any text it types, for both XInput2 and the core protocol.
+// `state` is the modifier mask from before the event.
+#[cfg(feature = "input")]
+unsafe fn process_key(
+    window: &mut WindowDetails,
+    details: &ConnectionDetails,
+    keycode: u32,
+    state: u32,
+    is_press: bool,
+    repeat: bool,
+) {
+    let mut xevent = XKeyEvent {
+        r#type: 2,
+        serial: 0,
+        send_event: 0,
+        display: details.display,
+        window: 0,
+        root: 0,
+        subwindow: 0,
+        time: 0,
+        x: 0,
+        y: 0,
+        x_root: 0,
+        y_root: 0,
+        state,
+        keycode,
+        same_screen: 0,
+    };
+    window.raw_modifiers = RawModifiers(state);
+    let f = if is_press {
+        if repeat { Event::KeyboardRepeat } else { Event::KeyboardDown }
+    } else {
+        Event::KeyboardUp
+    };
+    let key_modifiers = key_modifiers(state);
+    let unmodified_keysym = XLookupKeysym(&mut xevent, 0);
+    let mut modified_keysym: KeySym = 0;
+    let _ = XLookupString(&mut xevent, std::ptr::null_mut(), 0, &mut modified_keysym, std::ptr::null_mut());
+
+    if let Some(k) = keysym_to_key(unmodified_keysym, modified_keysym) {
+        window.modifiers.update(k, is_press);
+        if !repeat || window.key_repeat.load(Ordering::Relaxed) {
+            window.event_buffer.push(f(k, key_modifiers));
+        }
+    }
+
+    if is_press && window.text_input.load(Ordering::Relaxed) {
+        if let Ok(keysym) = u32::try_from(modified_keysym) {
+            match window.compose.feed(keysym) {
+                Compose::Key(keysym) => {
+                    if let Some(ch) = char::from_u32(xkb_keysym_to_utf32(keysym)) {
+                        if ch != '\0' {
+                            window.event_buffer.push(Event::Input(ch));
+                        }
+                    }
+                },
+                Compose::Text(text) => {
+                    window.event_buffer.extend(text.chars().map(Event::Input));
+                },
+                Compose::Pending => (),
+            }
+        }
+    }
+}
+
+// Reports a core key release which `process_event` held back, once it's known not to be part of a repeat
+#[cfg(feature = "input")]
+unsafe fn release_core_key(window: &mut WindowDetails, details: &ConnectionDetails, keycode: u8, state: u16) {
+    window.keys_held[usize::from(keycode / 8)] &= !(1 << (keycode % 8));
+    process_key(window, details, keycode.into(), state.into(), false, false);
+}
+
+// Reports a mouse button being pressed or released, or the wheel being scrolled, for both XInput2 and the core
+// protocol, which number buttons the same way.
+#[cfg(feature = "input")]
+fn process_button(window: &mut WindowDetails, detail: u32, state: u32, is_press: bool) {
+    let f = if is_press { Event::MouseDown } else { Event::MouseUp };
+    window.raw_modifiers = RawModifiers(state);
+    let button = match detail {
+        1 => Some(MouseButton::Left),
+        2 => Some(MouseButton::Middle),
+        3 => Some(MouseButton::Right),
+        // The wheel sends a press and a release for every notch, so only the presses count
+        4..=7 => {
+            if is_press {
+                let (dx, dy) = match detail {
+                    4 => (0, SCROLL_NOTCH),
+                    5 => (0, -SCROLL_NOTCH),
+                    6 => (-SCROLL_NOTCH, 0),
+                    _ => (SCROLL_NOTCH, 0),
+                };
+                window.event_buffer.push(Event::Scroll { dx, dy });
+            }
+            None
+        },
+        8 => Some(MouseButton::X1),
+        9 => Some(MouseButton::X2),
+        _ => None,
+    };
+    if let Some(button) = button {
+        window.mouse_buttons.update(button, is_press);
+        window.event_buffer.push(f(button));
+    }
+}
+
+// Reports the mouse entering the window at `position`
+#[cfg(feature = "input")]
+unsafe fn process_enter(window: &mut WindowDetails, details: &ConnectionDetails, (x, y): (f32, f32)) {
+    window.mouse_buttons = query_pointer(details.connection, window).0;
+    window.cursor_inside = true;
+    window.event_buffer.push(Event::MouseEnter);
+    // Windows always follows up with a move to where the mouse came in, so we do the same
+    window.event_buffer.push(Event::MouseMove { x, y });
+}
+
+// Converts XInput2's 16.16 fixed-point coordinates to the pixels reported by `MouseMove`, keeping the fraction
+#[cfg(feature = "input")]
+fn fp1616_position(x: xcb_input_fp1616_t, y: xcb_input_fp1616_t) -> (f32, f32) {
+    (x as f32 / 65536.0, y as f32 / 65536.0)
+}
+
+// Converts the core protocol's whole pixel coordinates in the same way as `fp1616_position`
+#[cfg(feature = "input")]
+fn core_position(x: i16, y: i16) -> (f32, f32) {
+    (f32::from(x), f32::from(y))
+}
+
+// Works out why focus changed from the `mode` and `detail` of a focus event. Core and XInput2 events use the same
+// values for both, except that XInput2 adds two modes for passive grabs, which count as grabs too.
+fn focus_reason(mode: u8, detail: u8) -> FocusReason {
+    const NOTIFY_NORMAL: u8 = 0;
+    const NOTIFY_POINTER: u8 = 5;
+    const NOTIFY_POINTER_ROOT: u8 = 6;
+    match (mode, detail) {
+        (NOTIFY_NORMAL, NOTIFY_POINTER) | (NOTIFY_NORMAL, NOTIFY_POINTER_ROOT) => FocusReason::Pointer,
+        (NOTIFY_NORMAL, _) => FocusReason::Normal,
+        _ => FocusReason::Grab,
+    }
+}
+
+// Records a new _NET_WM_STATE, or its deletion as all `false`
+fn update_net_wm_state(window: &mut WindowDetails, hidden: bool, maximised: (bool, bool), fullscreen: bool) {
+    // The maximised atoms are left alone while the window is fullscreen, so leaving fullscreen
+    // puts it back how it was and no Maximise event is needed either way
+    if fullscreen != window.state_fullscreen {
+        window.event_buffer.push(Event::Fullscreen(fullscreen));
+        window.state_fullscreen = fullscreen;
+    }
+
+    let was_minimised = window.minimised();
+    window.state_net_hidden = hidden;
+    let minimised = window.minimised();
+    if minimised && !was_minimised {
+        if window.state_maximised == (true, true) {
+            window.event_buffer.push(Event::Maximise(false));
+        }
+        window.event_buffer.push(Event::Minimise(true));
+    } else if !minimised && was_minimised {
+        window.event_buffer.push(Event::Minimise(false));
+        if maximised == (true, true) {
+            window.event_buffer.push(Event::Maximise(true));
+        }
+    } else if !minimised {
+        if maximised == (true, true) && window.state_maximised != (true, true) {
+            window.event_buffer.push(Event::Maximise(true));
+        } else if maximised != (true, true) && window.state_maximised == (true, true) {
+            window.event_buffer.push(Event::Maximise(false));
+        }
+    }
+    window.state_maximised = maximised;
+}
+
+// Records a new ICCCM WM_STATE, which only says whether the window's iconified
+fn set_icccm_iconic(window: &mut WindowDetails, iconic: bool) {
+    let was_minimised = window.minimised();
+    window.state_icccm_iconic = iconic;
+    if window.minimised() != was_minimised {
+        window.event_buffer.push(Event::Minimise(!was_minimised));
+    }
+}
+
+// Records a focus change from either protocol, which report it the same way
+fn set_focus(window: &mut WindowDetails, focused: bool, mode: u8, detail: u8) {
+    window.focused = focused;
+    window.event_buffer.push(Event::Focus { focused, reason: focus_reason(mode, detail) });
+}
+
+// Whether the pointer should be grabbed, to keep it inside the window while it's confined and focused
+fn wants_pointer_grab(window: &WindowDetails) -> bool {
+    window.focused && window.cursor_confined.load(Ordering::Relaxed)
+}
+
+// Grabs or releases the pointer to keep it inside the window while it's confined and focused.
+// assumes we hold connection lock
+unsafe fn update_pointer_grab(c: *mut xcb_connection_t, window: &WindowDetails) {
+    let wanted = wants_pointer_grab(window);
+    if wanted == window.pointer_grabbed.load(Ordering::Relaxed) {
+        return
+    }
+    if wanted {
+        // With `owner_events` set, the pointer events keep going to the window as if there were no grab
+        let cookie = xcb_grab_pointer(
+            c,
+            true.into(),
+            window.handle,
+            0,
+            XCB_GRAB_MODE_ASYNC,
+            XCB_GRAB_MODE_ASYNC,
+            window.handle,
+            XCB_NONE as u32,
+            XCB_CURRENT_TIME,
+        );
+        let mut err: *mut xcb_generic_error_t = std::ptr::null_mut();
+        let reply = xcb_grab_pointer_reply(c, cookie, &mut err);
+        if !reply.is_null() {
+            // The status may be AlreadyGrabbed, NotViewable or Frozen, which can all pass, so it's tried again later
+            window.pointer_grabbed.store((*reply).status == XCB_GRAB_STATUS_SUCCESS, Ordering::Relaxed);
+            free(reply.cast());
+        } else if !err.is_null() {
+            free(err.cast());
+        }
+    } else {
+        _ = xcb_ungrab_pointer(c, XCB_CURRENT_TIME);
+        _ = xcb_flush(c);
+        window.pointer_grabbed.store(false, Ordering::Relaxed);
+    }
+}
+
 // assumes we hold connection lock
 unsafe fn internal_set_maximised(c: *mut xcb_connection_t, xid: xcb_window_t, details: &ConnectionDetails, maximised: bool) {
-    let action = if maximised { 1 } else { 0 };
+    let atoms = [details.atoms._net_wm_state_maximized_horz, details.atoms._net_wm_state_maximized_vert];
+    internal_set_net_wm_state(c, xid, details, &atoms, maximised)
+}
+
+// assumes we hold connection lock
+// Adds or removes one or two `_NET_WM_STATE` atoms, which is as many as a single client message can change
+unsafe fn internal_set_net_wm_state(
+    c: *mut xcb_connection_t,
+    xid: xcb_window_t,
+    details: &ConnectionDetails,
+    state_atoms: &[xcb_atom_t],
+    enabled: bool,
+) {
+    if is_managed(c, xid, &details.atoms) {
+        send_net_wm_state(c, xid, details, state_atoms, enabled);
+    } else {
+        // A withdrawn window isn't managed, so a client message would be ignored. The spec has us edit the
+        // property ourselves instead, and the Window Manager reads it when the window is mapped.
+        let mut state = get_net_wm_state(c, xid, &details.atoms);
+        edit_net_wm_state(&mut state, state_atoms, enabled);
+        set_net_wm_state(c, xid, &details.atoms, &state);
+    }
+}
+
+// Whether the Window Manager is managing the window, which it shows by setting WM_STATE (ICCCM 4.1.3.1)
+unsafe fn is_managed(c: *mut xcb_connection_t, xid: xcb_window_t, atoms: &Atoms) -> bool {
+    get_property(c, xid, atoms.wm_state, atoms.wm_state).is_some()
+}
+
+unsafe fn get_net_wm_state(c: *mut xcb_connection_t, xid: xcb_window_t, atoms: &Atoms) -> Vec<xcb_atom_t> {
+    get_property(c, xid, atoms._net_wm_state, XCB_ATOM_ATOM)
+        .unwrap_or_default()
+        .chunks_exact(4)
+        .map(|x| u32::from_ne_bytes([x[0], x[1], x[2], x[3]]))
+        .collect()
+}
+
+unsafe fn set_net_wm_state(c: *mut xcb_connection_t, xid: xcb_window_t, atoms: &Atoms, state: &[xcb_atom_t]) {
+    _ = xcb_change_property(
+        c,
+        XCB_PROP_MODE_REPLACE,
+        xid,
+        atoms._net_wm_state,
+        XCB_ATOM_ATOM,
+        32,
+        state.len() as u32,
+        state.as_ptr().cast(),
+    );
+}
+
+// Adds or removes `state_atoms` in a copy of a withdrawn window's _NET_WM_STATE. Any 0 is left out, since that's
+// how a client message says there's no second atom.
+fn edit_net_wm_state(state: &mut Vec<xcb_atom_t>, state_atoms: &[xcb_atom_t], enabled: bool) {
+    let state_atoms = state_atoms.iter().copied().filter(|&atom| atom != 0);
+    state.retain(|atom| !state_atoms.clone().any(|x| x == *atom));
+    if enabled {
+        state.extend(state_atoms);
+    }
+}
+
+// Asks the Window Manager to add or remove one or two `_NET_WM_STATE` atoms of a window it's managing
+unsafe fn send_net_wm_state(
+    c: *mut xcb_connection_t,
+    xid: xcb_window_t,
+    details: &ConnectionDetails,
+    state_atoms: &[xcb_atom_t],
+    enabled: bool,
+) {
+    // Window Managers which don't follow the EWMH spec ignore this, so the window just stays as it is
+    let action = if enabled { 1 } else { 0 };
     let client_message = xcb_client_message_event_t {
         response_type: XCB_CLIENT_MESSAGE,
         format: 32,
         sequence: 0,
         window: xid,
         r#type: details.atoms._net_wm_state,
-        client_data: ClientData { data32: [
-            action,
-            details.atoms._net_wm_state_maximized_horz,
-            details.atoms._net_wm_state_maximized_vert,
-            1,
-            0,
-        ] },
+        client_data: ClientData {
+            data32: [action, state_atoms[0], state_atoms.get(1).copied().unwrap_or(0), 1, 0],
+        },
     };
     xcb_discard_reply(c, xcb_send_event_checked(
         c,
@@ -992,6 +3574,8 @@ unsafe fn internal_set_title(c: *mut xcb_connection_t, xid: xcb_window_t, atoms:
         title.bytes().len() as _,
         title.as_ptr().cast(),
     );
+    // WM_NAME is a STRING, which is Latin-1, for Window Managers which are too old to know about _NET_WM_NAME
+    let latin1 = latin1_lossy(title);
     let _ = xcb_change_property(
         c,
         XCB_PROP_MODE_REPLACE,
@@ -999,13 +3583,216 @@ unsafe fn internal_set_title(c: *mut xcb_connection_t, xid: xcb_window_t, atoms:
         XCB_ATOM_WM_NAME,
         XCB_ATOM_STRING,
         8,
-        title.bytes().len() as _,
-        title.as_ptr().cast(),
+        latin1.len() as _,
+        latin1.as_ptr().cast(),
+    );
+}
+
+// Whether a `_NET_WM_ICON` of this size can be sent in one ChangeProperty request. A request that's too long
+// would make xcb shut down the connection.
+unsafe fn icon_fits_request(c: *mut xcb_connection_t, width: u32, height: u32) -> bool {
+    // The maximum is in 4-byte units, the same as the icon's pixels, and ChangeProperty's header takes 6 of them
+    let units = 2 + u64::from(width) * u64::from(height);
+    units + 6 <= u64::from(xcb_get_maximum_request_length(c))
+}
+
+// assumes we hold connection lock, and that the icon has been checked with `icon_fits_request`
+unsafe fn set_net_wm_icon(c: *mut xcb_connection_t, atoms: &Atoms, xid: xcb_window_t, rgba: &[u8], w: u32, h: u32) {
+    let icon = net_wm_icon(rgba, w, h);
+    _ = xcb_change_property(
+        c,
+        XCB_PROP_MODE_REPLACE,
+        xid,
+        atoms._net_wm_icon,
+        XCB_ATOM_CARDINAL,
+        32,
+        icon.len() as u32,
+        icon.as_ptr().cast(),
     );
 }
 
+// Lays out an icon as `_NET_WM_ICON` wants it: the width and height, then a CARDINAL per pixel with alpha in the
+// top byte, followed by red, green and blue. Like most Window Managers, we take the alpha to be straight.
+fn net_wm_icon(rgba: &[u8], width: u32, height: u32) -> Vec<u32> {
+    let pixels = rgba.chunks_exact(4).map(|p| u32::from_be_bytes([p[3], p[0], p[1], p[2]]));
+    [width, height].iter().copied().chain(pixels).collect()
+}
+
+// The value of _NET_WM_WINDOW_OPACITY for an opacity from 0 to 1, where u32::MAX is fully opaque
+fn opacity_cardinal(opacity: f32) -> u32 {
+    (f64::from(opacity) * f64::from(u32::MAX)).round() as u32
+}
+
+// Encodes a string as Latin-1, replacing any characters it can't represent with '?'
+fn latin1_lossy(text: &str) -> Vec<u8> {
+    text.chars().map(|ch| u8::try_from(u32::from(ch)).unwrap_or(b'?')).collect()
+}
+
+#[cfg(feature = "input")]
+use crate::input::{DeviceId, Key, KeyModifiers, Modifiers, MouseButton, MouseButtons, RawModifiers};
+#[cfg(feature = "input")]
+use crate::event::SCROLL_NOTCH;
+
+// The locale to look up Compose sequences for. libxkbcommon leaves this to us, and suggests these variables.
+#[cfg(feature = "input")]
+fn compose_locale() -> std::ffi::CString {
+    use std::os::unix::ffi::OsStringExt;
+    ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .filter_map(std::env::var_os)
+        .find(|value| !value.is_empty())
+        .and_then(|value| std::ffi::CString::new(value.into_vec()).ok())
+        .unwrap_or_else(|| std::ffi::CString::new("C").unwrap())
+}
+
+// A window's progress through a dead key or Compose sequence. Only touched from `poll_events`, which has the
+// window borrowed mutably, so it's fine to send between threads.
+#[cfg(feature = "input")]
+struct ComposeState(*mut xkb_compose_state); // null if there's no Compose table
+#[cfg(feature = "input")]
+unsafe impl Send for ComposeState {}
+#[cfg(feature = "input")]
+unsafe impl Sync for ComposeState {}
+
+#[cfg(feature = "input")]
+enum Compose {
+    Key(u32),     // not part of a sequence, so it's typed as normal
+    Text(String), // a sequence was finished
+    Pending,      // the key was taken up by a sequence which isn't done yet, or was cancelled
+}
+
+#[cfg(feature = "input")]
+impl ComposeState {
+    unsafe fn new(table: *mut xkb_compose_table) -> Self {
+        if table.is_null() {
+            return Self(std::ptr::null_mut())
+        }
+        Self(xkb_compose_state_new(table, XKB_COMPOSE_STATE_NO_FLAGS))
+    }
+
+    // Feeds in the keysym of a pressed key
+    unsafe fn feed(&mut self, keysym: u32) -> Compose {
+        if self.0.is_null() || xkb_compose_state_feed(self.0, keysym) == XKB_COMPOSE_FEED_IGNORED {
+            return Compose::Key(keysym)
+        }
+        match xkb_compose_state_get_status(self.0) {
+            XKB_COMPOSE_COMPOSING => Compose::Pending,
+            XKB_COMPOSE_COMPOSED => {
+                let mut buffer = [0 as c_char; 64];
+                let len = xkb_compose_state_get_utf8(self.0, buffer.as_mut_ptr(), buffer.len());
+                let len = usize::try_from(len).unwrap_or(0).min(buffer.len() - 1);
+                let bytes = std::slice::from_raw_parts(buffer.as_ptr().cast::<u8>(), len);
+                // Some sequences only produce a keysym, like one for a function key, and have no text of their own
+                let result = if bytes.is_empty() {
+                    Compose::Key(xkb_compose_state_get_one_sym(self.0))
+                } else {
+                    Compose::Text(String::from_utf8_lossy(bytes).into_owned())
+                };
+                xkb_compose_state_reset(self.0);
+                result
+            },
+            // A key which doesn't fit the sequence ends it, and isn't typed itself
+            XKB_COMPOSE_CANCELLED => {
+                xkb_compose_state_reset(self.0);
+                Compose::Pending
+            },
+            _ => Compose::Key(keysym),
+        }
+    }
+}
+
+#[cfg(feature = "input")]
+impl Drop for ComposeState {
+    fn drop(&mut self) {
+        if !self.0.is_null() {
+            unsafe { xkb_compose_state_unref(self.0) };
+        }
+    }
+}
+
+// Decodes an XInput `mods.effective` mask with the usual modifier mapping
+#[cfg(feature = "input")]
+fn key_modifiers(mask: u32) -> KeyModifiers {
+    KeyModifiers {
+        shift: mask & XCB_MOD_MASK_SHIFT != 0,
+        control: mask & XCB_MOD_MASK_CONTROL != 0,
+        alt: mask & XCB_MOD_MASK_1 != 0,
+        super_: mask & XCB_MOD_MASK_4 != 0,
+        caps_lock: mask & XCB_MOD_MASK_LOCK != 0,
+        num_lock: mask & XCB_MOD_MASK_2 != 0,
+    }
+}
+
+// Asks the server which mouse buttons are held right now, and whether the mouse is inside the window.
+// The server only tells us where the mouse is, so it counts as inside even if another window is covering it.
+#[cfg(feature = "input")]
+unsafe fn query_pointer(c: *mut xcb_connection_t, window: &WindowDetails) -> (MouseButtons, bool) {
+    let mut buttons = MouseButtons::new();
+    let mut inside = false;
+    let reply = xcb_query_pointer_reply(c, xcb_query_pointer(c, window.handle), std::ptr::null_mut());
+    if !reply.is_null() {
+        let r = &*reply;
+        buttons.update(MouseButton::Left, r.mask & XCB_BUTTON_MASK_1 != 0);
+        buttons.update(MouseButton::Middle, r.mask & XCB_BUTTON_MASK_2 != 0);
+        buttons.update(MouseButton::Right, r.mask & XCB_BUTTON_MASK_3 != 0);
+        // The core protocol's mask stops at button 5, so the side buttons keep whatever state we last saw
+        buttons.update(MouseButton::X1, window.mouse_buttons.x1());
+        buttons.update(MouseButton::X2, window.mouse_buttons.x2());
+        let (width, height) = window.size;
+        inside = r.same_screen != 0
+            && (0..i32::from(width)).contains(&i32::from(r.win_x))
+            && (0..i32::from(height)).contains(&i32::from(r.win_y));
+        free(reply.cast());
+    }
+    (buttons, inside)
+}
+
+// Sends the `KeyboardUp` and `MouseUp` events which were missed for any modifier keys and mouse buttons which
+// we think are held but the server says aren't, such as ones released while another window had focus
 #[cfg(feature = "input")]
-use crate::input::Key;
+unsafe fn resync_input(details: &ConnectionDetails, window: &mut WindowDetails) {
+    let c = details.connection;
+    let reply = xcb_query_keymap_reply(c, xcb_query_keymap(c), std::ptr::null_mut());
+    if !reply.is_null() {
+        let keys = (*reply).keys;
+        free(reply.cast());
+        window.keys_held = keys;
+        for key in window.modifiers.held_keys() {
+            let keysyms: &[KeySym] = match key {
+                Key::LeftShift => &[0xFFE1],
+                Key::RightShift => &[0xFFE2],
+                Key::LeftControl => &[0xFFE3],
+                Key::RightControl => &[0xFFE4],
+                Key::LeftAlt => &[0xFFE9],
+                Key::RightAlt => &[0xFFEA, 0xFE03], // or AltGr
+                Key::LeftSuper => &[0xFFEB],
+                Key::RightSuper => &[0xFFEC],
+                _ => continue,
+            };
+            // Keysyms which aren't on the keyboard map to keycode 0, so if none are there the key is left alone
+            let keycodes = keysyms.iter().map(|keysym| usize::from(XKeysymToKeycode(details.display, *keysym)));
+            let (mut on_keyboard, mut held) = (false, false);
+            for keycode in keycodes.filter(|keycode| *keycode != 0) {
+                on_keyboard = true;
+                held |= keys[keycode / 8] & (1 << (keycode % 8)) != 0;
+            }
+            if on_keyboard && !held {
+                window.modifiers.update(key, false);
+                window.event_buffer.push(Event::KeyboardUp(key, key_modifiers(window.raw_modifiers.0)));
+            }
+        }
+    }
+
+    let (buttons, inside) = query_pointer(c, window);
+    window.cursor_inside = inside;
+    for button in [MouseButton::Left, MouseButton::Middle, MouseButton::Right, MouseButton::X1, MouseButton::X2] {
+        if window.mouse_buttons.is_pressed(button) && !buttons.is_pressed(button) {
+            window.event_buffer.push(Event::MouseUp(button));
+        }
+    }
+    window.mouse_buttons = buttons;
+}
+
 #[cfg(feature = "input")]
 fn keysym_to_key(keysym: KeySym, keysym2: KeySym) -> Option<Key> {
     // This function converts a keysym, as returned by XLookupKeysym, to a ramen key.
@@ -1013,6 +3800,7 @@ fn keysym_to_key(keysym: KeySym, keysym2: KeySym) -> Option<Key> {
     // however, XLookupKeysym ignores all modifiers, so this function should only receive "base" keysym values.
     // To avoid some annoying situations we also request keysym2 which is the modified keysym.
     // Values mostly copied from <X11/keysymdef.h>
+    // Anything else is quietly dropped, as there's no `Key` for it and the application can't do anything about it
     match keysym {
         0x20 => Some(Key::Space),
         0x22 => Some(Key::Quote),
@@ -1073,8 +3861,11 @@ fn keysym_to_key(keysym: KeySym, keysym2: KeySym) -> Option<Key> {
         0x7B => Some(Key::BraceLeft),
         0x7C => Some(Key::Pipe),
         0x7D => Some(Key::BraceRight),
+        0xFE03 => Some(Key::RightAlt), // ISO_Level3_Shift, which is what AltGr sends on most layouts that have it
+        0xFE20 => Some(Key::Tab), // ISO_Left_Tab, for layouts which put it on the base level
         0xFF08 => Some(Key::Backspace),
         0xFF09 => Some(Key::Tab),
+        0xFF0B => Some(Key::Clear),
         0xFF0D => Some(Key::Return),
         0xFF13 => Some(Key::Pause),
         0xFF14 => Some(Key::ScrollLock),
@@ -1088,7 +3879,13 @@ fn keysym_to_key(keysym: KeySym, keysym2: KeySym) -> Option<Key> {
         0xFF56 => Some(Key::PageDown),
         0xFF57 => Some(Key::End),
         0xFF58 => Some(Key::Home), // From numpad keysym names I'm pretty confident Begin and Home mean the same thing
+        0xFF60 => Some(Key::Select),
+        0xFF61 => Some(Key::PrintScreen),
+        0xFF62 => Some(Key::Execute),
         0xFF63 => Some(Key::Insert),
+        0xFF67 => Some(Key::Applications), // Menu
+        0xFF6A => Some(Key::Help),
+        0xFF6B => Some(Key::Pause), // Break, which is Pause with control held
         0xFF7F => Some(Key::NumLock),
         0xFFBE => Some(Key::F1),
         0xFFBF => Some(Key::F2),
@@ -1124,6 +3921,28 @@ fn keysym_to_key(keysym: KeySym, keysym2: KeySym) -> Option<Key> {
         0xFFEB => Some(Key::LeftSuper),
         0xFFEC => Some(Key::RightSuper),
         0xFFFF => Some(Key::Delete),
+
+        // Multimedia and browser keys, from <X11/XF86keysym.h>
+        0x1008FF11 => Some(Key::MediaVolumeDown),
+        0x1008FF12 => Some(Key::MediaVolumeMute),
+        0x1008FF13 => Some(Key::MediaVolumeUp),
+        0x1008FF14 => Some(Key::MediaPlayPause),
+        0x1008FF15 => Some(Key::MediaStop),
+        0x1008FF16 => Some(Key::MediaPreviousTrack),
+        0x1008FF17 => Some(Key::MediaNextTrack),
+        0x1008FF18 => Some(Key::BrowserHome),
+        0x1008FF19 => Some(Key::LaunchMail),
+        0x1008FF1B => Some(Key::BrowserSearch),
+        0x1008FF1D => Some(Key::LaunchApplication2), // Calculator, which is what Windows keyboards send this for
+        0x1008FF26 => Some(Key::BrowserBack),
+        0x1008FF27 => Some(Key::BrowserForward),
+        0x1008FF28 => Some(Key::BrowserStop),
+        0x1008FF29 => Some(Key::BrowserRefresh),
+        0x1008FF2F => Some(Key::Sleep),
+        0x1008FF30 => Some(Key::BrowserFavourites),
+        0x1008FF31 => Some(Key::MediaPlayPause), // AudioPause, sent by keyboards with separate play and pause keys
+        0x1008FF32 => Some(Key::LaunchMediaSelect),
+        0x1008FF33 => Some(Key::LaunchApplication1), // MyComputer, likewise
         
         (0xFF80..=0xFFB9) => match keysym2 {
             // We use the modified keysym for numpad keys because modifiers actually change our mapping rules
@@ -1167,3 +3986,218 @@ fn keysym_to_key(keysym: KeySym, keysym2: KeySym) -> Option<Key> {
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::window::{Controls, Style};
+
+    #[test]
+    fn borderless_keeps_mwm_functions() {
+        let style = Style::new().controls(Some(Controls::new().maximise(false)));
+        let initial = merge_mwm_hints(None, &style, MWM_HINTS_FUNCTIONS | MWM_HINTS_DECORATIONS);
+        assert_ne!(initial.functions & MWM_FUNC_MINIMIZE, 0);
+        assert_eq!(initial.functions & MWM_FUNC_MAXIMIZE, 0);
+
+        let borderless = merge_mwm_hints(Some(initial), &style.borderless(true), MWM_HINTS_DECORATIONS);
+        assert_eq!(borderless.decorations, 0);
+        assert_eq!(borderless.functions, initial.functions);
+        assert_eq!(borderless.input_mode, initial.input_mode);
+
+        let restored = merge_mwm_hints(Some(borderless), &style, MWM_HINTS_DECORATIONS);
+        assert_eq!(restored, initial);
+    }
+
+    #[test]
+    fn mode_refresh_rates() {
+        let mode = |dot_clock, htotal, vtotal, mode_flags| xcb_randr_mode_info_t {
+            id: 0,
+            width: 0,
+            height: 0,
+            dot_clock,
+            hsync_start: 0,
+            hsync_end: 0,
+            htotal,
+            hskew: 0,
+            vsync_start: 0,
+            vsync_end: 0,
+            vtotal,
+            name_len: 0,
+            mode_flags,
+        };
+        assert_eq!(mode_refresh_rate(&mode(148_500_000, 2200, 1125, 0)), 60000);
+        assert_eq!(mode_refresh_rate(&mode(148_352_000, 2200, 1125, 0)), 59940);
+        assert_eq!(mode_refresh_rate(&mode(74_250_000, 2200, 1125, XCB_RANDR_MODE_FLAG_INTERLACE)), 60000);
+        assert_eq!(mode_refresh_rate(&mode(25_175_000, 800, 525, XCB_RANDR_MODE_FLAG_DOUBLE_SCAN)), 29970);
+        assert_eq!(mode_refresh_rate(&mode(0, 0, 0, 0)), 0);
+    }
+
+    // A window with nothing set up, for helpers which only look at its state
+    fn window_details() -> WindowDetails {
+        WindowDetails {
+            handle: 0,
+            style: Arc::new(Mutex::new(Style::new())),
+            event_buffer: Vec::new(),
+            parent: 0,
+            position: (0, 0),
+            size: (800, 608),
+            state_maximised: (false, false),
+            state_net_hidden: false,
+            state_icccm_iconic: false,
+            state_fullscreen: false,
+            size_limits: Mutex::new(SizeLimits::default()),
+            win_gravity: AtomicU8::new(Gravity::NorthWest as u8),
+            #[cfg(feature = "input")]
+            modifiers: Modifiers::new(),
+            #[cfg(feature = "input")]
+            raw_modifiers: RawModifiers::default(),
+            #[cfg(feature = "input")]
+            mouse_buttons: MouseButtons::new(),
+            #[cfg(feature = "input")]
+            key_repeat: AtomicBool::new(true),
+            #[cfg(feature = "input")]
+            text_input: AtomicBool::new(true),
+            #[cfg(feature = "input")]
+            compose: ComposeState(std::ptr::null_mut()),
+            #[cfg(feature = "input")]
+            resync_input: AtomicBool::new(false),
+            #[cfg(feature = "input")]
+            keys_held: [0; 32],
+            #[cfg(feature = "input")]
+            pending_release: None,
+            #[cfg(feature = "input")]
+            cursor_inside: false,
+            cursor_barriers: Mutex::new(None),
+            cursor_confined: AtomicBool::new(false),
+            pointer_grabbed: AtomicBool::new(false),
+            focused: false,
+            frame_pacing: Mutex::new(FramePacing::default()),
+            monitors: Vec::new(),
+            depth: 24,
+            visual: 0,
+            screenid: 0,
+            scale_factor: 1.0,
+            offscreen: false,
+            initial_geometry: false,
+            wakeup: EventFd::new().unwrap(),
+        }
+    }
+
+    #[test]
+    fn focus_allows_confinement() {
+        // XInput2 focus events, which are what's selected with the input feature, use the core protocol's values
+        const XI_NOTIFY_NORMAL: u8 = 0;
+        const XI_NOTIFY_NONLINEAR: u8 = 3;
+        let mut window = window_details();
+        window.cursor_confined.store(true, Ordering::Relaxed);
+        assert!(!wants_pointer_grab(&window));
+
+        set_focus(&mut window, true, XI_NOTIFY_NORMAL, XI_NOTIFY_NONLINEAR);
+        assert!(wants_pointer_grab(&window));
+        assert_eq!(window.event_buffer, [Event::Focus { focused: true, reason: FocusReason::Normal }]);
+
+        set_focus(&mut window, false, XI_NOTIFY_NORMAL, XI_NOTIFY_NONLINEAR);
+        assert!(!wants_pointer_grab(&window));
+    }
+
+    #[test]
+    fn minimised_from_either_state() {
+        let mut window = window_details();
+        set_icccm_iconic(&mut window, true);
+        assert_eq!(window.event_buffer, [Event::Minimise(true)]);
+
+        // A Window Manager which only sets WM_STATE can still change _NET_WM_STATE while the window's iconic
+        update_net_wm_state(&mut window, false, (false, false), true);
+        assert!(window.minimised());
+        assert_eq!(window.event_buffer, [Event::Minimise(true), Event::Fullscreen(true)]);
+
+        update_net_wm_state(&mut window, true, (false, false), true);
+        set_icccm_iconic(&mut window, false);
+        assert_eq!(window.event_buffer.len(), 2);
+        update_net_wm_state(&mut window, false, (false, false), true);
+        assert_eq!(window.event_buffer[2..], [Event::Minimise(false)]);
+    }
+
+    #[test]
+    fn frame_offsets() {
+        // A titlebar 24 pixels high and 2 pixel borders, as (left, right, top, bottom)
+        let extents = (2, 2, 24, 2);
+        assert_eq!(frame_offset(extents, Gravity::NorthWest), (2, 24));
+        assert_eq!(frame_offset(extents, Gravity::Center), (0, 11));
+        assert_eq!(frame_offset(extents, Gravity::SouthEast), (-2, -2));
+        assert_eq!(frame_offset(extents, Gravity::Static), (0, 0));
+        assert_eq!(frame_offset((0, 0, 0, 0), Gravity::North), (0, 0));
+    }
+
+    #[test]
+    fn withdrawn_net_wm_state_edits() {
+        let mut state = vec![10, 20];
+        edit_net_wm_state(&mut state, &[30, 0], true);
+        assert_eq!(state, [10, 20, 30]);
+        edit_net_wm_state(&mut state, &[10, 20], false);
+        assert_eq!(state, [30]);
+        edit_net_wm_state(&mut state, &[30, 0], true);
+        assert_eq!(state, [30]);
+    }
+
+    #[test]
+    fn wm_name_is_latin1() {
+        assert_eq!(latin1_lossy("Crème brûlée"), b"Cr\xe8me br\xfbl\xe9e");
+        assert_eq!(latin1_lossy("設定 - app"), b"?? - app");
+        assert_eq!(latin1_lossy(""), b"");
+    }
+
+    #[test]
+    fn opacity_cardinals() {
+        assert_eq!(opacity_cardinal(0.0), 0);
+        assert_eq!(opacity_cardinal(0.5), 0x80000000);
+        assert_eq!(opacity_cardinal(1.0), u32::MAX);
+    }
+
+    #[test]
+    fn net_wm_icon_is_argb() {
+        let rgba = [0x11, 0x22, 0x33, 0x44, 0xFF, 0x00, 0x00, 0x80];
+        assert_eq!(net_wm_icon(&rgba, 2, 1), vec![2, 1, 0x44112233, 0x80FF0000]);
+    }
+
+    #[test]
+    fn xft_dpi_from_resources() {
+        assert_eq!(xft_dpi("Xcursor.size:\t24\nXft.dpi:\t192\nXft.hinting:\t1\n"), Some(192.0));
+        assert_eq!(xft_dpi("Xft.dpi: 120.5"), Some(120.5));
+        assert_eq!(xft_dpi("Xft.dpi:\t0\n*.dpi:\t96\n"), None);
+        assert_eq!(xft_dpi(""), None);
+    }
+
+    #[cfg(feature = "input")]
+    #[test]
+    fn xf86_media_keys() {
+        assert_eq!(keysym_to_key(0x1008FF13, 0x1008FF13), Some(Key::MediaVolumeUp));
+        assert_eq!(keysym_to_key(0x1008FF14, 0x1008FF14), Some(Key::MediaPlayPause));
+        assert_eq!(keysym_to_key(0x1008FF26, 0x1008FF26), Some(Key::BrowserBack));
+        assert_eq!(keysym_to_key(0x1008FF01, 0x1008FF01), None);
+    }
+
+    #[cfg(feature = "input")]
+    #[test]
+    fn named_function_keys() {
+        assert_eq!(keysym_to_key(0xFF61, 0xFF61), Some(Key::PrintScreen));
+        assert_eq!(keysym_to_key(0xFF67, 0xFF67), Some(Key::Applications));
+        assert_eq!(keysym_to_key(0xFE03, 0xFE03), Some(Key::RightAlt));
+    }
+
+    #[cfg(feature = "input")]
+    #[test]
+    fn key_modifiers_from_mask() {
+        let ctrl_num = key_modifiers(XCB_MOD_MASK_CONTROL | XCB_MOD_MASK_2);
+        assert_eq!(ctrl_num, KeyModifiers { control: true, num_lock: true, ..KeyModifiers::default() });
+        assert_eq!(key_modifiers(0), KeyModifiers::default());
+    }
+
+    #[cfg(feature = "input")]
+    #[test]
+    fn fp1616_positions() {
+        assert_eq!(fp1616_position(12 << 16, (34 << 16) | 0x8000), (12.0, 34.5));
+        assert_eq!(fp1616_position(-(3 << 16) - 0x4000, 0), (-3.25, 0.0));
+        assert_eq!(core_position(-4, 20), (-4.0, 20.0));
+    }
+}
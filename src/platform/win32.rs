@@ -10,4 +10,4 @@ pub use self::{
 };
 
 // internals
-pub(crate) use imp::{Connection, Window};
+pub(crate) use imp::{lock_connection, Batch, Connection, Window};
@@ -61,13 +61,17 @@ pub(crate) type ATOM = WORD;
 pub(crate) type BOOL = c_int;
 pub(crate) type BYTE = c_uchar;
 pub(crate) type CHAR = c_char;
+pub(crate) type COLORREF = DWORD;
 pub(crate) enum DPI_AWARENESS_CONTEXT__ {}
 pub(crate) type DPI_AWARENESS_CONTEXT = *mut DPI_AWARENESS_CONTEXT__;
 pub(crate) type DWORD = c_ulong;
 pub(crate) type HANDLE = *mut c_void;
 pub(crate) type HCURSOR = HICON;
+pub(crate) type HGLOBAL = HANDLE;
+pub(crate) type HIMC = HANDLE;
 pub(crate) type HLOCAL = HANDLE;
 pub(crate) type HOOKPROC = unsafe extern "system" fn(c_int, WPARAM, LPARAM) -> LRESULT;
+pub(crate) type MONITORENUMPROC = unsafe extern "system" fn(HMONITOR, HDC, *mut RECT, LPARAM) -> BOOL;
 pub(crate) type HRESULT = c_long;
 pub(crate) type INT = c_int;
 pub(crate) type LANGID = USHORT;
@@ -92,6 +96,7 @@ pub type WNDPROC = unsafe extern "system" fn(HWND, UINT, WPARAM, LPARAM) -> LRES
 pub(crate) const _WIN32_WINNT_VISTA: WORD = 0x0600;
 pub(crate) const _WIN32_WINNT_WINBLUE: WORD = 0x0603;
 pub(crate) const CCHILDREN_TITLEBAR: usize = 5;
+pub(crate) const CF_UNICODETEXT: UINT = 13;
 pub(crate) const CP_UTF8: DWORD = 65001;
 pub(crate) const CS_OWNDC: UINT = 0x0020;
 pub(crate) const CW_USEDEFAULT: c_int = 0x80000000;
@@ -99,11 +104,13 @@ pub(crate) const DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2: DPI_AWARENESS_CONTE
 pub(crate) const ERROR_SUCCESS: DWORD = 0; // lol
 pub(crate) const FALSE: BOOL = 0;
 pub(crate) const GCL_CBCLSEXTRA: c_int = -20;
+pub(crate) const GMEM_MOVEABLE: UINT = 0x0002;
 pub(crate) const GWL_EXSTYLE: c_int = -20;
 pub(crate) const GWL_STYLE: c_int = -16;
 pub(crate) const GWL_USERDATA: c_int = -21;
 pub(crate) const HCBT_DESTROYWND: c_int = 4;
 pub(crate) const HTCAPTION: LRESULT = 2;
+pub(crate) const IACE_DEFAULT: DWORD = 0x0010;
 pub(crate) const IDC_APPSTARTING: *const WCHAR = 32650 as _;
 pub(crate) const IDC_ARROW: *const WCHAR = 32512 as _;
 pub(crate) const IDC_CROSS: *const WCHAR = 32515 as _;
@@ -135,6 +142,8 @@ pub(crate) const PROCESS_SYSTEM_DPI_AWARE: PROCESS_DPI_AWARENESS = 1;
 pub(crate) const SC_CLOSE: WPARAM = 0xF060;
 pub(crate) const SC_MAXIMIZE: WPARAM = 0xF030;
 pub(crate) const SC_RESTORE: WPARAM = 0xF120;
+pub(crate) const TPM_RIGHTBUTTON: UINT = 0x0002;
+pub(crate) const TPM_RETURNCMD: UINT = 0x0100;
 pub(crate) const SIZE_RESTORED: WPARAM = 0;
 pub(crate) const SIZE_MINIMIZED: WPARAM = 1;
 pub(crate) const SIZE_MAXIMIZED: WPARAM = 2;
@@ -142,6 +151,11 @@ pub(crate) const SIZE_MAXSHOW: WPARAM = 3;
 pub(crate) const SIZE_MAXHIDE: WPARAM = 4;
 pub(crate) const SW_HIDE: c_int = 0;
 pub(crate) const SW_SHOW: c_int = 5;
+pub(crate) const HWND_TOPMOST: HWND = -1isize as HWND;
+pub(crate) const HWND_NOTOPMOST: HWND = -2isize as HWND;
+pub(crate) const LWA_ALPHA: DWORD = 0x00000002;
+pub(crate) const SW_MINIMIZE: c_int = 6;
+pub(crate) const SW_RESTORE: c_int = 9;
 pub(crate) const SWP_ASYNCWINDOWPOS: UINT = 0x4000;
 pub(crate) const SWP_DEFERERASE: UINT = 0x2000;
 pub(crate) const SWP_DRAWFRAME: UINT = SWP_FRAMECHANGED;
@@ -151,7 +165,11 @@ pub(crate) const SWP_NOACTIVATE: UINT = 0x0010;
 pub(crate) const SWP_NOCOPYBITS: UINT = 0x0100;
 pub(crate) const SWP_NOMOVE: UINT = 0x0002;
 pub(crate) const SWP_NOOWNERZORDER: UINT = 0x0200;
+pub(crate) const MONITOR_DEFAULTTOPRIMARY: DWORD = 0x00000001;
 pub(crate) const MONITOR_DEFAULTTONEAREST: DWORD = 0x00000002;
+pub(crate) const MONITORINFOF_PRIMARY: DWORD = 0x00000001;
+pub(crate) const CCHDEVICENAME: usize = 32;
+pub(crate) const ENUM_CURRENT_SETTINGS: DWORD = 0xFFFFFFFF;
 pub(crate) const SWP_NOREDRAW: UINT = 0x0008;
 pub(crate) const SWP_NOREPOSITION: UINT = SWP_NOOWNERZORDER;
 pub(crate) const SWP_NOSENDCHANGING: UINT = 0x0400;
@@ -167,6 +185,7 @@ pub(crate) const VER_SERVICEPACKMAJOR: DWORD = 0x0000020;
 pub(crate) const VER_SERVICEPACKMINOR: DWORD = 0x0000010;
 // WINAPI defines these as `int` but that's annoying and stupid for several reasons.
 // We redefine them as u8's.
+pub(crate) const SM_SWAPBUTTON: c_int = 23;
 pub(crate) const VK_LBUTTON: u8 = 0x01;
 pub(crate) const VK_RBUTTON: u8 = 0x02;
 pub(crate) const VK_CANCEL: u8 = 0x03;
@@ -357,6 +376,8 @@ pub(crate) const WM_ERASEBKGND: UINT = 0x0014;
 pub(crate) const WM_SHOWWINDOW: UINT = 0x0018;
 pub(crate) const WM_ACTIVATEAPP: UINT = 0x001C;
 pub(crate) const WM_SETCURSOR: UINT = 0x0020;
+pub(crate) const WM_GETMINMAXINFO: UINT = 0x0024;
+pub(crate) const WM_DISPLAYCHANGE: UINT = 0x007E;
 pub(crate) const WM_NCCREATE: UINT = 0x0081;
 pub(crate) const WM_NCDESTROY: UINT = 0x0082;
 pub(crate) const WM_NCLBUTTONDOWN: UINT = 0x00A1;
@@ -376,6 +397,7 @@ pub(crate) const WM_MBUTTONUP: UINT = 0x0208;
 pub(crate) const WM_MOUSEWHEEL: UINT = 0x020A;
 pub(crate) const WM_XBUTTONDOWN: UINT = 0x020B;
 pub(crate) const WM_XBUTTONUP: UINT = 0x020C;
+pub(crate) const WM_MOUSEHWHEEL: UINT = 0x020E;
 pub(crate) const WM_MOVING: UINT = 0x0216;
 pub(crate) const WM_EXITSIZEMOVE: UINT = 0x0232;
 pub(crate) const WM_USER: UINT = 0x0400;
@@ -386,6 +408,7 @@ pub(crate) const WS_CLIPCHILDREN: DWORD = 0x02000000;
 pub(crate) const WS_CLIPSIBLINGS: DWORD = 0x04000000;
 pub(crate) const WS_DISABLED: DWORD = 0x08000000;
 pub(crate) const WS_DLGFRAME: DWORD = 0x00400000;
+pub(crate) const WS_EX_LAYERED: DWORD = 0x00080000;
 pub(crate) const WS_EX_LAYOUTRTL: DWORD = 0x00400000;
 pub(crate) const WS_EX_TOOLWINDOW: DWORD = 0x00000080;
 pub(crate) const WS_GROUP: DWORD = 0x00020000;
@@ -482,6 +505,62 @@ pub(crate) struct POINT {
     pub(crate) y: LONG,
 }
 #[repr(C)]
+pub(crate) struct MONITORINFO {
+    pub(crate) cbSize: DWORD,
+    pub(crate) rcMonitor: RECT,
+    pub(crate) rcWork: RECT,
+    pub(crate) dwFlags: DWORD,
+}
+#[repr(C)]
+pub(crate) struct MONITORINFOEXW {
+    pub(crate) cbSize: DWORD,
+    pub(crate) rcMonitor: RECT,
+    pub(crate) rcWork: RECT,
+    pub(crate) dwFlags: DWORD,
+    pub(crate) szDevice: [WCHAR; CCHDEVICENAME],
+}
+// Only the display half of the unions is declared, since there's no printing here
+#[repr(C)]
+pub(crate) struct DEVMODEW {
+    pub(crate) dmDeviceName: [WCHAR; 32],
+    pub(crate) dmSpecVersion: WORD,
+    pub(crate) dmDriverVersion: WORD,
+    pub(crate) dmSize: WORD,
+    pub(crate) dmDriverExtra: WORD,
+    pub(crate) dmFields: DWORD,
+    pub(crate) dmPosition: POINT,
+    pub(crate) dmDisplayOrientation: DWORD,
+    pub(crate) dmDisplayFixedOutput: DWORD,
+    pub(crate) dmColor: SHORT,
+    pub(crate) dmDuplex: SHORT,
+    pub(crate) dmYResolution: SHORT,
+    pub(crate) dmTTOption: SHORT,
+    pub(crate) dmCollate: SHORT,
+    pub(crate) dmFormName: [WCHAR; 32],
+    pub(crate) dmLogPixels: WORD,
+    pub(crate) dmBitsPerPel: DWORD,
+    pub(crate) dmPelsWidth: DWORD,
+    pub(crate) dmPelsHeight: DWORD,
+    pub(crate) dmDisplayFlags: DWORD,
+    pub(crate) dmDisplayFrequency: DWORD,
+    pub(crate) dmICMMethod: DWORD,
+    pub(crate) dmICMIntent: DWORD,
+    pub(crate) dmMediaType: DWORD,
+    pub(crate) dmDitherType: DWORD,
+    pub(crate) dmReserved1: DWORD,
+    pub(crate) dmReserved2: DWORD,
+    pub(crate) dmPanningWidth: DWORD,
+    pub(crate) dmPanningHeight: DWORD,
+}
+#[repr(C)]
+pub(crate) struct MINMAXINFO {
+    pub(crate) ptReserved: POINT,
+    pub(crate) ptMaxSize: POINT,
+    pub(crate) ptMaxPosition: POINT,
+    pub(crate) ptMinTrackSize: POINT,
+    pub(crate) ptMaxTrackSize: POINT,
+}
+#[repr(C)]
 pub(crate) struct RECT {
     pub(crate) left: LONG,
     pub(crate) top: LONG,
@@ -495,6 +574,15 @@ pub(crate) struct TITLEBARINFO {
     pub(crate) rgstate: [DWORD; CCHILDREN_TITLEBAR + 1],
 }
 #[repr(C)]
+pub(crate) struct WINDOWPLACEMENT {
+    pub(crate) length: UINT,
+    pub(crate) flags: UINT,
+    pub(crate) showCmd: UINT,
+    pub(crate) ptMinPosition: POINT,
+    pub(crate) ptMaxPosition: POINT,
+    pub(crate) rcNormalPosition: RECT,
+}
+#[repr(C)]
 pub(crate) struct WNDCLASSEXW {
     pub(crate) cbSize: UINT,
     pub(crate) style: UINT,
@@ -551,6 +639,13 @@ extern "system" {
     pub(crate) fn SetEvent(hEvent: HANDLE) -> BOOL;
     pub(crate) fn WaitForSingleObject(hHandle: HANDLE, dwMilliseconds: DWORD) -> DWORD;
 
+    // Global memory, which is still what the clipboard uses
+    pub(crate) fn GlobalAlloc(uFlags: UINT, dwBytes: usize) -> HGLOBAL;
+    pub(crate) fn GlobalFree(hMem: HGLOBAL) -> HGLOBAL;
+    pub(crate) fn GlobalLock(hMem: HGLOBAL) -> *mut c_void;
+    pub(crate) fn GlobalSize(hMem: HGLOBAL) -> usize;
+    pub(crate) fn GlobalUnlock(hMem: HGLOBAL) -> BOOL;
+
     // Dynamic linking
     pub(crate) fn GetProcAddress(hModule: HMODULE, lpProcName: *const CHAR) -> FARPROC;
     pub(crate) fn LoadLibraryExW(lpLibFileName: *const WCHAR, hFile: HANDLE, dwFlags: DWORD) -> HMODULE;
@@ -564,11 +659,23 @@ extern "system" {
 }
 #[link(name = "user32")]
 extern "system" {
+    // Clipboard
+    pub(crate) fn OpenClipboard(hWndNewOwner: HWND) -> BOOL;
+    pub(crate) fn CloseClipboard() -> BOOL;
+    pub(crate) fn EmptyClipboard() -> BOOL;
+    pub(crate) fn GetClipboardData(uFormat: UINT) -> HANDLE;
+    pub(crate) fn SetClipboardData(uFormat: UINT, hMem: HANDLE) -> HANDLE;
+    pub(crate) fn RegisterClipboardFormatW(lpszFormat: *const WCHAR) -> UINT;
+
     // Window class management
     pub(crate) fn GetClassInfoExW(hinst: HINSTANCE, lpszClass: *const WCHAR, lpwcx: *mut WNDCLASSEXW) -> BOOL;
     pub(crate) fn RegisterClassExW(lpWndClass: *const WNDCLASSEXW) -> ATOM;
     pub(crate) fn SetWindowTextW(hWnd: HWND, lpString: *const WCHAR) -> BOOL;
     pub(crate) fn MonitorFromWindow(hwnd: HWND, dwFlags: DWORD) -> HMONITOR;
+    pub(crate) fn MonitorFromPoint(pt: POINT, dwFlags: DWORD) -> HMONITOR;
+    pub(crate) fn EnumDisplayMonitors(hdc: HDC, lprcClip: *const RECT, lpfnEnum: MONITORENUMPROC, dwData: LPARAM) -> BOOL;
+    pub(crate) fn GetMonitorInfoW(hMonitor: HMONITOR, lpmi: *mut MONITORINFO) -> BOOL;
+    pub(crate) fn EnumDisplaySettingsW(lpszDeviceName: *const WCHAR, iModeNum: DWORD, lpDevMode: *mut DEVMODEW) -> BOOL;
     // Window management
     pub(crate) fn CreateWindowExW(
         dwExStyle: DWORD,
@@ -585,9 +692,15 @@ extern "system" {
         lpParam: *mut c_void,
     ) -> HWND;
     pub(crate) fn AdjustWindowRectEx(lpRect: *mut RECT, dwStyle: DWORD, bMenu: BOOL, dwExStyle: DWORD) -> BOOL;
-    pub(crate) fn ClientToScreen(hWnd: HWND, lpPoint: *mut POINT) -> BOOL;
     pub(crate) fn GetClientRect(hWnd: HWND, lpRect: *mut RECT) -> BOOL;
     pub(crate) fn GetWindowRect(hWnd: HWND, lpRect: *mut RECT) -> BOOL;
+    pub(crate) fn GetWindowPlacement(hWnd: HWND, lpwndpl: *mut WINDOWPLACEMENT) -> BOOL;
+    pub(crate) fn SetWindowPlacement(hWnd: HWND, lpwndpl: *const WINDOWPLACEMENT) -> BOOL;
+    pub(crate) fn IsIconic(hWnd: HWND) -> BOOL;
+    pub(crate) fn IsWindow(hWnd: HWND) -> BOOL;
+    pub(crate) fn FlashWindow(hWnd: HWND, bInvert: BOOL) -> BOOL;
+    pub(crate) fn SetForegroundWindow(hWnd: HWND) -> BOOL;
+    pub(crate) fn SetLayeredWindowAttributes(hwnd: HWND, crKey: COLORREF, bAlpha: BYTE, dwFlags: DWORD) -> BOOL;
     pub(crate) fn GetTitleBarInfo(hwnd: HWND, pti: *mut TITLEBARINFO) -> BOOL;
     pub(crate) fn SetWindowPos(
         hWnd: HWND,
@@ -599,6 +712,8 @@ extern "system" {
         uFlags: UINT,
     ) -> BOOL;
     pub(crate) fn WindowFromPoint(Point: POINT) -> HWND;
+    pub(crate) fn ScreenToClient(hWnd: HWND, lpPoint: *mut POINT) -> BOOL;
+    pub(crate) fn ClientToScreen(hWnd: HWND, lpPoint: *mut POINT) -> BOOL;
     pub(crate) fn DestroyWindow(hWnd: HWND) -> BOOL;
 
     pub(crate) fn TrackMouseEvent(x: *mut TRACKMOUSEEVENT) -> BOOL;
@@ -635,8 +750,10 @@ extern "system" {
     pub(crate) fn GetCursorPos(lpPoint: *mut POINT) -> BOOL;
     pub(crate) fn SetCapture(hWnd: HWND) -> HWND;
     pub(crate) fn GetCapture() -> HWND;
+    pub(crate) fn GetFocus() -> HWND;
     pub(crate) fn ReleaseCapture() -> BOOL;
     pub(crate) fn GetAsyncKeyState(vKey: c_int) -> SHORT;
+    pub(crate) fn GetKeyState(nVirtKey: c_int) -> SHORT;
     pub(crate) fn GetSystemMetrics(nIndex: c_int) -> c_int;
     pub(crate) fn SetCursor(hCursor: HCURSOR) -> HCURSOR;
     pub(crate) fn MapVirtualKeyW(hCode: UINT, uMapType: UINT) -> UINT;
@@ -644,6 +761,15 @@ extern "system" {
     // Misc legacy garbage
     pub(crate) fn EnableMenuItem(hMenu: HMENU, uIDEnableItem: UINT, uEnable: UINT) -> BOOL;
     pub(crate) fn GetSystemMenu(hWnd: HWND, bRevert: BOOL) -> HMENU;
+    pub(crate) fn TrackPopupMenu(
+        hMenu: HMENU,
+        uFlags: UINT,
+        x: c_int,
+        y: c_int,
+        nReserved: c_int,
+        hWnd: HWND,
+        prcRect: *const RECT,
+    ) -> BOOL;
 
     // Yeah, whatever
     pub(crate) fn LoadImageW(
@@ -742,6 +868,25 @@ pub(crate) struct OSVERSIONINFOEXW {
     pub(crate) wReserved: BYTE,
 }
 
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(crate) struct UNSIGNED_RATIO {
+    pub(crate) uiNumerator: UINT,
+    pub(crate) uiDenominator: UINT,
+}
+
+// dwmapi.h declares this with 1-byte packing. Only the leading fields are used, the rest are kept as raw bytes
+// so that `cbSize` still matches the full 292-byte struct.
+#[repr(C, packed)]
+pub(crate) struct DWM_TIMING_INFO {
+    pub(crate) cbSize: UINT,
+    pub(crate) rateRefresh: UNSIGNED_RATIO,
+    pub(crate) qpcRefreshPeriod: u64,
+    pub(crate) rateCompose: UNSIGNED_RATIO,
+    pub(crate) qpcVBlank: u64,
+    pub(crate) _rest: [BYTE; 256],
+}
+
 
 /// Generates simple dynamic linkings.
 macro_rules! dyn_link {
@@ -836,6 +981,20 @@ dyn_link! {
                 pvAttribute: *const c_void,
                 cbAttribute: DWORD,
             ) -> HRESULT;
+
+            /// (Windows Vista+)
+            /// Gets the compositor's refresh timings. From Windows 8.1 onwards `hwnd` must be null.
+            fn DwmGetCompositionTimingInfo(hwnd: HWND, pTimingInfo: *mut DWM_TIMING_INFO) -> HRESULT;
+
+            /// (Windows Vista+)
+            /// Checks whether desktop composition is enabled. From Windows 8 onwards it always is.
+            fn DwmIsCompositionEnabled(pfEnabled: *mut BOOL) -> HRESULT;
+        },
+
+        "Imm32.dll" {
+            /// (Win2000+)
+            /// Associates an input method context with a window, or restores the default one with `IACE_DEFAULT`.
+            fn ImmAssociateContextEx(hWnd: HWND, hIMC: HIMC, dwFlags: DWORD) -> BOOL;
         },
 
         "Ntdll.dll" {
@@ -7,15 +7,16 @@ use super::ffi::*;
 use crate::{
     connection,
     error::Error,
-    event::Event,
+    event::{Event, FocusReason},
+    monitor::{self, Monitor, MonitorId},
     util::{sync::{self, Condvar, Mutex}, LazyCell},
-    window::{self, Cursor, Style},
+    window::{self, clamp_size, size_limits_valid, CaptionAction, Cursor, Gravity, Rectangle, Style, WindowId},
 };
 
 #[cfg(feature = "input")]
-use crate::input::{Key, MouseButton};
+use crate::input::{Key, KeyModifiers, Modifiers, MouseButton, MouseButtons, RawModifiers};
 
-use std::{cell::UnsafeCell, mem, ptr};
+use std::{cell::UnsafeCell, mem, ptr, sync::atomic::{AtomicBool, Ordering}, time::{Duration, Instant}};
 
 /// TODO: yeah
 /// 
@@ -23,11 +24,21 @@ use std::{cell::UnsafeCell, mem, ptr};
 // Global immutable struct containing dynamically acquired API state
 static WIN32: LazyCell<Win32State> = LazyCell::new(Win32State::new);
 
+/// The DPI mode that's enabled process-wide, set the first time it's needed (see `set_dpi_awareness`).
+static DPI_MODE: LazyCell<Win32DpiMode> = LazyCell::new(set_dpi_awareness);
+
+/// Whether the process should be declared as DPI-aware when `DPI_MODE` is instanced.
+/// Cleared by `Builder::dpi_aware(false)` or `Connection::set_dpi_aware(false)`, only meaningful before then.
+static DPI_AWARENESS_REQUESTED: AtomicBool = AtomicBool::new(true);
+
 const BASE_DPI: UINT = 96;
 /// Custom window message
 const RAMEN_WM_CREATE: UINT = WM_USER + 0;
 const RAMEN_WM_DROP: UINT = WM_USER + 1;
 const RAMEN_WM_SETCURSOR: UINT = WM_USER + 2;
+const RAMEN_WM_SETTEXTINPUT: UINT = WM_USER + 3;
+const RAMEN_WM_SETCONFINED: UINT = WM_USER + 4;
+const RAMEN_WM_SHOWMENU: UINT = WM_USER + 5;
 
 /// Checks the current Windows version (see usage in `Win32State`)
 unsafe fn is_windows_ver_or_greater(dl: &Win32DL, major: WORD, minor: WORD, sp_major: WORD) -> bool {
@@ -65,13 +76,11 @@ struct Win32State {
     /// Whether the system is at least on Windows 10 1607 (build 14393 - "Anniversary Update").
     at_least_anniversary_update: bool,
 
-    /// The DPI mode that's enabled process-wide. The newest available is selected.
-    /// MSDN recommends setting this with the manifest but that's rather unpleasant.
-    /// Instead, it's set dynamically at runtime when this struct is instanced.
-    dpi_mode: Win32DpiMode,
-
     /// Dynamically linked Win32 functions that might not be available on all systems.
     dl: Win32DL,
+
+    /// Ticks per second of `QueryPerformanceCounter`, which DWM reports its timings in.
+    qpc_frequency: u64,
 }
 
 #[derive(PartialEq)]
@@ -84,41 +93,55 @@ enum Win32DpiMode {
 
 impl Win32State {
     fn new() -> Self {
-        const VISTA_MAJ: WORD = (_WIN32_WINNT_VISTA >> 8) & 0xFF;
-        const VISTA_MIN: WORD = _WIN32_WINNT_VISTA & 0xFF;
-        const W81_MAJ: WORD = (_WIN32_WINNT_WINBLUE >> 8) & 0xFF;
-        const W81_MIN: WORD = _WIN32_WINNT_WINBLUE & 0xFF;
-
         unsafe {
             let dl = Win32DL::link();
 
-            let at_least_vista = is_windows_ver_or_greater(&dl, VISTA_MAJ, VISTA_MIN, 0);
-            let at_least_8_point_1 = is_windows_ver_or_greater(&dl, W81_MAJ, W81_MIN, 0);
             let at_least_anniversary_update = is_win10_ver_or_greater(&dl, 14393);
-            let at_least_creators_update = is_win10_ver_or_greater(&dl, 15063);
-
-            let dpi_mode = if at_least_creators_update {
-                let _ = dl.SetProcessDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2);
-                Win32DpiMode::PerMonitorV2
-            } else if at_least_8_point_1 {
-                let _ = dl.SetProcessDpiAwareness(PROCESS_PER_MONITOR_DPI_AWARE);
-                Win32DpiMode::PerMonitorV1
-            } else if at_least_vista {
-                let _ = dl.SetProcessDPIAware();
-                Win32DpiMode::System
-            } else {
-                Win32DpiMode::Unsupported
-            };
+
+            let mut qpc_frequency = 0;
+            let _ = QueryPerformanceFrequency(&mut qpc_frequency);
 
             Self {
                 at_least_anniversary_update,
-                dpi_mode,
                 dl,
+                qpc_frequency,
             }
         }
     }
 }
 
+/// Declares the process as DPI-aware, unless that was opted out of. The newest available mode is selected.
+///
+/// MSDN recommends setting this with the manifest but that's rather unpleasant.
+/// Instead, it's set dynamically at runtime when `DPI_MODE` is first touched, which is kept apart from `WIN32`
+/// so that anything else which links functions, like `Connection::is_compositor_running`, doesn't decide it early.
+fn set_dpi_awareness() -> Win32DpiMode {
+    const VISTA_MAJ: WORD = (_WIN32_WINNT_VISTA >> 8) & 0xFF;
+    const VISTA_MIN: WORD = _WIN32_WINNT_VISTA & 0xFF;
+    const W81_MAJ: WORD = (_WIN32_WINNT_WINBLUE >> 8) & 0xFF;
+    const W81_MIN: WORD = _WIN32_WINNT_WINBLUE & 0xFF;
+
+    if !DPI_AWARENESS_REQUESTED.load(Ordering::Acquire) {
+        // The user opted out, so Windows will bitmap-scale us and we don't do any scaling ourselves
+        return Win32DpiMode::Unsupported
+    }
+    unsafe {
+        let dl = &WIN32.dl;
+        if is_win10_ver_or_greater(dl, 15063) {
+            let _ = dl.SetProcessDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2);
+            Win32DpiMode::PerMonitorV2
+        } else if is_windows_ver_or_greater(dl, W81_MAJ, W81_MIN, 0) {
+            let _ = dl.SetProcessDpiAwareness(PROCESS_PER_MONITOR_DPI_AWARE);
+            Win32DpiMode::PerMonitorV1
+        } else if is_windows_ver_or_greater(dl, VISTA_MAJ, VISTA_MIN, 0) {
+            let _ = dl.SetProcessDPIAware();
+            Win32DpiMode::System
+        } else {
+            Win32DpiMode::Unsupported
+        }
+    }
+}
+
 /// Win32 functions need the full outer size for creation. This function calculates that size from an inner size.
 ///
 /// Since for legacy reasons things like drop shadow are part of the bounds, don't use this for reporting outer size.
@@ -130,7 +153,7 @@ unsafe fn adjust_window_for_dpi(
     dpi: UINT,
 ) -> ((LONG, LONG), RECT) {
     let mut window = RECT { left: 0, top: 0, right: width as LONG, bottom: height as LONG };
-    if match win32.dpi_mode {
+    if match *DPI_MODE {
         // Non-client area DPI scaling is enabled in PMv1 Win10 1607+ and PMv2 (any).
         // For PMv1, this is done with EnableNonClientDpiScaling at WM_NCCREATE.
         Win32DpiMode::PerMonitorV1 if win32.at_least_anniversary_update => true,
@@ -153,11 +176,17 @@ fn rect_to_size2d(rect: &RECT) -> (LONG, LONG) {
 pub(crate) struct Connection {
     id: DWORD,
     handle: HANDLE,
+    windows: Vec<usize>, // the `HWND`s of the windows built from this connection which are still open
 }
 
 unsafe impl Send for Connection {}
 unsafe impl Sync for Connection {}
 
+/// Locks the connection. There's nothing to do on letting go of it here, unlike on X11.
+pub(crate) fn lock_connection(connection: &Mutex<Connection>) -> sync::MutexGuard<'_, Connection> {
+    sync::mutex_lock(connection)
+}
+
 impl Connection {
     pub(crate) fn new() -> Result<Self, Error> {
         unsafe {
@@ -172,9 +201,174 @@ impl Connection {
             }
             assert!(WaitForSingleObject(event, INFINITE) == 0);
             let _ = CloseHandle(event);
-            Ok(Self { id, handle })
+            Ok(Self { id, handle, windows: Vec::new() })
+        }
+    }
+
+    pub(crate) fn poll_events(&mut self) {
+        // Each window's thread already stores its own events as they arrive
+    }
+
+    pub(crate) fn monitors(&self) -> Vec<Monitor> {
+        unsafe { enum_monitors() }
+    }
+
+    pub(crate) fn monitor_name(&self, monitor: MonitorId) -> Option<String> {
+        unsafe {
+            let mut info: MONITORINFOEXW = mem::zeroed();
+            info.cbSize = mem::size_of_val(&info) as DWORD;
+            if GetMonitorInfoW(monitor.0 as usize as HMONITOR, (&mut info as *mut MONITORINFOEXW).cast()) == 0 {
+                return None
+            }
+            let len = info.szDevice.iter().position(|&c| c == 0).unwrap_or(info.szDevice.len());
+            Some(String::from_utf16_lossy(&info.szDevice[..len]))
+        }
+    }
+
+    pub(crate) fn clipboard_data(&mut self, mime: &str) -> Option<Vec<u8>> {
+        unsafe {
+            let format = clipboard_format(mime)?;
+            if OpenClipboard(ptr::null_mut()) == FALSE {
+                return None
+            }
+            let handle = GetClipboardData(format);
+            let memory = if handle.is_null() { ptr::null_mut() } else { GlobalLock(handle) };
+            let data = if !memory.is_null() {
+                let size = GlobalSize(handle);
+                let data = if format == CF_UNICODETEXT {
+                    // This is null-terminated, and the allocation may be bigger than the text
+                    let wide = std::slice::from_raw_parts(memory as *const WCHAR, size / 2);
+                    let len = wide.iter().position(|&c| c == 0).unwrap_or(wide.len());
+                    String::from_utf16_lossy(&wide[..len]).into_bytes()
+                } else {
+                    std::slice::from_raw_parts(memory as *const u8, size).to_vec()
+                };
+                let _ = GlobalUnlock(handle);
+                Some(data)
+            } else {
+                None
+            };
+            let _ = CloseClipboard();
+            data
+        }
+    }
+
+    pub(crate) fn set_clipboard_data(&mut self, entries: &[(String, Vec<u8>)]) {
+        unsafe {
+            if OpenClipboard(ptr::null_mut()) == FALSE {
+                return
+            }
+            let _ = EmptyClipboard();
+            for (mime, data) in entries {
+                let format = match clipboard_format(mime) {
+                    Some(format) => format,
+                    None => continue,
+                };
+                let bytes = if format == CF_UNICODETEXT {
+                    String::from_utf8_lossy(data).encode_utf16().chain(Some(0)).flat_map(u16::to_ne_bytes).collect()
+                } else {
+                    data.clone()
+                };
+                let handle = GlobalAlloc(GMEM_MOVEABLE, bytes.len());
+                if handle.is_null() {
+                    continue
+                }
+                let memory = GlobalLock(handle);
+                if !memory.is_null() {
+                    ptr::copy_nonoverlapping(bytes.as_ptr(), memory.cast(), bytes.len());
+                    let _ = GlobalUnlock(handle);
+                }
+                // The system owns the memory once it's been set, otherwise it's still ours to free
+                if memory.is_null() || SetClipboardData(format, handle).is_null() {
+                    let _ = GlobalFree(handle);
+                }
+            }
+            let _ = CloseClipboard();
+        }
+    }
+
+    pub(crate) fn set_dpi_aware(&mut self, dpi_aware: bool) {
+        DPI_AWARENESS_REQUESTED.store(dpi_aware, Ordering::Release);
+    }
+
+    pub(crate) fn is_compositor_running(&self) -> bool {
+        let mut enabled: BOOL = FALSE;
+        unsafe { WIN32.dl.DwmIsCompositionEnabled(&mut enabled) == Some(0) && enabled != FALSE }
+    }
+
+    pub(crate) fn default_scale_factor(&self) -> f64 {
+        unsafe {
+            // This sets the process' DPI awareness if no window has been built yet, the same as building one would
+            let win32 = WIN32.get();
+            let dpi_mode = DPI_MODE.get();
+            if *dpi_mode == Win32DpiMode::PerMonitorV1 || *dpi_mode == Win32DpiMode::PerMonitorV2 {
+                let monitor = MonitorFromPoint(POINT { x: 0, y: 0 }, MONITOR_DEFAULTTOPRIMARY);
+                let mut dx: UINT = 0;
+                let mut dy: UINT = 0;
+                if win32.dl.GetDpiForMonitor(monitor, 0, &mut dx, &mut dy) == Some(0) && dx != 0 {
+                    return f64::from(dx) / f64::from(BASE_DPI)
+                }
+            }
+            1.0
         }
     }
+
+    pub(crate) fn server_vendor(&self) -> Option<String> {
+        None
+    }
+
+    pub(crate) fn windows(&self) -> Vec<WindowId> {
+        self.windows.iter().map(|&hwnd| WindowId(hwnd as u64)).collect()
+    }
+
+    pub(crate) fn protocol_version(&self) -> Option<(u16, u16)> {
+        None
+    }
+}
+
+/// Maps a MIME type to a clipboard format, registering one named after it if it isn't a standard type.
+unsafe fn clipboard_format(mime: &str) -> Option<UINT> {
+    let name = match mime.to_ascii_lowercase().as_str() {
+        "text/plain" | "text/plain;charset=utf-8" => return Some(CF_UNICODETEXT),
+        "image/png" => "PNG", // Not standard, but it's what every application uses
+        _ => mime,
+    };
+    let mut name_wstr = Vec::new();
+    let name = str_to_wstr(name, &mut name_wstr)?;
+    match RegisterClipboardFormatW(name) {
+        0 => None,
+        format => Some(format),
+    }
+}
+
+unsafe fn enum_monitors() -> Vec<Monitor> {
+    unsafe extern "system" fn callback(hmonitor: HMONITOR, _hdc: HDC, _clip: *mut RECT, data: LPARAM) -> BOOL {
+        let monitors = &mut *(data as *mut Vec<Monitor>);
+        let mut info: MONITORINFOEXW = mem::zeroed();
+        info.cbSize = mem::size_of_val(&info) as DWORD;
+        if GetMonitorInfoW(hmonitor, (&mut info as *mut MONITORINFOEXW).cast()) != 0 {
+            let RECT { left, top, right, bottom } = info.rcMonitor;
+            let mut mode: DEVMODEW = mem::zeroed();
+            mode.dmSize = mem::size_of_val(&mode) as WORD;
+            let refresh_rate = match EnumDisplaySettingsW(info.szDevice.as_ptr(), ENUM_CURRENT_SETTINGS, &mut mode) {
+                // 0 and 1 mean the hardware's default rate, whatever that is
+                0 => 0,
+                _ if mode.dmDisplayFrequency <= 1 => 0,
+                _ => mode.dmDisplayFrequency.saturating_mul(1000),
+            };
+            monitors.push(Monitor {
+                id: MonitorId(hmonitor as usize as u64),
+                rect: Rectangle { x: left as i16, y: top as i16, width: (right - left) as u16, height: (bottom - top) as u16 },
+                primary: info.dwFlags & MONITORINFOF_PRIMARY != 0,
+                refresh_rate,
+            });
+        }
+        TRUE
+    }
+
+    let mut monitors = Vec::new();
+    let _ = EnumDisplayMonitors(ptr::null_mut(), ptr::null(), callback, (&mut monitors) as *mut Vec<Monitor> as LPARAM);
+    monitors
 }
 
 impl Drop for Connection {
@@ -372,7 +566,7 @@ unsafe fn set_close_button(hwnd: HWND, enabled: bool) {
 }
 
 pub(crate) struct Window {
-    _connection: connection::Connection,
+    connection: connection::Connection,
     hwnd: HWND,
     state: Box<UnsafeCell<WindowState>>,
 }
@@ -390,11 +584,34 @@ struct WindowState {
     event_backbuf: Vec<Event>,
     event_frontbuf: Vec<Event>,
     event_sync: Mutex<()>,
+    event_cvar: Condvar, // signalled when an event is added to `event_backbuf`
     mouse_tracked: bool,
     cursor: HCURSOR,
+    cursor_confined: bool, // only touched by the window thread
     dpi: UINT,
     is_max: bool,
     is_min: bool,
+    fullscreen: Option<WINDOWPLACEMENT>, // where to put the window back to when it leaves fullscreen
+    min_size: Option<(u16, u16)>,
+    max_size: Option<(u16, u16)>,
+    #[cfg(feature = "input")]
+    modifiers: Modifiers,
+    #[cfg(feature = "input")]
+    mouse_buttons: MouseButtons,
+    #[cfg(feature = "input")]
+    raw_modifiers: RawModifiers, // as of the events in `event_frontbuf`
+    #[cfg(feature = "input")]
+    raw_modifiers_backbuf: RawModifiers, // as of the events in `event_backbuf`, guarded by `event_sync`
+    #[cfg(feature = "input")]
+    key_repeat: bool,
+    #[cfg(feature = "input")]
+    text_input: bool,
+    #[cfg(feature = "input")]
+    resync_input: bool,
+    #[cfg(feature = "input")]
+    cursor_inside: bool, // as of the events in `event_frontbuf`
+    frame_pacing: Option<u64>, // the last vblank reported by DWM, if enabled
+    monitors: Vec<Monitor>, // as of the last `WM_DISPLAYCHANGE`, for telling what's changed
     style: Style,
     wh: (u16, u16),
 }
@@ -419,6 +636,12 @@ fn cursor_to_int_resource(cursor: Cursor) -> *const WCHAR {
 }
 
 unsafe fn make_window(builder: window::Builder) -> Result<Window, Error> {
+    // This has to be decided before `DPI_MODE` is first touched, as DPI awareness is set process-wide then, which
+    // happens just below when the outer size is worked out
+    if !builder.dpi_aware {
+        DPI_AWARENESS_REQUESTED.store(false, Ordering::Release);
+    }
+
     let mut class_name_wstr = Vec::<WCHAR>::new();
     let class_name = str_to_wstr(&*builder.class_name, class_name_wstr.as_mut())
         .ok_or(Error::OutOfMemory)?;
@@ -430,12 +653,29 @@ unsafe fn make_window(builder: window::Builder) -> Result<Window, Error> {
     let (dw_style, dw_style_ex) = style_to_bits(&style);
     let dpi = BASE_DPI;
     let ((width, height), wrect) = adjust_window_for_dpi(WIN32.get(), builder.size, dw_style, dw_style_ex, dpi);
-    let (pos_x, pos_y) = builder.position.map(|(x, y)| (x as LONG + wrect.left, y as LONG + wrect.top)).unwrap_or((CW_USEDEFAULT, CW_USEDEFAULT));
+    let owner = builder.modal_for.map_or(ptr::null_mut(), |hwnd| hwnd as HWND);
+    let (pos_x, pos_y) = match builder.position {
+        Some((x, y)) => (x as LONG + wrect.left, y as LONG + wrect.top),
+        None => {
+            // Windows doesn't place owned windows over their owner by itself, so dialogs are centered here
+            let mut owner_rect: RECT = mem::zeroed();
+            if !owner.is_null() && GetWindowRect(owner, &mut owner_rect) != 0 {
+                (
+                    owner_rect.left + (owner_rect.right - owner_rect.left - width) / 2,
+                    owner_rect.top + (owner_rect.bottom - owner_rect.top - height) / 2,
+                )
+            } else {
+                (CW_USEDEFAULT, CW_USEDEFAULT)
+            }
+        },
+    };
     let window_state = Box::new(UnsafeCell::new(WindowState {
         event_backbuf: Vec::new(),
         event_frontbuf: Vec::new(),
         event_sync: Mutex::new(()),
+        event_cvar: Condvar::new(),
         mouse_tracked: false,
+        cursor_confined: false,
         cursor: {
             let rsrc = cursor_to_int_resource(builder.cursor);
             if !rsrc.is_null() {
@@ -447,6 +687,27 @@ unsafe fn make_window(builder: window::Builder) -> Result<Window, Error> {
         dpi,
         is_max: false,
         is_min: false,
+        fullscreen: None,
+        min_size: builder.min_size,
+        max_size: builder.max_size,
+        #[cfg(feature = "input")]
+        modifiers: Modifiers::new(),
+        #[cfg(feature = "input")]
+        mouse_buttons: MouseButtons::new(),
+        #[cfg(feature = "input")]
+        raw_modifiers: RawModifiers::default(),
+        #[cfg(feature = "input")]
+        raw_modifiers_backbuf: RawModifiers::default(),
+        #[cfg(feature = "input")]
+        key_repeat: true,
+        #[cfg(feature = "input")]
+        text_input: true,
+        #[cfg(feature = "input")]
+        resync_input: false,
+        #[cfg(feature = "input")]
+        cursor_inside: false,
+        frame_pacing: None,
+        monitors: enum_monitors(),
         style: builder.style,
         wh: builder.size,
     }));
@@ -463,12 +724,13 @@ unsafe fn make_window(builder: window::Builder) -> Result<Window, Error> {
             lpCreateParams: (&create_params) as *const WindowCreateParams as *mut c_void,
             hInstance: base_hinstance(),
             hMenu: ptr::null_mut(),
-            hwndParent: ptr::null_mut(),
+            hwndParent: owner, // this is the owner, rather than the parent, for windows which aren't `WS_CHILD`
             x: pos_x,
             y: pos_y,
             cx: width,
             cy: height,
-            style: dw_style as _,
+            // Windows which start maximised or minimised are shown once they're in that state, so they don't flash
+            style: (if builder.maximised || builder.minimised { dw_style & !WS_VISIBLE } else { dw_style }) as _,
             lpszName: title_name,
             lpszClass: class_name,
             dwExStyle: dw_style_ex,
@@ -488,15 +750,27 @@ unsafe fn make_window(builder: window::Builder) -> Result<Window, Error> {
     }?;
 
     set_close_button(hwnd, style.controls.as_ref().map(|x| x.close).unwrap_or(false));
-    if builder.maximised {
-        let _ = ShowWindow(hwnd, 3);
+    if style.visible {
+        if builder.minimised {
+            let _ = ShowWindow(hwnd, 7); // SW_SHOWMINNOACTIVE
+        } else if builder.maximised {
+            let _ = ShowWindow(hwnd, 3);
+        }
     }
 
-    Ok(Window {
-        _connection: builder.connection,
+    sync::mutex_lock(&builder.connection.0).windows.push(hwnd as usize);
+    let window = Window {
+        connection: builder.connection,
         hwnd,
         state: window_state,
-    })
+    };
+    if builder.always_on_top {
+        window.set_always_on_top(true);
+    }
+    if style.visible && builder.fullscreen {
+        window.set_fullscreen(true);
+    }
+    Ok(window)
 }
 
 impl Window {
@@ -511,16 +785,195 @@ impl Window {
         }
     }
 
+    pub(crate) fn drain_events(&mut self) -> Vec<Event> {
+        unsafe {
+            // safety: the front buffer is only touched by the thread which owns the window, like in `events`
+            (&mut *self.state.get()).event_frontbuf.drain(..).collect()
+        }
+    }
+
+    pub(crate) fn keep_alive(&self) {
+        // Messages are handled by the connection's own thread, so Windows never sees the window as unresponsive
+    }
+
+    pub(crate) fn sync(&self) {
+        unsafe {
+            // Sent messages are handled in order, so this returns once the window thread has caught up
+            let _ = SendMessageW(self.hwnd, WM_NULL, 0, 0);
+        }
+    }
+
     pub(crate) fn poll_events(&mut self) {
         unsafe {
-            let state = &mut *self.state.get();
-            let guard = sync::mutex_lock(&state.event_sync);
-            state.event_frontbuf.clear();
-            mem::swap(&mut state.event_frontbuf, &mut state.event_backbuf);
-            mem::drop(guard);
+            let state = &*self.state.get();
+            self.poll_events_locked(sync::mutex_lock(&state.event_sync));
+        }
+    }
+
+    /// Polls events like `poll_events`, unless the window thread is storing an event right now.
+    pub(crate) fn try_poll_events(&mut self) -> bool {
+        unsafe {
+            let state = &*self.state.get();
+            match sync::mutex_try_lock(&state.event_sync) {
+                Some(guard) => {
+                    self.poll_events_locked(guard);
+                    true
+                },
+                None => false,
+            }
+        }
+    }
+
+    /// The body of `poll_events`, for once `event_sync` is locked. The lock is released as soon as the events are in.
+    unsafe fn poll_events_locked(&mut self, guard: sync::MutexGuard<'_, ()>) {
+        let state = &mut *self.state.get();
+        state.event_frontbuf.clear();
+        mem::swap(&mut state.event_frontbuf, &mut state.event_backbuf);
+        #[cfg(feature = "input")]
+        {
+            state.raw_modifiers = state.raw_modifiers_backbuf;
+        }
+        mem::drop(guard);
+
+        // Only the user thread touches this, so it stays consistent with the events just made visible
+        #[cfg(feature = "input")]
+        let mut recheck_cursor = false;
+        #[cfg(feature = "input")]
+        for event in state.event_frontbuf.iter() {
+            match *event {
+                Event::KeyboardDown(key, _) => state.modifiers.update(key, true),
+                Event::KeyboardUp(key, _) => state.modifiers.update(key, false),
+                Event::MouseDown(button) => state.mouse_buttons.update(button, true),
+                Event::MouseUp(button) => state.mouse_buttons.update(button, false),
+                // Keys and buttons may have been let go of while some other window had focus
+                Event::Focus { focused: true, .. } => state.resync_input = true,
+                // A button may have been let go of while some other window had the mouse captured
+                Event::MouseEnter => {
+                    state.mouse_buttons = async_mouse_buttons();
+                    state.cursor_inside = true;
+                },
+                Event::MouseLeave | Event::Visible(false) => state.cursor_inside = false,
+                // The window may have appeared under a cursor which hasn't moved yet
+                Event::Visible(true) => recheck_cursor = true,
+                _ => (),
+            }
+        }
+        #[cfg(feature = "input")]
+        if mem::take(&mut state.resync_input) {
+            resync_input(state);
+            recheck_cursor = true;
+        }
+        #[cfg(feature = "input")]
+        if recheck_cursor {
+            state.cursor_inside = cursor_in_client(self.hwnd);
+        }
+        #[cfg(feature = "input")]
+        if !state.key_repeat {
+            state.event_frontbuf.retain(|event| !matches!(event, Event::KeyboardRepeat(..)));
+        }
+        #[cfg(feature = "input")]
+        if !state.text_input {
+            state.event_frontbuf.retain(|event| !matches!(event, Event::Input(_)));
+        }
+
+        // DWM doesn't notify us of vblanks, so we check whether one has happened since the last poll
+        if let Some(last_vblank) = state.frame_pacing {
+            let win32 = WIN32.get();
+            let mut timing: DWM_TIMING_INFO = mem::zeroed();
+            timing.cbSize = mem::size_of::<DWM_TIMING_INFO>() as UINT;
+            let success = win32.dl.DwmGetCompositionTimingInfo(ptr::null_mut(), &mut timing) == Some(0);
+            let (vblank, period) = (timing.qpcVBlank, timing.qpcRefreshPeriod); // copied out of the packed struct
+            if success && vblank != last_vblank && win32.qpc_frequency != 0 {
+                state.frame_pacing = Some(vblank);
+                let target = u128::from(vblank) + u128::from(period);
+                let micros = target * 1_000_000 / u128::from(win32.qpc_frequency);
+                state.event_frontbuf.push(Event::RedrawRequested(micros as u64));
+            }
+        }
+    }
+
+    /// Polls events until there's at least one, or until `deadline` passes.
+    pub(crate) fn wait_events_until(&mut self, deadline: Option<Instant>) {
+        loop {
+            unsafe {
+                let state = &*self.state.get();
+                // `RedrawRequested` is made up by `poll_events` rather than sent, so we wake up once per vblank for it
+                let deadline = match (state.frame_pacing, self.last_frame_time()) {
+                    (Some(_), Some(frame)) => {
+                        let vblank = Instant::now() + frame;
+                        Some(deadline.map_or(vblank, |deadline| deadline.min(vblank)))
+                    },
+                    _ => deadline,
+                };
+                let mut guard = sync::mutex_lock(&state.event_sync);
+                while state.event_backbuf.is_empty() {
+                    match deadline {
+                        Some(deadline) => match deadline.saturating_duration_since(Instant::now()) {
+                            Duration::ZERO => break,
+                            remaining => sync::cvar_wait_timeout(&state.event_cvar, &mut guard, remaining),
+                        },
+                        None => sync::cvar_wait(&state.event_cvar, &mut guard),
+                    }
+                }
+                mem::drop(guard);
+            }
+            self.poll_events();
+            let passed = deadline.map_or(false, |deadline| Instant::now() >= deadline);
+            if !self.events().is_empty() || passed {
+                return
+            }
+        }
+    }
+
+    pub(crate) fn is_connected(&self) -> bool {
+        // There's no server to lose, but the window goes away if its thread does
+        unsafe { IsWindow(self.hwnd) != 0 }
+    }
+
+    pub(crate) fn is_minimised(&self) -> bool {
+        unsafe { IsIconic(self.hwnd) != 0 }
+    }
+
+    pub(crate) fn scale_factor(&self) -> f64 {
+        // This stays at `BASE_DPI` unless the process is per-monitor DPI aware
+        unsafe { f64::from((&*self.state.get()).dpi) / f64::from(BASE_DPI) }
+    }
+
+    pub(crate) fn position(&self) -> (i16, i16) {
+        unsafe {
+            let mut point = POINT { x: 0, y: 0 };
+            let _ = ClientToScreen(self.hwnd, &mut point);
+            (point.x as i16, point.y as i16)
+        }
+    }
+
+    pub(crate) fn size(&self) -> (u16, u16) {
+        unsafe {
+            let mut rect: RECT = mem::zeroed();
+            let _ = GetClientRect(self.hwnd, &mut rect);
+            ((rect.right - rect.left) as u16, (rect.bottom - rect.top) as u16)
         }
     }
 
+    #[cfg(feature = "input")]
+    pub(crate) fn modifiers(&self) -> Modifiers {
+        unsafe { (&*self.state.get()).modifiers }
+    }
+
+    #[cfg(feature = "input")]
+    pub(crate) fn raw_modifiers(&self) -> RawModifiers {
+        unsafe { (&*self.state.get()).raw_modifiers }
+    }
+
+    #[cfg(feature = "input")]
+    pub(crate) fn mouse_buttons(&self) -> MouseButtons {
+        unsafe { (&*self.state.get()).mouse_buttons }
+    }
+
+    pub(crate) fn batch(&self) -> Batch<'_> {
+        Batch { window: self }
+    }
+
     pub(crate) fn set_borderless(&self, borderless: bool) {
         unsafe {
             let state = &mut *user_state(self.hwnd);
@@ -528,8 +981,12 @@ impl Window {
             state.style.borderless = borderless;
             let (dw_style, dw_style_ex) = style_to_bits(&state.style);
             std::mem::drop(_g);
+            if state.fullscreen.is_some() {
+                // This gets applied when the window leaves fullscreen
+                return
+            }
             let _ = set_instance_storage(self.hwnd, GWL_STYLE, dw_style as _);
-            let _ = set_instance_storage(self.hwnd, GWL_EXSTYLE, dw_style_ex as _);
+            set_ex_style(self.hwnd, dw_style_ex);
             ping_window_frame(self.hwnd);
         }
     }
@@ -540,6 +997,144 @@ impl Window {
         }
     }
 
+    pub(crate) fn set_cursor_confine_rect(&self, rect: Option<Rectangle>) {
+        unsafe {
+            let _ = match rect {
+                Some(Rectangle { x, y, width, height }) => {
+                    let rect = RECT {
+                        left: x.into(),
+                        top: y.into(),
+                        right: LONG::from(x) + LONG::from(width),
+                        bottom: LONG::from(y) + LONG::from(height),
+                    };
+                    ClipCursor(&rect)
+                },
+                None => ClipCursor(ptr::null()),
+            };
+        }
+    }
+
+    pub(crate) fn trigger_caption_action(&self, action: CaptionAction) {
+        unsafe {
+            // These are the same commands a real caption sends, so they go through `DefWindowProcW` the same way
+            _ = match action {
+                CaptionAction::Maximise => PostMessageW(self.hwnd, WM_SYSCOMMAND, SC_MAXIMIZE, 0),
+                CaptionAction::Restore => PostMessageW(self.hwnd, WM_SYSCOMMAND, SC_RESTORE, 0),
+                CaptionAction::Menu => PostMessageW(self.hwnd, RAMEN_WM_SHOWMENU, 0, 0),
+            };
+        }
+    }
+
+    pub(crate) fn set_cursor_confined(&self, confined: bool) {
+        unsafe {
+            // Clipping has to follow the focus and size changes seen by the window thread
+            _ = SendMessageW(self.hwnd, RAMEN_WM_SETCONFINED, confined as WPARAM, 0);
+        }
+    }
+
+    pub(crate) fn move_to_monitor(&self, monitor: &Monitor) {
+        unsafe {
+            let mut client = RECT { left: 0, top: 0, right: 0, bottom: 0 };
+            let mut origin = POINT { x: 0, y: 0 };
+            let _ = GetClientRect(self.hwnd, &mut client);
+            let _ = ClientToScreen(self.hwnd, &mut origin);
+            let current = Rectangle {
+                x: origin.x as i16,
+                y: origin.y as i16,
+                width: client.right as u16,
+                height: client.bottom as u16,
+            };
+            self.set_position(monitor::place_on_monitor(current, &enum_monitors(), monitor));
+        }
+    }
+
+    pub(crate) fn request_focus(&self) {
+        unsafe {
+            // If this is refused, Windows flashes the taskbar button instead
+            let _ = SetForegroundWindow(self.hwnd);
+        }
+    }
+
+    pub(crate) fn set_opacity(&self, opacity: f32) {
+        unsafe {
+            // Layered windows cost more to draw, so an opaque window is taken back out of that mode
+            let ex_style = instance_storage(self.hwnd, GWL_EXSTYLE) as DWORD;
+            if opacity >= 1.0 {
+                let _ = set_instance_storage(self.hwnd, GWL_EXSTYLE, (ex_style & !WS_EX_LAYERED) as _);
+            } else {
+                let _ = set_instance_storage(self.hwnd, GWL_EXSTYLE, (ex_style | WS_EX_LAYERED) as _);
+                let _ = SetLayeredWindowAttributes(self.hwnd, 0, (opacity * 255.0).round() as BYTE, LWA_ALPHA);
+            }
+        }
+    }
+
+    pub(crate) fn set_urgent(&self, urgent: bool) {
+        unsafe {
+            // Flashing once leaves the taskbar button highlighted until the window is focused, and un-flashing
+            // puts it back to normal
+            let _ = FlashWindow(self.hwnd, if urgent { TRUE } else { FALSE });
+        }
+    }
+
+    #[cfg(feature = "input")]
+    pub(crate) fn set_key_repeat(&self, enabled: bool) {
+        unsafe {
+            // Repeats are filtered out in `poll_events`, so this is only touched by the user thread
+            (&mut *self.state.get()).key_repeat = enabled;
+        }
+    }
+
+    #[cfg(feature = "input")]
+    pub(crate) fn set_text_input(&self, enabled: bool) {
+        unsafe {
+            // Like `key_repeat`, text is filtered out in `poll_events` by the user thread
+            (&mut *self.state.get()).text_input = enabled;
+            // The IME context has to be swapped on the window thread
+            _ = SendMessageW(self.hwnd, RAMEN_WM_SETTEXTINPUT, enabled as WPARAM, 0);
+        }
+    }
+
+    #[cfg(feature = "input")]
+    pub(crate) fn cursor_inside(&self) -> bool {
+        unsafe { (&*self.state.get()).cursor_inside }
+    }
+
+    #[cfg(feature = "input")]
+    pub(crate) fn resync_input_state(&self) {
+        unsafe {
+            // Picked up by the next `poll_events`, since only the user thread touches the held state
+            (&mut *self.state.get()).resync_input = true;
+        }
+    }
+
+    pub(crate) fn set_frame_pacing(&self, enabled: bool) {
+        unsafe {
+            // Like `modifiers`, this is only ever touched by the user thread
+            let state = &mut *self.state.get();
+            state.frame_pacing = match (enabled, state.frame_pacing) {
+                (true, None) => Some(0),
+                (true, last) => last,
+                (false, _) => None,
+            };
+        }
+    }
+
+    pub(crate) fn last_frame_time(&self) -> Option<std::time::Duration> {
+        unsafe {
+            let win32 = WIN32.get();
+            let mut timing: DWM_TIMING_INFO = mem::zeroed();
+            timing.cbSize = mem::size_of::<DWM_TIMING_INFO>() as UINT;
+            let success = win32.dl.DwmGetCompositionTimingInfo(ptr::null_mut(), &mut timing) == Some(0);
+            let period = timing.qpcRefreshPeriod; // copied out of the packed struct
+            if success && period != 0 && win32.qpc_frequency != 0 {
+                let nanos = u128::from(period) * 1_000_000_000 / u128::from(win32.qpc_frequency);
+                Some(std::time::Duration::from_nanos(nanos as u64))
+            } else {
+                None
+            }
+        }
+    }
+
     pub(crate) fn set_resizable(&self, resizable: bool) {
         unsafe {
             let state = &mut *user_state(self.hwnd);
@@ -547,12 +1142,62 @@ impl Window {
             state.style.resizable = resizable;
             let (dw_style, dw_style_ex) = style_to_bits(&state.style);
             std::mem::drop(_g);
+            if state.fullscreen.is_some() {
+                // This gets applied when the window leaves fullscreen
+                return
+            }
             let _ = set_instance_storage(self.hwnd, GWL_STYLE, dw_style as _);
-            let _ = set_instance_storage(self.hwnd, GWL_EXSTYLE, dw_style_ex as _);
+            set_ex_style(self.hwnd, dw_style_ex);
             ping_window_frame(self.hwnd);
         }
     }
 
+    pub(crate) fn set_always_on_top(&self, always_on_top: bool) {
+        unsafe {
+            // The topmost flag can only be changed by moving the window in the Z order, not through its styles
+            let after = if always_on_top { HWND_TOPMOST } else { HWND_NOTOPMOST };
+            let _ = SetWindowPos(self.hwnd, after, 0, 0, 0, 0, SWP_NOMOVE | SWP_NOSIZE | SWP_NOACTIVATE);
+        }
+    }
+
+    pub(crate) fn set_fullscreen(&self, fullscreen: bool) {
+        unsafe {
+            let state = &mut *user_state(self.hwnd);
+            if state.fullscreen.is_some() == fullscreen {
+                return
+            }
+            // Only the frame bits are touched, so a maximised window keeps `WS_MAXIMIZE` the whole time
+            let frame_bits = WS_CAPTION | WS_THICKFRAME | WS_POPUP;
+            let current = instance_storage(self.hwnd, GWL_STYLE) as DWORD;
+            if fullscreen {
+                // The placement remembers whether the window was maximised, so it's restored the same way
+                let mut placement: WINDOWPLACEMENT = mem::zeroed();
+                placement.length = mem::size_of_val(&placement) as UINT;
+                let mut info: MONITORINFO = mem::zeroed();
+                info.cbSize = mem::size_of_val(&info) as DWORD;
+                let monitor = MonitorFromWindow(self.hwnd, MONITOR_DEFAULTTONEAREST);
+                if GetWindowPlacement(self.hwnd, &mut placement) == 0 || GetMonitorInfoW(monitor, &mut info) == 0 {
+                    return
+                }
+                state.fullscreen = Some(placement);
+                let _ = set_instance_storage(self.hwnd, GWL_STYLE, ((current & !frame_bits) | WS_POPUP) as _);
+                let RECT { left, top, right, bottom } = info.rcMonitor;
+                let flags = SWP_NOOWNERZORDER | SWP_FRAMECHANGED;
+                let _ = SetWindowPos(self.hwnd, ptr::null_mut(), left, top, right - left, bottom - top, flags);
+            } else if let Some(placement) = state.fullscreen.take() {
+                let _g = sync::mutex_lock(&state.event_sync);
+                let (dw_style, dw_style_ex) = style_to_bits(&state.style);
+                std::mem::drop(_g);
+                let dw_style = (current & !frame_bits) | (dw_style & frame_bits);
+                let _ = set_instance_storage(self.hwnd, GWL_STYLE, dw_style as _);
+                set_ex_style(self.hwnd, dw_style_ex);
+                let _ = SetWindowPlacement(self.hwnd, &placement);
+                ping_window_frame(self.hwnd);
+            }
+            state.dispatch_event(Event::Fullscreen(fullscreen));
+        }
+    }
+
     pub(crate) fn set_maximised(&self, maximised: bool) {
         unsafe {
             let state = &*self.state.get();
@@ -562,6 +1207,20 @@ impl Window {
         }
     }
 
+    pub(crate) fn set_minimised(&self, minimised: bool) {
+        unsafe {
+            // Restoring puts a window back to maximised if it was before it was minimised
+            if (IsIconic(self.hwnd) != 0) != minimised {
+                let _ = ShowWindow(self.hwnd, if minimised { SW_MINIMIZE } else { SW_RESTORE });
+            }
+        }
+    }
+
+    pub(crate) fn set_icon(&self, _rgba: &[u8], _width: u32, _height: u32) -> Result<(), Error> {
+        // TODO: build an HICON for WM_SETICON
+        Ok(())
+    }
+
     pub(crate) fn set_title(&self, title: &str) {
         unsafe {
             let mut name_wstr = Vec::<WCHAR>::new();
@@ -583,10 +1242,46 @@ impl Window {
         }
     }
 
-    pub(crate) fn set_size(&self, (w, h): (u16, u16)) {
+    pub(crate) fn set_max_size(&self, size: Option<(u16, u16)>) -> Result<(), Error> {
+        unsafe {
+            let state = &mut *user_state(self.hwnd);
+            let _g = sync::mutex_lock(&state.event_sync);
+            if !size_limits_valid(state.min_size, size) {
+                return Err(Error::Invalid)
+            }
+            state.max_size = size;
+            let wh = state.wh;
+            std::mem::drop(_g);
+            // The new limit is only checked by the system on the next resize, so this enforces it right away
+            self.set_size(wh);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn set_min_size(&self, size: Option<(u16, u16)>) -> Result<(), Error> {
+        unsafe {
+            let state = &mut *user_state(self.hwnd);
+            let _g = sync::mutex_lock(&state.event_sync);
+            if !size_limits_valid(size, state.max_size) {
+                return Err(Error::Invalid)
+            }
+            state.min_size = size;
+            let wh = state.wh;
+            std::mem::drop(_g);
+            self.set_size(wh);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn set_base_size(&self, _size: Option<(u16, u16)>) {
+        // Windows has no resize increments for a base size to apply to
+    }
+
+    pub(crate) fn set_size(&self, size: (u16, u16)) {
         unsafe {
             let state = &*self.state.get();
             let _g = sync::mutex_lock(&state.event_sync);
+            let (w, h) = clamp_size(size, state.min_size, state.max_size);
             let (dw_style, dw_style_ex) = style_to_bits(&state.style);
             let ((width, height), _) = adjust_window_for_dpi(WIN32.get(), (w, h), dw_style, dw_style_ex, state.dpi);
             std::mem::drop(_g);
@@ -594,25 +1289,179 @@ impl Window {
         }
     }
 
+    pub(crate) fn set_size_with_anchor(&self, size: (u16, u16), anchor: Gravity) {
+        unsafe {
+            let state = &*self.state.get();
+            let _g = sync::mutex_lock(&state.event_sync);
+            let (w, h) = clamp_size(size, state.min_size, state.max_size);
+            let (dw_style, dw_style_ex) = style_to_bits(&state.style);
+            let ((width, height), _) = adjust_window_for_dpi(WIN32.get(), (w, h), dw_style, dw_style_ex, state.dpi);
+            std::mem::drop(_g);
+            // The anchor is kept in place on the outer rect, so the frame grows evenly along with the inner area
+            let mut rect: RECT = mem::zeroed();
+            if GetWindowRect(self.hwnd, &mut rect) == 0 {
+                return
+            }
+            let old = (rect.right - rect.left, rect.bottom - rect.top);
+            let (dx, dy) = anchor.offset(old, (width, height));
+            let (x, y) = (rect.left + dx, rect.top + dy);
+            let _ = SetWindowPos(self.hwnd, ptr::null_mut(), x, y, width as _, height as _, SWP_NOZORDER);
+        }
+    }
+
     pub(crate) fn set_visible(&self, visible: bool) {
         unsafe {
             let _ = ShowWindow(self.hwnd, if visible { SW_SHOW } else { SW_HIDE });
         }
     }
 
+    pub(crate) fn set_withdrawn(&self, withdrawn: bool) {
+        // Windows has no withdrawn state, a hidden window keeps its place and its minimised or maximised state
+        self.set_visible(!withdrawn)
+    }
+
     pub(crate) fn hwnd(&self) -> HWND {
         self.hwnd
     }
+
+    pub(crate) fn id(&self) -> WindowId {
+        WindowId(self.hwnd as usize as u64)
+    }
 }
 
 impl WindowState {
     fn dispatch_event(&mut self, event: Event) {
+        // The key state is only accurate on the window thread, so it's read here and carried over in `poll_events`
+        #[cfg(feature = "input")]
+        let raw_modifiers = match event {
+            Event::KeyboardDown(..) | Event::KeyboardRepeat(..) | Event::KeyboardUp(..)
+                | Event::MouseDown(_) | Event::MouseUp(_) => Some(unsafe { raw_key_state() }),
+            _ => None,
+        };
         let guard = sync::mutex_lock(&self.event_sync);
+        #[cfg(feature = "input")]
+        if let Some(raw) = raw_modifiers {
+            self.raw_modifiers_backbuf = raw;
+        }
         self.event_backbuf.push(event);
         mem::drop(guard);
+        sync::cvar_notify_one(&self.event_cvar);
     }
 }
 
+/// Reads the modifier mask described in [`RawModifiers`] from the calling thread's key state.
+#[cfg(feature = "input")]
+unsafe fn raw_key_state() -> RawModifiers {
+    const HELD: [u8; 8] = [VK_LSHIFT, VK_RSHIFT, VK_LCONTROL, VK_RCONTROL, VK_LMENU, VK_RMENU, VK_LWIN, VK_RWIN];
+    const TOGGLED: [u8; 3] = [VK_CAPITAL, VK_NUMLOCK, VK_SCROLL];
+    let mut bits = 0;
+    for (i, vk) in HELD.iter().enumerate() {
+        // the high bit is set while the key is down
+        if GetKeyState(c_int::from(*vk)) < 0 {
+            bits |= 1 << i;
+        }
+    }
+    for (i, vk) in TOGGLED.iter().enumerate() {
+        // and the low bit while it's toggled on
+        if GetKeyState(c_int::from(*vk)) & 1 != 0 {
+            bits |= 1 << (HELD.len() + i);
+        }
+    }
+    RawModifiers(bits)
+}
+
+/// Decodes the mask from `raw_key_state`.
+#[cfg(feature = "input")]
+fn key_modifiers(raw: RawModifiers) -> KeyModifiers {
+    let RawModifiers(bits) = raw;
+    KeyModifiers {
+        shift: bits & 0b11 != 0,
+        control: bits & 0b1100 != 0,
+        alt: bits & 0b110000 != 0,
+        super_: bits & 0b11000000 != 0,
+        caps_lock: bits & (1 << 8) != 0,
+        num_lock: bits & (1 << 9) != 0,
+    }
+}
+
+/// Reads which mouse buttons are held right now. Unlike `GetKeyState`, this works from any thread.
+#[cfg(feature = "input")]
+unsafe fn async_mouse_buttons() -> MouseButtons {
+    // The virtual key codes are for the physical buttons, so they're switched back for left-handed users
+    let swapped = GetSystemMetrics(SM_SWAPBUTTON) != 0;
+    let (left, right) = if swapped { (VK_RBUTTON, VK_LBUTTON) } else { (VK_LBUTTON, VK_RBUTTON) };
+    let mut buttons = MouseButtons::new();
+    buttons.update(MouseButton::Left, GetAsyncKeyState(c_int::from(left)) < 0);
+    buttons.update(MouseButton::Middle, GetAsyncKeyState(c_int::from(VK_MBUTTON)) < 0);
+    buttons.update(MouseButton::Right, GetAsyncKeyState(c_int::from(right)) < 0);
+    buttons.update(MouseButton::X1, GetAsyncKeyState(c_int::from(VK_XBUTTON1)) < 0);
+    buttons.update(MouseButton::X2, GetAsyncKeyState(c_int::from(VK_XBUTTON2)) < 0);
+    buttons
+}
+
+/// Clips the cursor to the client area of `hwnd`, if it's confined and has focus. Only the window thread may call this.
+unsafe fn clip_cursor_to_client(hwnd: HWND, state: &WindowState) {
+    if !state.cursor_confined || GetFocus() != hwnd {
+        return
+    }
+    let mut rect: RECT = mem::zeroed();
+    let mut origin = POINT { x: 0, y: 0 };
+    if GetClientRect(hwnd, &mut rect) != 0 && ClientToScreen(hwnd, &mut origin) != 0 {
+        let rect = RECT {
+            left: origin.x,
+            top: origin.y,
+            right: origin.x + rect.right,
+            bottom: origin.y + rect.bottom,
+        };
+        _ = ClipCursor(&rect);
+    }
+}
+
+/// Whether the cursor is over the client area of `hwnd`, and not covered by another window.
+#[cfg(feature = "input")]
+unsafe fn cursor_in_client(hwnd: HWND) -> bool {
+    let mut mouse_pos: POINT = mem::zeroed();
+    if GetCursorPos(&mut mouse_pos) == 0 || WindowFromPoint(POINT { ..mouse_pos }) != hwnd {
+        return false
+    }
+    let mut rect: RECT = mem::zeroed();
+    if ScreenToClient(hwnd, &mut mouse_pos) == 0 || GetClientRect(hwnd, &mut rect) == 0 {
+        return false
+    }
+    mouse_pos.x >= rect.left && mouse_pos.x < rect.right && mouse_pos.y >= rect.top && mouse_pos.y < rect.bottom
+}
+
+/// Sends the `KeyboardUp` and `MouseUp` events which were missed for any modifier keys and mouse buttons which
+/// the user thread thinks are held but aren't. Only the user thread may call this.
+#[cfg(feature = "input")]
+unsafe fn resync_input(state: &mut WindowState) {
+    for key in state.modifiers.held_keys() {
+        let vk = match key {
+            Key::LeftShift => VK_LSHIFT,
+            Key::RightShift => VK_RSHIFT,
+            Key::LeftControl => VK_LCONTROL,
+            Key::RightControl => VK_RCONTROL,
+            Key::LeftAlt => VK_LMENU,
+            Key::RightAlt => VK_RMENU,
+            Key::LeftSuper => VK_LWIN,
+            Key::RightSuper => VK_RWIN,
+            _ => continue,
+        };
+        if GetAsyncKeyState(c_int::from(vk)) >= 0 {
+            state.modifiers.update(key, false);
+            state.event_frontbuf.push(Event::KeyboardUp(key, key_modifiers(state.raw_modifiers)));
+        }
+    }
+
+    let buttons = async_mouse_buttons();
+    for button in [MouseButton::Left, MouseButton::Middle, MouseButton::Right, MouseButton::X1, MouseButton::X2] {
+        if state.mouse_buttons.is_pressed(button) && !buttons.is_pressed(button) {
+            state.event_frontbuf.push(Event::MouseUp(button));
+        }
+    }
+    state.mouse_buttons = buttons;
+}
+
 /// Returns a pointer to the `WindowState` for a ramen window.
 #[inline]
 unsafe fn user_state(hwnd: HWND) -> *mut WindowState {
@@ -632,21 +1481,23 @@ fn extend_key(key: Key, lparam: LPARAM) -> Key {
     }
 }
 
+/// Only the window thread may call this, as it reads the thread's key state.
 #[cfg(feature = "input")]
-fn map_tr_state(key: Key, lparam: LPARAM) -> Event {
+unsafe fn map_tr_state(key: Key, lparam: LPARAM) -> Event {
+    let modifiers = key_modifiers(raw_key_state());
     if (lparam & (1 << 31)) == 0 {
         if (lparam & (1 << 30)) != 0 {
-            Event::KeyboardRepeat(key)
+            Event::KeyboardRepeat(key, modifiers)
         } else {
-            Event::KeyboardDown(key)
+            Event::KeyboardDown(key, modifiers)
         }
     } else {
-        Event::KeyboardUp(key)
+        Event::KeyboardUp(key, modifiers)
     }
 }
 
 #[cfg(feature = "input")]
-fn sys_key_event(wparam: WPARAM, lparam: LPARAM) -> Option<Event> {
+unsafe fn sys_key_event(wparam: WPARAM, lparam: LPARAM) -> Option<Event> {
     let alt_bit = (lparam & (1 << 29)) != 0;
     let transition_state = (lparam & (1 << 31)) != 0;
 
@@ -946,7 +1797,7 @@ pub unsafe extern "system" fn window_proc(hwnd: HWND, msg: UINT, wparam: WPARAM,
         WM_CREATE => {
             let param = &**(lparam as *const *const WindowCreateParams);
             let state = &mut *param.state;
-            if WIN32.dpi_mode == Win32DpiMode::PerMonitorV1 || WIN32.dpi_mode == Win32DpiMode::PerMonitorV2 {
+            if *DPI_MODE == Win32DpiMode::PerMonitorV1 || *DPI_MODE == Win32DpiMode::PerMonitorV2 {
                 let monitor = MonitorFromWindow(hwnd, MONITOR_DEFAULTTONEAREST);
                 let mut dx: UINT = 0;
                 let mut dy: UINT = 0;
@@ -973,6 +1824,7 @@ pub unsafe extern "system" fn window_proc(hwnd: HWND, msg: UINT, wparam: WPARAM,
             if !(x as i16 == -32000 || y as i16 == -32000) {
                 state.dispatch_event(Event::Move((x as _, y as _)));
             }
+            clip_cursor_to_client(hwnd, state);
             0
         },
 
@@ -1007,6 +1859,7 @@ pub unsafe extern "system" fn window_proc(hwnd: HWND, msg: UINT, wparam: WPARAM,
                 SIZE_MAXIMIZED => set_max_min(state,  true, false),
                 _ => (), // rest are for pop-up (`WS_POPUP`) windows
             }
+            clip_cursor_to_client(hwnd, state);
             0
         },
 
@@ -1046,14 +1899,21 @@ pub unsafe extern "system" fn window_proc(hwnd: HWND, msg: UINT, wparam: WPARAM,
         // This is mainly intended for textbox controls but works perfectly fine for actual windows.
         // See also: `WM_ACTIVATE` (to know why this is used for focus events)
         WM_SETFOCUS => {
-            (*user_state(hwnd)).dispatch_event(Event::Focus(true));
+            let state = &mut *user_state(hwnd);
+            state.dispatch_event(Event::Focus { focused: true, reason: FocusReason::Normal });
+            // Windows releases the clip whenever another window is activated, so it's put back here
+            clip_cursor_to_client(hwnd, state);
             0
         },
 
         // Received when a window loses keyboard focus. Return 0.
         // See also: `WM_SETFOCUS` and `WM_ACTIVATE`
         WM_KILLFOCUS => {
-            (*user_state(hwnd)).dispatch_event(Event::Focus(false));
+            let state = &mut *user_state(hwnd);
+            state.dispatch_event(Event::Focus { focused: false, reason: FocusReason::Normal });
+            if state.cursor_confined {
+                _ = ClipCursor(ptr::null());
+            }
             0
         },
 
@@ -1080,11 +1940,53 @@ pub unsafe extern "system" fn window_proc(hwnd: HWND, msg: UINT, wparam: WPARAM,
             0
         },
 
+        // Received when the size or position of the window is about to change, to ask for its size limits.
+        // Note that this is sent before `WM_NCCREATE`, when there's no `WindowState` to read yet.
+        // wParam: Unused, ignore.
+        // lParam: `MINMAXINFO *` (in, out)
+        // Return 0 if handled.
+        WM_GETMINMAXINFO => {
+            let state = user_state(hwnd);
+            if !state.is_null() {
+                let state = &*state;
+                let info = &mut *(lparam as *mut MINMAXINFO);
+                let _g = sync::mutex_lock(&state.event_sync);
+                let (dw_style, dw_style_ex) = style_to_bits(&state.style);
+                if let Some(size) = state.min_size {
+                    let ((x, y), _) = adjust_window_for_dpi(WIN32.get(), size, dw_style, dw_style_ex, state.dpi);
+                    info.ptMinTrackSize = POINT { x, y };
+                }
+                if let Some(size) = state.max_size {
+                    let ((x, y), _) = adjust_window_for_dpi(WIN32.get(), size, dw_style, dw_style_ex, state.dpi);
+                    info.ptMaxTrackSize = POINT { x, y };
+                }
+                0
+            } else {
+                DefWindowProcW(hwnd, msg, wparam, lparam)
+            }
+        },
+
+        // Received by all top-level windows when the display layout changes, such as a monitor being plugged in.
+        // wParam: The new colour depth of the desktop, in bits per pixel.
+        // lParam: The new resolution of the primary monitor as `(width, height)` in the low and high words.
+        // Return 0 if handled.
+        WM_DISPLAYCHANGE => {
+            let state = &mut *user_state(hwnd);
+            let monitors = enum_monitors();
+            let mut events = Vec::new();
+            monitor::monitor_changes(&state.monitors, &monitors, |event| events.push(event));
+            state.monitors = monitors;
+            for event in events {
+                state.dispatch_event(event);
+            }
+            0
+        },
+
         WM_NCCREATE => {
             // `lpCreateParams` is the first field, so `CREATESTRUCTW *` is `WindowCreateParams **`
             let params = &**(lparam as *const *const WindowCreateParams);
             let _ = set_instance_storage(hwnd, GWL_USERDATA, params.state as usize);
-            if WIN32.dpi_mode == Win32DpiMode::PerMonitorV1 {
+            if *DPI_MODE == Win32DpiMode::PerMonitorV1 {
                 _ = WIN32.dl.EnableNonClientDpiScaling(hwnd);
             }
             DefWindowProcW(hwnd, msg, wparam, lparam)
@@ -1161,15 +2063,16 @@ pub unsafe extern "system" fn window_proc(hwnd: HWND, msg: UINT, wparam: WPARAM,
             brony_detected.into()
         },
 
-        WM_MOUSEWHEEL => {
+        // wParam: HIWORD = the distance scrolled, in multiples of `WHEEL_DELTA` for ordinary mice. Return 0.
+        // Positive is away from the user for `WM_MOUSEWHEEL`, and to the right for `WM_MOUSEHWHEEL`.
+        WM_MOUSEWHEEL | WM_MOUSEHWHEEL => {
             #[cfg(feature = "input")]
             {
                 let state = &mut *user_state(hwnd);
-                let delta = (wparam >> 16) as u16 as i16;
-                if delta > 0 {
-                    state.dispatch_event(Event::ScrollUp);
-                } else if delta < 0 {
-                    state.dispatch_event(Event::ScrollDown);
+                let delta = i32::from((wparam >> 16) as u16 as i16);
+                if delta != 0 {
+                    let (dx, dy) = if msg == WM_MOUSEHWHEEL { (delta, 0) } else { (0, delta) };
+                    state.dispatch_event(Event::Scroll { dx, dy });
                 }
             }
             0
@@ -1179,8 +2082,9 @@ pub unsafe extern "system" fn window_proc(hwnd: HWND, msg: UINT, wparam: WPARAM,
             #[cfg(feature = "input")]
             {
                 let state = &mut *user_state(hwnd);
-                let x = lparam & 0xFFFF;
-                let y = (lparam >> 16) & 0xFFFF;
+                // These are signed, and go negative while a button is held and the mouse is left of or above us
+                let x = f32::from((lparam & 0xFFFF) as u16 as i16);
+                let y = f32::from(((lparam >> 16) & 0xFFFF) as u16 as i16);
                 if !state.mouse_tracked {
                     state.mouse_tracked = true;
                     state.dispatch_event(Event::MouseEnter);
@@ -1192,7 +2096,7 @@ pub unsafe extern "system" fn window_proc(hwnd: HWND, msg: UINT, wparam: WPARAM,
                     };
                     let _ = TrackMouseEvent(&mut tme);
                 }
-                state.dispatch_event(Event::MouseMove((x as _, y as _)));
+                state.dispatch_event(Event::MouseMove { x, y });
             }
             0
         },
@@ -1200,11 +2104,15 @@ pub unsafe extern "system" fn window_proc(hwnd: HWND, msg: UINT, wparam: WPARAM,
         WM_DPICHANGED => {
             let dx = (wparam & 0xffff) as UINT;
             let mut state = &mut *user_state(hwnd);
-            if WIN32.dpi_mode == Win32DpiMode::PerMonitorV1 || WIN32.dpi_mode == Win32DpiMode::PerMonitorV2 {
+            if *DPI_MODE == Win32DpiMode::PerMonitorV1 || *DPI_MODE == Win32DpiMode::PerMonitorV2 {
+                let changed = dx != state.dpi;
                 state.dpi = dx;
                 let (dw_style, dw_style_ex) = style_to_bits(&state.style);
                 let ((width, height), _) = adjust_window_for_dpi(WIN32.get(), state.wh, dw_style, dw_style_ex, dx);
                 let _ = SetWindowPos(hwnd, ptr::null_mut(), 0, 0, width as _, height as _, SWP_NOMOVE);
+                if changed {
+                    state.dispatch_event(Event::ScaleFactorChanged(f64::from(dx) / f64::from(BASE_DPI)));
+                }
             }
             0
         },
@@ -1267,6 +2175,23 @@ pub unsafe extern "system" fn window_proc(hwnd: HWND, msg: UINT, wparam: WPARAM,
             }
             0
         },
+        // wParam: HIWORD = which of the side buttons it was. Unlike the other buttons, this returns TRUE.
+        WM_XBUTTONDOWN | WM_XBUTTONUP => {
+            #[cfg(feature = "input")]
+            {
+                let state = &mut *user_state(hwnd);
+                let button = match (wparam >> 16) as WORD {
+                    XBUTTON1 => Some(MouseButton::X1),
+                    XBUTTON2 => Some(MouseButton::X2),
+                    _ => None,
+                };
+                if let Some(button) = button {
+                    let f = if msg == WM_XBUTTONDOWN { Event::MouseDown } else { Event::MouseUp };
+                    state.dispatch_event(f(button));
+                }
+            }
+            TRUE as LRESULT
+        },
 
         WM_SETCURSOR => {
             if (hwnd == wparam as HWND) && ((lparam & 0xFFFF) as WORD == HTCLIENT as WORD) {
@@ -1285,6 +2210,42 @@ pub unsafe extern "system" fn window_proc(hwnd: HWND, msg: UINT, wparam: WPARAM,
             0
         },
 
+        // Custom message: Turns the IME on (`wparam != 0`) or off for this window.
+        // Return 0.
+        RAMEN_WM_SETTEXTINPUT => {
+            // Associating no context disables the IME, and `IACE_DEFAULT` puts back the one Windows gave us
+            let flags = if wparam != 0 { IACE_DEFAULT } else { 0 };
+            let _ = WIN32.get().dl.ImmAssociateContextEx(hwnd, ptr::null_mut(), flags);
+            0
+        },
+
+        // Custom message: Shows the window's system menu at the cursor, as if the caption had been right-clicked.
+        // Return 0.
+        RAMEN_WM_SHOWMENU => {
+            let menu = GetSystemMenu(hwnd, 0);
+            let mut mouse_pos: POINT = mem::zeroed();
+            if !menu.is_null() && GetCursorPos(&mut mouse_pos) != 0 {
+                let flags = TPM_RETURNCMD | TPM_RIGHTBUTTON;
+                let command = TrackPopupMenu(menu, flags, mouse_pos.x, mouse_pos.y, 0, hwnd, ptr::null());
+                if command != 0 {
+                    _ = PostMessageW(hwnd, WM_SYSCOMMAND, command as WPARAM, 0);
+                }
+            }
+            0
+        },
+
+        // Custom message: Confines the cursor to the client area while focused (`wparam != 0`), or releases it.
+        // Return 0.
+        RAMEN_WM_SETCONFINED => {
+            let state = &mut *user_state(hwnd);
+            if state.cursor_confined && wparam == 0 {
+                _ = ClipCursor(ptr::null());
+            }
+            state.cursor_confined = wparam != 0;
+            clip_cursor_to_client(hwnd, state);
+            0
+        },
+
         RAMEN_WM_SETCURSOR => {
             let state = &mut *user_state(hwnd);
             let cursor = mem::transmute::<_, Cursor>(wparam as u32);
@@ -1309,14 +2270,84 @@ pub unsafe extern "system" fn window_proc(hwnd: HWND, msg: UINT, wparam: WPARAM,
     }
 }
 
+// Win32 calls aren't buffered, so there's nothing to coalesce and each change goes straight through
+pub(crate) struct Batch<'a> {
+    window: &'a Window,
+}
+
+impl Batch<'_> {
+    pub(crate) fn set_borderless(&mut self, borderless: bool) {
+        self.window.set_borderless(borderless)
+    }
+
+    pub(crate) fn set_always_on_top(&mut self, always_on_top: bool) {
+        self.window.set_always_on_top(always_on_top)
+    }
+
+    pub(crate) fn set_fullscreen(&mut self, fullscreen: bool) {
+        self.window.set_fullscreen(fullscreen)
+    }
+
+    pub(crate) fn set_maximised(&mut self, maximised: bool) {
+        self.window.set_maximised(maximised)
+    }
+
+    pub(crate) fn set_minimised(&mut self, minimised: bool) {
+        self.window.set_minimised(minimised)
+    }
+
+    pub(crate) fn set_max_size(&mut self, size: Option<(u16, u16)>) -> Result<(), Error> {
+        self.window.set_max_size(size)
+    }
+
+    pub(crate) fn set_min_size(&mut self, size: Option<(u16, u16)>) -> Result<(), Error> {
+        self.window.set_min_size(size)
+    }
+
+    pub(crate) fn set_base_size(&mut self, size: Option<(u16, u16)>) {
+        self.window.set_base_size(size)
+    }
+
+    pub(crate) fn set_position(&mut self, position: (i16, i16)) {
+        self.window.set_position(position)
+    }
+
+    pub(crate) fn set_resizable(&mut self, resizable: bool) {
+        self.window.set_resizable(resizable)
+    }
+
+    pub(crate) fn set_size(&mut self, size: (u16, u16)) {
+        self.window.set_size(size)
+    }
+
+    pub(crate) fn set_size_with_anchor(&mut self, size: (u16, u16), anchor: Gravity) {
+        self.window.set_size_with_anchor(size, anchor)
+    }
+
+    pub(crate) fn set_title(&mut self, title: &str) {
+        self.window.set_title(title)
+    }
+
+    pub(crate) fn set_visible(&mut self, visible: bool) {
+        self.window.set_visible(visible)
+    }
+}
+
 impl Drop for Window {
     fn drop(&mut self) {
+        sync::mutex_lock(&self.connection.0).windows.retain(|&hwnd| hwnd != self.hwnd as usize);
         unsafe {
             let _ = PostMessageW(self.hwnd, RAMEN_WM_DROP, 0, 0);
         }
     }
 }
 
+/// Sets the extended styles from a `Style`, keeping `WS_EX_LAYERED`, which is `set_opacity`'s business.
+unsafe fn set_ex_style(hwnd: HWND, dw_style_ex: DWORD) {
+    let layered = instance_storage(hwnd, GWL_EXSTYLE) as DWORD & WS_EX_LAYERED;
+    let _ = set_instance_storage(hwnd, GWL_EXSTYLE, (dw_style_ex | layered) as _);
+}
+
 #[inline]
 unsafe fn ping_window_frame(hwnd: HWND) {
     const MASK: UINT = SWP_NOMOVE | SWP_NOSIZE | SWP_NOOWNERZORDER | SWP_NOZORDER | SWP_FRAMECHANGED;
@@ -13,7 +13,7 @@ macro_rules! cstr {
 }
 
 macro_rules! load {
-    ($($(#[$outer:meta])* $vis:vis $name:ident($type_name:ident) $($so_name:literal),+ {
+    ($($(#[$outer:meta])* $vis:vis $name:ident($type_name:ident $(, $env_name:literal)?) $($so_name:literal),+ {
         $($(#[$inner:meta])* fn $fn_name:ident($($arg_name:ident:$arg_ty:ty),+$(,)?) $(-> $ret:ty)?;)+
     })+) => {
         pub(self) enum __anyopaque {}
@@ -31,32 +31,61 @@ macro_rules! load {
                 $vis unsafe fn load() -> Result<(), crate::error::Error> {
                     static INIT: ::std::sync::Once = ::std::sync::Once::new();
                     static mut LOADED: bool = false;
+                    static mut ERROR: Option<String> = None; // why it failed to load, kept for every later call
+                    unsafe fn last_error(what: &str) -> String {
+                        let err = dlerror();
+                        if err.is_null() {
+                            format!("failed to load {}", what)
+                        } else {
+                            String::from_utf8_lossy(::std::ffi::CStr::from_ptr(err).to_bytes()).into_owned()
+                        }
+                    }
                     INIT.call_once(|| {
                         let mut handle = ::std::ptr::null_mut();
-                        for name in [$(cstr!($so_name)),+] {
-                            handle = dlopen(name);
-                            if !handle.is_null() { break; }
-                        }
                         let _ = dlerror();
+                        #[allow(unused_mut, unused_assignments)]
+                        let mut path: Option<::std::ffi::OsString> = None;
+                        $(path = ::std::env::var_os($env_name);)?
+                        // An explicit path is the only one tried, so that a mistake in it isn't hidden by a fallback.
+                        // It's loaded globally, so that other libraries loaded later which need it link against it.
+                        if let Some(path) = path {
+                            use ::std::os::unix::ffi::OsStrExt;
+                            match ::std::ffi::CString::new(path.as_bytes()) {
+                                Ok(path) => {
+                                    handle = ::libc::dlopen(path.as_ptr(), ::libc::RTLD_GLOBAL | ::libc::RTLD_LAZY)
+                                },
+                                Err(_) => {
+                                    ERROR = Some(format!("{:?} contains a nul byte", path));
+                                    return;
+                                },
+                            }
+                        } else {
+                            for name in [$(cstr!($so_name)),+] {
+                                handle = dlopen(name);
+                                if !handle.is_null() { break; }
+                            }
+                        }
+                        if handle.is_null() {
+                            ERROR = Some(last_error(concat!($($so_name, " "),+)));
+                            return;
+                        }
                         let mut fp = $name.as_mut_ptr().cast::<*mut c_void>();
-                        if handle.is_null() { return; }
                         $($(#[$inner])* {
                             *fp = dlsym(handle, cstr!(stringify!($fn_name)));
+                            if (*fp).is_null() {
+                                ERROR = Some(last_error(stringify!($fn_name)));
+                                return;
+                            }
                             fp = fp.offset(1);
                         })*
                         _ = fp;
                         LOADED = true;
                     });
-                    let err_start = dlerror();
-                    if err_start.is_null() {
-                        if LOADED {
-                            Ok(())
-                        } else {
-                            Err(crate::error::Error::Unsupported)
-                        }
+                    if LOADED {
+                        Ok(())
                     } else {
-                        let cstr = std::ffi::CStr::from_ptr(err_start);
-                        Err(crate::error::Error::Text(String::from_utf8_lossy(cstr.to_bytes())))
+                        let err = (*::std::ptr::addr_of!(ERROR)).clone().unwrap_or_default();
+                        Err(crate::error::Error::LibraryLoad(err))
                     }
                 }
             }
@@ -154,12 +183,14 @@ impl<T> TryPush<T> for Vec<T> {
 
 #[allow(dead_code, unused_imports)]
 pub(crate) mod sync {
-    pub(crate) use self::imp::{cvar_notify_one, cvar_wait, mutex_lock, Condvar, Mutex, MutexGuard};
+    pub(crate) use self::imp::{
+        cvar_notify_one, cvar_wait, cvar_wait_timeout, mutex_lock, mutex_try_lock, Condvar, Mutex, MutexGuard,
+    };
 
     #[cfg(not(feature = "parking-lot"))]
     pub(crate) mod imp {
-        use std::ptr;
-        pub(crate) use std::sync::{Condvar, Mutex, MutexGuard};
+        use std::{ptr, time::Duration};
+        pub(crate) use std::sync::{Condvar, Mutex, MutexGuard, TryLockError};
 
         #[inline]
         pub(crate) fn cvar_notify_one(cvar: &Condvar) {
@@ -176,9 +207,26 @@ pub(crate) mod sync {
             }
         }
 
+        pub(crate) fn cvar_wait_timeout<T>(cvar: &Condvar, guard: &mut MutexGuard<T>, timeout: Duration) {
+            // Same hack as `cvar_wait`
+            unsafe {
+                let guard_copy = ptr::read(guard);
+                let (result, _) = cvar.wait_timeout(guard_copy, timeout).expect("cvar mutex poisoned (this is a bug)");
+                ptr::write(guard, result);
+            }
+        }
+
         pub(crate) fn mutex_lock<T>(mtx: &Mutex<T>) -> MutexGuard<T> {
             mtx.lock().expect("mutex poisoned (this is a bug)")
         }
+
+        pub(crate) fn mutex_try_lock<T>(mtx: &Mutex<T>) -> Option<MutexGuard<'_, T>> {
+            match mtx.try_lock() {
+                Ok(guard) => Some(guard),
+                Err(TryLockError::WouldBlock) => None,
+                Err(TryLockError::Poisoned(_)) => panic!("mutex poisoned (this is a bug)"),
+            }
+        }
     }
 
     #[cfg(feature = "parking-lot")]
@@ -195,9 +243,19 @@ pub(crate) mod sync {
             cvar.wait(guard);
         }
 
+        #[inline]
+        pub(crate) fn cvar_wait_timeout<T>(cvar: &Condvar, guard: &mut MutexGuard<T>, timeout: std::time::Duration) {
+            let _ = cvar.wait_for(guard, timeout);
+        }
+
         #[inline]
         pub(crate) fn mutex_lock<T>(mtx: &Mutex<T>) -> MutexGuard<T> {
             mtx.lock()
         }
+
+        #[inline]
+        pub(crate) fn mutex_try_lock<T>(mtx: &Mutex<T>) -> Option<MutexGuard<'_, T>> {
+            mtx.try_lock()
+        }
     }
 }
@@ -6,7 +6,7 @@ pub use self::{
     decoration::{Controls, Style},
 };
 
-use crate::{event::Event, platform::imp};
+use crate::{event::Event, monitor::Monitor, platform::imp};
 
 /// yeah
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
@@ -55,6 +55,221 @@ pub enum Cursor {
     Wait,
 }
 
+/// What [`Window::run`] should do next, as returned by its handler.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum ControlFlow {
+    /// Sleep until the next event. A [`CloseRequest`](Event::CloseRequest) answered with this closes the window,
+    /// ending the loop.
+    Wait,
+
+    /// Sleep until the next event, the same as `Wait`, except that a [`CloseRequest`](Event::CloseRequest) answered
+    /// with this is ignored and the window stays open.
+    KeepOpen,
+
+    /// Sleep until the next event or until the given time, whichever comes first. The handler is sent
+    /// [`Event::Wakeup`] if the time is reached.
+    WaitUntil(std::time::Instant),
+
+    /// Check for events again straight away without sleeping, sending [`Event::Wakeup`] if there are none, such as
+    /// for a game which redraws continuously.
+    Poll,
+
+    /// End the loop, closing the window.
+    Exit,
+}
+
+/// A rectangular area of the user's desktop. The position is measured in pixels relative to the top-left of the
+/// desktop, across all monitors, and the size is measured in pixels.
+#[derive(Copy, Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Rectangle {
+    pub x: i16,
+    pub y: i16,
+    pub width: u16,
+    pub height: u16,
+}
+
+/// A point on the edge or in the middle of a window, which stays in place while the window is resized.
+/// 
+/// Used with [`Window::set_size_with_anchor`] and, on X11, [`Builder::bit_gravity`].
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Gravity {
+    /// For bit gravity, the contents are discarded, and the window is cleared to its background. As an anchor it
+    /// works the same as `NorthWest`.
+    Forget = 0,
+    NorthWest = 1,
+    North = 2,
+    NorthEast = 3,
+    West = 4,
+    Center = 5,
+    East = 6,
+    SouthWest = 7,
+    South = 8,
+    SouthEast = 9,
+    /// The contents stay where they are on the screen, rather than relative to the window. As an anchor it's the
+    /// same as `NorthWest`, except that on X11 the inner area stays put rather than the Window Manager's frame.
+    Static = 10,
+}
+
+impl Gravity {
+    /// The gravity with the given X11 value, or `NorthWest` if it's not a valid one.
+    #[cfg(unix)]
+    pub(crate) fn from_u8(value: u8) -> Self {
+        match value {
+            0 => Gravity::Forget,
+            2 => Gravity::North,
+            3 => Gravity::NorthEast,
+            4 => Gravity::West,
+            5 => Gravity::Center,
+            6 => Gravity::East,
+            7 => Gravity::SouthWest,
+            8 => Gravity::South,
+            9 => Gravity::SouthEast,
+            10 => Gravity::Static,
+            _ => Gravity::NorthWest,
+        }
+    }
+
+    /// How far the top-left of a window has to move for this point to stay put when it's resized from `old` to `new`.
+    pub(crate) fn offset(self, (old_w, old_h): (i32, i32), (new_w, new_h): (i32, i32)) -> (i32, i32) {
+        let (dw, dh) = (old_w - new_w, old_h - new_h);
+        match self {
+            Gravity::Forget | Gravity::Static | Gravity::NorthWest => (0, 0),
+            Gravity::North => (dw / 2, 0),
+            Gravity::NorthEast => (dw, 0),
+            Gravity::West => (0, dh / 2),
+            Gravity::Center => (dw / 2, dh / 2),
+            Gravity::East => (dw, dh / 2),
+            Gravity::SouthWest => (0, dh),
+            Gravity::South => (dw / 2, dh),
+            Gravity::SouthEast => (dw, dh),
+        }
+    }
+}
+
+/// Whether `rgba` has exactly four bytes for every pixel of a `width` by `height` icon.
+pub(crate) fn icon_size_matches(rgba: &[u8], width: u32, height: u32) -> bool {
+    u64::from(width) * u64::from(height) * 4 == rgba.len() as u64
+}
+
+/// Whether a minimum and maximum size can be used together, which they can't if the maximum is smaller than the
+/// minimum in either dimension. Either may be absent.
+pub(crate) fn size_limits_valid(min: Option<(u16, u16)>, max: Option<(u16, u16)>) -> bool {
+    match (min, max) {
+        (Some((min_w, min_h)), Some((max_w, max_h))) => max_w >= min_w && max_h >= min_h,
+        _ => true,
+    }
+}
+
+/// Clamps a size into the given minimum and maximum sizes, either of which may be absent.
+/// If the minimum is larger than the maximum, the maximum takes priority.
+pub(crate) fn clamp_size(
+    (width, height): (u16, u16),
+    min: Option<(u16, u16)>,
+    max: Option<(u16, u16)>,
+) -> (u16, u16) {
+    let (min_w, min_h) = min.unwrap_or((0, 0));
+    let (max_w, max_h) = max.unwrap_or((u16::MAX, u16::MAX));
+    (width.max(min_w).min(max_w), height.max(min_h).min(max_h))
+}
+
+/// A set of changes being made to a [`Window`] together, see [`Window::batch`].
+/// 
+/// Each function here does the same as the [`Window`] function of the same name.
+pub struct Batch<'a>(imp::Batch<'a>);
+
+impl Batch<'_> {
+    pub fn set_always_on_top(&mut self, always_on_top: bool) -> &mut Self {
+        self.0.set_always_on_top(always_on_top);
+        self
+    }
+
+    pub fn set_borderless(&mut self, borderless: bool) -> &mut Self {
+        self.0.set_borderless(borderless);
+        self
+    }
+
+    pub fn set_fullscreen(&mut self, fullscreen: bool) -> &mut Self {
+        self.0.set_fullscreen(fullscreen);
+        self
+    }
+
+    pub fn set_maximised(&mut self, maximised: bool) -> &mut Self {
+        self.0.set_maximised(maximised);
+        self
+    }
+
+    pub fn set_minimised(&mut self, minimised: bool) -> &mut Self {
+        self.0.set_minimised(minimised);
+        self
+    }
+
+    pub fn set_max_size(&mut self, size: Option<(u16, u16)>) -> Result<&mut Self, crate::error::Error> {
+        self.0.set_max_size(size)?;
+        Ok(self)
+    }
+
+    pub fn set_min_size(&mut self, size: Option<(u16, u16)>) -> Result<&mut Self, crate::error::Error> {
+        self.0.set_min_size(size)?;
+        Ok(self)
+    }
+
+    pub fn set_base_size(&mut self, size: Option<(u16, u16)>) -> &mut Self {
+        self.0.set_base_size(size);
+        self
+    }
+
+    pub fn set_position(&mut self, position: (i16, i16)) -> &mut Self {
+        self.0.set_position(position);
+        self
+    }
+
+    pub fn set_resizable(&mut self, resizable: bool) -> &mut Self {
+        self.0.set_resizable(resizable);
+        self
+    }
+
+    pub fn set_size(&mut self, size: (u16, u16)) -> &mut Self {
+        self.0.set_size(size);
+        self
+    }
+
+    pub fn set_size_with_anchor(&mut self, size: (u16, u16), anchor: Gravity) -> &mut Self {
+        self.0.set_size_with_anchor(size, anchor);
+        self
+    }
+
+    pub fn set_title(&mut self, title: &str) -> &mut Self {
+        self.0.set_title(title);
+        self
+    }
+
+    pub fn set_visible(&mut self, visible: bool) -> &mut Self {
+        self.0.set_visible(visible);
+        self
+    }
+}
+
+/// A titlebar action for [`Window::trigger_caption_action`].
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum CaptionAction {
+    /// Maximises the window, as double-clicking the titlebar of a normal window would.
+    Maximise,
+
+    /// Restores the window from being maximised.
+    Restore,
+
+    /// Opens the window menu at the mouse cursor, as right-clicking the titlebar would.
+    Menu,
+}
+
+/// Identifies a window for as long as it stays open.
+/// 
+/// These are listed by [`Connection::windows`](crate::connection::Connection::windows), and can be compared against
+/// [`Window::id`] to find which of an application's windows one refers to.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct WindowId(pub(crate) u64);
+
 /// Represents an open window. Dropping it closes the window.
 ///
 /// To instantiate windows, use a [`builder`](crate::connection::Connection::builder).
@@ -72,6 +287,25 @@ impl Window {
         self.0.events()
     }
 
+    /// Returns a copy of the events currently in the buffer, in the same order as [`events`](Self::events).
+    /// 
+    /// Unlike `events()`, the result doesn't borrow the window, so it can be kept around while calling other functions
+    /// on it, including `poll_events()`.
+    pub fn events_owned(&self) -> Vec<Event> {
+        self.0.events().to_vec()
+    }
+
+    /// Moves the events currently in the buffer out of it, in the same order as [`events`](Self::events), leaving it
+    /// empty until the next call to `poll_events()`.
+    /// 
+    /// `events()` borrows the buffer, which is the cheapest way to look at each event, but means the window can't be
+    /// used mutably while iterating. This hands over ownership of the events instead, without copying them like
+    /// [`events_owned`](Self::events_owned) does, so the events can be handled while freely calling anything on the
+    /// window, including `poll_events()` itself.
+    pub fn drain_events(&mut self) -> Vec<Event> {
+        self.0.drain_events()
+    }
+
     pub fn set_cursor(&self, cursor: Cursor) {
         #[cfg(windows)]
         {
@@ -83,6 +317,159 @@ impl Window {
         }
     }
 
+    /// Confines the mouse cursor to an area of the user's desktop, or releases it if `None` is passed.
+    /// 
+    /// The area doesn't need to be inside this window. On X11 this is done with pointer barriers, so the cursor is only
+    /// kept inside the area once it has entered it, and nothing happens if the server doesn't support XFixes 5.0.
+    /// On Windows, the system may release the cursor when the window loses focus.
+    pub fn set_cursor_confine_rect(&self, rect: Option<Rectangle>) {
+        self.0.set_cursor_confine_rect(rect)
+    }
+
+    /// Sets whether the mouse cursor should be kept inside the window's inner drawable area, such as while a game
+    /// is being played.
+    /// 
+    /// Unlike a one-off grab, this setting is remembered: the cursor is released whenever the window loses focus,
+    /// such as when the user alt-tabs away, and confined again automatically when focus comes back. On X11 this is an
+    /// active pointer grab, which keeps being retried while another client has the pointer grabbed. On Windows, the
+    /// clip follows the window as it's moved or resized, and releasing it also releases any
+    /// `set_cursor_confine_rect` area.
+    /// 
+    /// Defaults to `false`.
+    pub fn set_cursor_confined(&self, confined: bool) {
+        self.0.set_cursor_confined(confined)
+    }
+
+    /// Performs a titlebar action, for windows which draw their own titlebar, such as borderless ones.
+    /// 
+    /// This lets a custom titlebar behave like a real one, such as by maximising or restoring the window when it's
+    /// double-clicked. On X11, these are requested from the Window Manager, and `CaptionAction::Menu` uses
+    /// `_GTK_SHOW_WINDOW_MENU`, which is supported by GNOME, KDE and some others, but not all Window Managers. On
+    /// Windows, the system menu is used.
+    pub fn trigger_caption_action(&self, action: CaptionAction) {
+        self.0.trigger_caption_action(action)
+    }
+
+    /// Enables or disables [`Event::RedrawRequested`], which is sent whenever the compositor is ready for a new frame.
+    /// 
+    /// This is for pacing animation to the display's refresh rate without running a timer. On X11 it needs the
+    /// Present extension, and on Windows it needs desktop composition to be enabled. If neither is available,
+    /// no events will be sent.
+    pub fn set_frame_pacing(&self, enabled: bool) {
+        self.0.set_frame_pacing(enabled)
+    }
+
+    /// Returns the most recently measured time between display refreshes, or `None` if it hasn't been measured.
+    /// 
+    /// This is the real refresh interval, such as about 6.9ms on a 144Hz display, so it can be used as a simulation
+    /// timestep. On X11 it's measured from the Present extension's notifications, so it's only available while
+    /// frame pacing is enabled or frames are being presented with Present, and is only updated as those arrive.
+    /// On Windows it's reported by the Desktop Window Manager.
+    pub fn last_frame_time(&self) -> Option<std::time::Duration> {
+        self.0.last_frame_time()
+    }
+
+    /// Asks for the window to be brought to the front and given keyboard focus, such as when the user clicks a
+    /// notification for it.
+    /// 
+    /// This is only a request. To stop applications stealing focus while the user is busy elsewhere, many Window
+    /// Managers, and Windows itself, will refuse it and highlight the window's taskbar button instead. If it does
+    /// work, an [`Event::Focus`] is sent as usual.
+    /// 
+    /// On X11 this is done with `_NET_ACTIVE_WINDOW` if the Window Manager supports it, and otherwise by raising the
+    /// window and setting the input focus directly, which only works while the window is visible.
+    pub fn request_focus(&self) {
+        self.0.request_focus()
+    }
+
+    /// Sets how opaque the window is, from `0.0` for invisible to `1.0` for fully opaque, which is the default.
+    /// Values outside that range are clamped to it.
+    /// 
+    /// On X11 this is done with `_NET_WM_WINDOW_OPACITY`, which only has an effect while a compositor is running.
+    /// The opacity applies to the whole window, including the Window Manager's decorations.
+    pub fn set_opacity(&self, opacity: f32) {
+        // NaN is treated as opaque, as casting it would give 0 and make the window disappear
+        let opacity = if opacity.is_nan() { 1.0 } else { opacity.clamp(0.0, 1.0) };
+        self.0.set_opacity(opacity)
+    }
+
+    /// Sets or clears the window's urgency hint, which asks the user to pay attention to it.
+    /// 
+    /// On X11 this is the urgency flag in the ICCCM `WM_HINTS` property, which even minimal Window Managers tend to
+    /// show somehow, and the rest of the window's hints are left alone. On Windows the taskbar button is flashed once,
+    /// and stays highlighted until the window is focused or the hint is cleared.
+    pub fn set_urgent(&self, urgent: bool) {
+        self.0.set_urgent(urgent)
+    }
+
+    /// Enables or disables [`Event::KeyboardRepeat`]. It's enabled by default.
+    /// 
+    /// While disabled, holding a key down only produces [`Event::KeyboardDown`] and then [`Event::KeyboardUp`] when
+    /// it's released, which is what you want if held keys are treated as continuous input. This only filters the
+    /// events ramen gives you - the user's system-wide repeat setting isn't changed, other applications aren't affected,
+    /// and [`Event::Input`] still repeats as normal for text entry.
+    #[cfg_attr(feature = "nightly-rustdoc", doc(cfg(feature = "input")))]
+    #[cfg_attr(not(feature = "nightly-rustdoc"), cfg(feature = "input"))]
+    pub fn set_key_repeat(&self, enabled: bool) {
+        self.0.set_key_repeat(enabled)
+    }
+
+    /// Enables or disables text input. It's enabled by default.
+    /// 
+    /// While disabled, no [`Event::Input`] events are sent, and on Windows the IME is turned off for this window, so
+    /// the keyboard can be used for movement without also typing letters. Turn it on while a text field has focus.
+    /// [`Event::KeyboardDown`] and friends are sent either way.
+    #[cfg_attr(feature = "nightly-rustdoc", doc(cfg(feature = "input")))]
+    #[cfg_attr(not(feature = "nightly-rustdoc"), cfg(feature = "input"))]
+    pub fn set_text_input(&self, enabled: bool) {
+        self.0.set_text_input(enabled)
+    }
+
+    /// Checks the held modifier keys and mouse buttons against the system's own state, sending
+    /// [`Event::KeyboardUp`] and [`Event::MouseUp`] for any which were released without ramen seeing it.
+    /// 
+    /// The events arrive with the next call to `poll_events()`. This is already done automatically whenever the
+    /// window gains focus, which is where a key released in another window usually gets stuck, such as holding
+    /// shift while switching away.
+    #[cfg_attr(feature = "nightly-rustdoc", doc(cfg(feature = "input")))]
+    #[cfg_attr(not(feature = "nightly-rustdoc"), cfg(feature = "input"))]
+    pub fn resync_input_state(&self) {
+        self.0.resync_input_state()
+    }
+
+    /// Answers any pending "are you still responding?" checks from the operating system, without processing events.
+    /// 
+    /// This is much cheaper than `poll_events()`, so it can be called between chunks of a long computation to stop
+    /// the window from being reported as not responding. Events which arrive in the meantime are kept, and will be
+    /// returned by the next call to `poll_events()`. On X11 this answers `_NET_WM_PING`. On Windows it does nothing,
+    /// since ramen's windows are always kept responsive by a background thread.
+    pub fn keep_alive(&self) {
+        self.0.keep_alive()
+    }
+
+    /// Waits until every change requested so far has been processed, so that any events caused by them will be
+    /// picked up by the next call to `poll_events()`.
+    /// 
+    /// On X11 this is a round trip to the server, and on Windows it waits for the window's thread to catch up.
+    /// This doesn't wait for the Window Manager, which might still be reacting to a request when this returns.
+    /// It's mostly useful when you need events to arrive in a predictable order, such as in tests.
+    pub fn sync(&self) {
+        self.0.sync()
+    }
+
+    /// Makes several changes to the window at once, through a [`Batch`] passed to `f`.
+    /// 
+    /// Each setter normally sends its request off to the operating system immediately. Inside a batch, requests are
+    /// held back and sent together once `f` returns, which saves round-trips when setting up a window over a slow
+    /// connection, such as remote X11. The end result is the same as calling each setter in order. On X11, anything
+    /// the setters need to ask the server first, like the size of the window's frame, is asked for once per batch.
+    pub fn batch<F>(&self, f: F)
+    where
+        F: FnOnce(&mut Batch<'_>),
+    {
+        f(&mut Batch(self.0.batch()))
+    }
+
     /// Pulls any new events into the buffer, discarding any events which were previously in the buffer.
     /// 
     /// Query the buffer by calling `events()`.
@@ -92,19 +479,237 @@ impl Window {
     /// (usually a few seconds), then the operating system may mark it as unresponsive and/or try to kill it.
     /// 
     /// Note however that there is no such requirement for calling `events()`.
+    /// 
+    /// On X11, all of a Connection's windows share one connection to the server, which is locked while this reads
+    /// events off it. Windows polling on different threads therefore wait for each other, as do building and
+    /// dropping windows, and most other calls on the Connection. See `try_poll_events()` for a way around waiting.
     pub fn poll_events(&mut self) {
         self.0.poll_events()
     }
 
+    /// Polls events like `poll_events()` if it can be done without waiting for another thread, returning whether
+    /// it did.
+    /// 
+    /// If the Connection is busy, such as with another window polling on another thread, this returns `false`
+    /// straight away and the buffer is left as it was, so the same `events()` are still there. On Windows, each
+    /// window's events are only locked for as long as it takes to store one, so this very rarely returns `false`.
+    pub fn try_poll_events(&mut self) -> bool {
+        self.0.try_poll_events()
+    }
+
+    /// Polls events like `poll_events()`, but sleeps until there's at least one event rather than returning with none.
+    /// 
+    /// This is for applications which only need to do anything in response to events, so that they don't spin while
+    /// there's nothing to do. Events for other windows on the same connection are still kept for those windows, the
+    /// same as with `poll_events()`. This returns early with no events if the connection is lost.
+    pub fn wait_events(&mut self) {
+        self.0.wait_events_until(None)
+    }
+
+    /// Waits for events like [`wait_events`](Self::wait_events), but gives up after `timeout` has passed, in which
+    /// case there may be no events.
+    pub fn wait_events_timeout(&mut self, timeout: std::time::Duration) {
+        // A timeout too long to represent may as well be forever
+        self.0.wait_events_until(std::time::Instant::now().checked_add(timeout))
+    }
+
+    /// Runs an event loop, calling `handler` with every event until it returns [`ControlFlow::Exit`], or until a
+    /// [`CloseRequest`](Event::CloseRequest) isn't kept open. The window is closed when this returns, which also
+    /// happens if the connection to the display server is lost.
+    /// 
+    /// This is a shortcut for the usual loop of `poll_events()` and `events()`, which sleeps while there's nothing to
+    /// do rather than spinning. After each set of events, the loop does whatever the handler asked for, or sleeps
+    /// until the soonest time asked for if it asked for more than one. Handling events by hand is still possible
+    /// for loops which need more control, such as ones handling several windows.
+    pub fn run(mut self, mut handler: impl FnMut(&mut Window, Event) -> ControlFlow) {
+        let mut flow = ControlFlow::Wait;
+        let mut events = Vec::new();
+        loop {
+            match flow {
+                ControlFlow::Poll => self.0.poll_events(),
+                ControlFlow::WaitUntil(deadline) => self.0.wait_events_until(Some(deadline)),
+                _ => self.0.wait_events_until(None),
+            }
+            events.clear();
+            events.extend_from_slice(self.0.events());
+            if events.is_empty() {
+                // Waiting gives up early if the connection is lost, and no more events can arrive after that
+                if !self.0.is_connected() {
+                    return
+                }
+                if flow == ControlFlow::Wait {
+                    continue
+                }
+                events.push(Event::Wakeup);
+            }
+
+            flow = ControlFlow::Wait;
+            for &event in events.iter() {
+                let next = handler(&mut self, event);
+                flow = match (flow, next) {
+                    (_, ControlFlow::Exit) => return,
+                    (_, ControlFlow::Wait) if event == Event::CloseRequest => return,
+                    (ControlFlow::Poll, _) | (_, ControlFlow::Poll) => ControlFlow::Poll,
+                    (ControlFlow::WaitUntil(a), ControlFlow::WaitUntil(b)) => ControlFlow::WaitUntil(a.min(b)),
+                    (ControlFlow::WaitUntil(a), _) | (_, ControlFlow::WaitUntil(a)) => ControlFlow::WaitUntil(a),
+                    _ => ControlFlow::Wait,
+                };
+            }
+        }
+    }
+
+    /// Polls events like `poll_events()`, then replaces the contents of `out` with them.
+    /// 
+    /// `out` keeps its allocation, so a single `Vec` can be reused across frames and windows without allocating once
+    /// it's big enough. The events can still be queried with `events()` afterwards.
+    pub fn poll_events_into(&mut self, out: &mut Vec<Event>) {
+        self.0.poll_events();
+        out.clear();
+        out.extend_from_slice(self.0.events());
+    }
+
+    /// Returns whether the window's connection to the display server is still alive.
+    /// 
+    /// Once the connection is lost, such as when the X server shuts down at the end of the session, no more events
+    /// will arrive and every setter quietly does nothing. Applications should check this periodically and exit.
+    pub fn is_connected(&self) -> bool {
+        self.0.is_connected()
+    }
+
+    /// Returns whether the window is currently minimised. [`Event::Minimise`] is sent whenever this changes.
+    /// 
+    /// Rendering can usually be paused while the window is minimised, since nothing will be shown.
+    pub fn is_minimised(&self) -> bool {
+        self.0.is_minimised()
+    }
+
+    /// Returns the position of the top-left of the window's inner drawable area, in pixels relative to the top-left
+    /// of the user's desktop.
+    /// 
+    /// On X11 this is tracked from the events the window receives, so it's the position as of the last time
+    /// `poll_events()` was called, and asking for it doesn't need a round trip to the server. On Windows it's the
+    /// current position.
+    pub fn position(&self) -> (i16, i16) {
+        self.0.position()
+    }
+
+    /// Returns the size, in pixels, of the window's inner drawable area.
+    /// 
+    /// Like [`position`](Self::position), this is as of the last time `poll_events()` was called on X11, and is the
+    /// current size on Windows.
+    pub fn size(&self) -> (u16, u16) {
+        self.0.size()
+    }
+
+    /// Returns the modifier keys held down as of the last time `poll_events()` was called, telling apart the left and
+    /// right key of each kind.
+    #[cfg_attr(feature = "nightly-rustdoc", doc(cfg(feature = "input")))]
+    #[cfg_attr(not(feature = "nightly-rustdoc"), cfg(feature = "input"))]
+    pub fn modifiers(&self) -> crate::input::Modifiers {
+        self.0.modifiers()
+    }
+
+    /// Returns the platform's own modifier mask from the last keyboard or mouse button event handled by
+    /// `poll_events()`. Most applications want [`modifiers`](Self::modifiers) instead.
+    #[cfg_attr(feature = "nightly-rustdoc", doc(cfg(feature = "input")))]
+    #[cfg_attr(not(feature = "nightly-rustdoc"), cfg(feature = "input"))]
+    pub fn raw_modifiers(&self) -> crate::input::RawModifiers {
+        self.0.raw_modifiers()
+    }
+
+    /// Returns the mouse buttons held down as of the last time `poll_events()` was called.
+    /// 
+    /// This is tracked from [`Event::MouseDown`] and [`Event::MouseUp`], and checked against the system's own state
+    /// whenever the window gains focus or the mouse enters it, so a button released while the mouse was elsewhere
+    /// doesn't stay held.
+    #[cfg_attr(feature = "nightly-rustdoc", doc(cfg(feature = "input")))]
+    #[cfg_attr(not(feature = "nightly-rustdoc"), cfg(feature = "input"))]
+    pub fn mouse_buttons(&self) -> crate::input::MouseButtons {
+        self.0.mouse_buttons()
+    }
+
+    /// Returns whether the mouse is inside the window's inner drawable area, as of the last time `poll_events()`
+    /// was called.
+    /// 
+    /// This follows [`Event::MouseEnter`] and [`Event::MouseLeave`], and is checked against the system's own state
+    /// whenever the window gains focus or is shown, so it doesn't get stuck after the window is hidden or covered
+    /// while the mouse is over it.
+    #[cfg_attr(feature = "nightly-rustdoc", doc(cfg(feature = "input")))]
+    #[cfg_attr(not(feature = "nightly-rustdoc"), cfg(feature = "input"))]
+    pub fn cursor_inside(&self) -> bool {
+        self.0.cursor_inside()
+    }
+
+    /// Returns the scale factor of the window, such as `2.0` when it's on a monitor set to 200% scaling. Sizes and
+    /// positions are in physical pixels, which are this many times larger than logical ones.
+    /// 
+    /// On Windows this follows the monitor the window is on, and is `1.0` if the process isn't DPI aware. On X11 it's
+    /// the same for every window, as explained in
+    /// [`Connection::default_scale_factor`](crate::connection::Connection::default_scale_factor).
+    /// [`Event::ScaleFactorChanged`] is sent whenever this changes.
+    pub fn scale_factor(&self) -> f64 {
+        self.0.scale_factor()
+    }
+
+    /// Returns the position of the top-left of the window's inner drawable area like the one passed to
+    /// [`set_logical_position`](Self::set_logical_position), in logical pixels relative to the top-left of the
+    /// user's desktop.
+    /// 
+    /// This is [`position`](Self::position) divided by the scale factor.
+    pub fn logical_position(&self) -> (f64, f64) {
+        let scale = self.0.scale_factor();
+        let (x, y) = self.0.position();
+        (f64::from(x) / scale, f64::from(y) / scale)
+    }
+
+    /// Returns the size of the window's inner drawable area in logical pixels.
+    /// 
+    /// Since [`set_logical_size`](Self::set_logical_size) has to round to whole physical pixels, this may not be
+    /// exactly the size that was set. This is [`size`](Self::size) divided by the scale factor.
+    pub fn logical_size(&self) -> (f64, f64) {
+        let scale = self.0.scale_factor();
+        let (width, height) = self.0.size();
+        (f64::from(width) / scale, f64::from(height) / scale)
+    }
+
     /// Sets whether the window has any decorational border around it.
     /// 
     /// This function does not complete immediately - it simply sends a request to the operating system. The operating
     /// system may or may not choose to honour your request. If it does honour the request, it is guaranteed to have
     /// completed by the next time `poll_events()` returns after being called for this window.
+    /// 
+    /// On X11 this is done with the Motif `_MOTIF_WM_HINTS` property. Most Window Managers follow it, but ones which
+    /// don't will keep the border.
     pub fn set_borderless(&self, borderless: bool) {
         self.0.set_borderless(borderless)
     }
 
+    /// Sets whether the window is kept above other windows, such as for an overlay or a tool palette.
+    /// 
+    /// This is a hint, so no event is sent when it changes, and calling it again with the same value has no effect.
+    /// On X11 this is done with `_NET_WM_STATE_ABOVE`, which it's up to the Window Manager to follow.
+    pub fn set_always_on_top(&self, always_on_top: bool) {
+        self.0.set_always_on_top(always_on_top)
+    }
+
+    /// Sets whether the window is fullscreen, covering the whole of the monitor it's on without any decorations.
+    /// 
+    /// Calling `set_fullscreen(true)` on a fullscreen window, or `set_fullscreen(false)` on a window which isn't
+    /// fullscreen, will have no effect. Leaving fullscreen puts the window back how it was before, including whether
+    /// it was maximised.
+    /// 
+    /// This function does not complete immediately - it simply sends a request to the operating system. The operating
+    /// system may or may not choose to honour your request. If it does honour the request, it is guaranteed to have
+    /// completed by the next time `poll_events()` returns after being called for this window, and the resulting set of
+    /// events will contain a `Fullscreen` event if the window entered or left fullscreen as a result of calling this
+    /// function.
+    /// 
+    /// On X11 this is done with `_NET_WM_STATE_FULLSCREEN`, which Window Managers that don't follow the EWMH spec
+    /// will ignore.
+    pub fn set_fullscreen(&self, fullscreen: bool) {
+        self.0.set_fullscreen(fullscreen)
+    }
+
     /// Sets whether the window is maximised.
     /// 
     /// Calling `set_maximised(true)` on a maximised window, or `set_maximised(false)` on an un-maximised window,
@@ -118,9 +723,74 @@ impl Window {
         self.0.set_maximised(maximised)
     }
 
+    /// Sets whether the window is minimised. Restoring a window which was maximised before it was minimised leaves
+    /// it maximised.
+    /// 
+    /// Calling `set_minimised(true)` on a minimised window, or `set_minimised(false)` on a window which isn't
+    /// minimised, will have no effect. On X11 a hidden window can't be minimised, and restoring one shows it.
+    /// 
+    /// This function does not complete immediately - it simply sends a request to the operating system. The operating
+    /// system may or may not choose to honour your request. If it does honour the request, it is guaranteed to have
+    /// completed by the next time `poll_events()` returns after being called for this window, and the resulting set of
+    /// events will contain a `Minimise` event if the window was minimised or restored as a result of calling this
+    /// function.
+    pub fn set_minimised(&self, minimised: bool) {
+        self.0.set_minimised(minimised)
+    }
+
+    /// Sets the largest size, in pixels, that the user can resize the inner drawable area of the window to, or
+    /// removes the limit if `None` is passed. If the window is currently larger than this, it's resized to fit.
+    /// Setting the same minimum and maximum size makes the window a fixed size.
+    /// 
+    /// On X11 the limits are written to `WM_NORMAL_HINTS` as its `PMinSize` and `PMaxSize`, alongside the hints
+    /// [`set_resizable`](Self::set_resizable) uses.
+    /// 
+    /// Like [`Builder::build`], this returns [`Error::Invalid`](crate::error::Error::Invalid) and changes nothing if
+    /// the maximum would be smaller than the current minimum in either dimension. To move both limits past each
+    /// other, change the one which makes room for the other first.
+    /// 
+    /// Some X11 window managers ignore this, so don't rely on it: `Resize` events always report the window's real size,
+    /// even if it's outside of these limits.
+    pub fn set_max_size(&self, size: Option<(u16, u16)>) -> Result<(), crate::error::Error> {
+        self.0.set_max_size(size)
+    }
+
+    /// Sets the smallest size, in pixels, that the user can resize the inner drawable area of the window to, or
+    /// removes the limit if `None` is passed. If the window is currently smaller than this, it's resized to fit.
+    /// 
+    /// Returns [`Error::Invalid`](crate::error::Error::Invalid) and changes nothing if the minimum would be larger
+    /// than the current maximum, the same as [`set_max_size`](Self::set_max_size).
+    /// 
+    /// Some X11 window managers ignore this, so don't rely on it: `Resize` events always report the window's real size,
+    /// even if it's outside of these limits.
+    pub fn set_min_size(&self, size: Option<(u16, u16)>) -> Result<(), crate::error::Error> {
+        self.0.set_min_size(size)
+    }
+
+    /// Sets the base size which the window's resize increments are counted from, separately from its minimum size,
+    /// or goes back to the default if `None` is passed.
+    /// 
+    /// Window Managers which honour resize increments only allow sizes of `base + n * increment`, so a terminal should
+    /// set this to the size of its padding and borders, without any cells. On X11, this is `PBaseSize` in
+    /// `WM_NORMAL_HINTS`, which otherwise defaults to the window's current size. This has no effect on Windows.
+    pub fn set_base_size(&self, size: Option<(u16, u16)>) {
+        self.0.set_base_size(size)
+    }
+
+    /// Moves the window onto a monitor, from the list given by
+    /// [`Connection::monitors`](crate::connection::Connection::monitors).
+    /// 
+    /// The window keeps the same position relative to the top-left of its monitor if it fits, and is otherwise centred
+    /// on the new monitor. Like `set_position`, this is only a request which the operating system may not honour.
+    pub fn move_to_monitor(&self, monitor: &Monitor) {
+        self.0.move_to_monitor(monitor)
+    }
+
     /// Sets the position of the top-left of the window's inner drawable area.
     /// 
-    /// The position is measured in pixels relative to the top-left of the user's desktop, across all monitors.
+    /// The position is measured in pixels relative to the top-left of the user's desktop, across all monitors. On X11
+    /// the size of the Window Manager's frame is read from `_NET_FRAME_EXTENTS` to work out where to put the window,
+    /// so the position may be off by the size of the frame if the Window Manager doesn't set that.
     /// 
     /// This function does not complete immediately - it simply sends a request to the operating system. The operating
     /// system may or may not choose to honour your request. If it does honour the request, it is guaranteed to have
@@ -136,12 +806,20 @@ impl Window {
     /// the user from resizing it via the normal methods.
     /// 
     /// Calling this function is likely to cause visual bugs in X11-based window managers.
+    /// 
+    /// On X11 a window which isn't resizable has its minimum and maximum size hints pinned to its size, which
+    /// [`set_size`](Self::set_size) keeps up to date. Making it resizable again puts back the limits from
+    /// [`set_min_size`](Self::set_min_size) and [`set_max_size`](Self::set_max_size).
     pub fn set_resizable(&self, resizable: bool) {
         self.0.set_resizable(resizable)
     }
 
     /// Sets the size, in pixels, of the inner drawable area of the window.
     /// 
+    /// The size is clamped to fit within any limits set with [`set_min_size`](Self::set_min_size) and
+    /// [`set_max_size`](Self::set_max_size) before the request is made. On X11 a width or height of 0 is raised to 1,
+    /// since windows there can't be empty.
+    /// 
     /// This function does not complete immediately - it simply sends a request to the operating system. The operating
     /// system may or may not choose to honour your request. If it does honour the request, it is guaranteed to have
     /// completed by the next time `poll_events()` returns after being called for this window, and the resulting set of
@@ -150,25 +828,112 @@ impl Window {
         self.0.set_size(size)
     }
 
+    /// Sets the size of the window's inner drawable area like [`set_size`](Self::set_size), but moves the window so
+    /// that `anchor` stays where it is, rather than the top-left. For example, `Gravity::South` keeps the bottom edge
+    /// in place and grows or shrinks the window evenly to each side.
+    /// 
+    /// On X11 this also sets the window's gravity hint, which the Window Manager uses to place its frame. Like
+    /// `set_size`, this is only a request which the operating system may not honour.
+    pub fn set_size_with_anchor(&self, size: (u16, u16), anchor: Gravity) {
+        self.0.set_size_with_anchor(size, anchor)
+    }
+
+    /// Sets the position of the window like [`set_position`](Self::set_position), but in logical pixels, which are
+    /// multiplied by the current [`scale_factor`](Self::scale_factor).
+    /// 
+    /// The result is rounded to the nearest physical pixel, so at fractional scales such as 1.5x, a position of
+    /// `(101.0, 0.0)` becomes `(152, 0)`, and reading it back with [`logical_position`](Self::logical_position) gives
+    /// about `101.33`.
+    pub fn set_logical_position(&self, position: (f64, f64)) {
+        let scale = self.0.scale_factor();
+        self.0.set_position(((position.0 * scale).round() as i16, (position.1 * scale).round() as i16))
+    }
+
+    /// Sets the size of the window's inner drawable area like [`set_size`](Self::set_size), but in logical pixels,
+    /// which are multiplied by the current [`scale_factor`](Self::scale_factor).
+    /// 
+    /// The result is rounded to the nearest physical pixel, the same as
+    /// [`set_logical_position`](Self::set_logical_position), and any size limits are applied after rounding.
+    pub fn set_logical_size(&self, size: (f64, f64)) {
+        let scale = self.0.scale_factor();
+        self.0.set_size(((size.0 * scale).round() as u16, (size.1 * scale).round() as u16))
+    }
+
     /// Sets the title of the window, which will usually displayed in a title bar above the window.
     /// 
     /// This function does not complete immediately - it simply sends a request to the operating system. The operating
     /// system may or may not choose to honour your request. If it does honour the request, it is guaranteed to have
     /// completed by the next time `poll_events()` returns after being called for this window.
+    /// 
+    /// On X11 the title is written as UTF-8 to `_NET_WM_NAME`, and as Latin-1 to `WM_NAME` for older Window Managers,
+    /// with any characters that Latin-1 can't represent replaced by `?`. Offscreen windows have no title.
     pub fn set_title(&self, title: &str) {
         self.0.set_title(title)
     }
 
+    /// Sets the window's icon, which is usually shown in its title bar and in any taskbars.
+    /// 
+    /// `rgba` is the icon's pixels from the top-left, row by row, with four bytes each for red, green, blue and
+    /// alpha. The alpha isn't premultiplied. This returns [`Error::Invalid`](crate::error::Error::Invalid) if `rgba`
+    /// isn't exactly `width * height * 4` bytes long, or on X11 if the icon is too big to send to the server.
+    /// 
+    /// On X11 this sets `_NET_WM_ICON`, which the Window Manager may scale. This currently has no effect on Windows.
+    pub fn set_icon(&self, rgba: &[u8], width: u32, height: u32) -> Result<(), crate::error::Error> {
+        if !icon_size_matches(rgba, width, height) {
+            return Err(crate::error::Error::Invalid)
+        }
+        self.0.set_icon(rgba, width, height)
+    }
+
     /// Sets whether the window is visible on the user's screen and in any taskbars.
     /// 
     /// This function does not complete immediately - it simply sends a request to the operating system. The operating
     /// system may or may not choose to honour your request. If it does honour the request, it is guaranteed to have
     /// completed by the next time `poll_events()` returns after being called for this window, and the resulting set of
     /// events will contain a `Visible` event if the window's visibility changed as a result of calling this function.
+    /// 
+    /// Showing a window which is already visible, or hiding one which is already hidden, does nothing.
     pub fn set_visible(&self, visible: bool) {
         self.0.set_visible(visible)
     }
 
+    /// Sets whether the window is withdrawn, which hides it and makes the Window Manager forget about it entirely.
+    /// 
+    /// On X11, `set_visible` only maps or unmaps the window. Unmapping a minimised window leaves it minimised, while
+    /// withdrawing it moves it to the ICCCM Withdrawn state from either Normal or Iconic, which loses its position and
+    /// other Window Manager state. Un-withdrawing it maps it again, as if it had just been created. On Windows, there
+    /// is no withdrawn state, so this is the same as `set_visible(!withdrawn)`.
+    /// 
+    /// Like `set_visible`, this only sends a request to the operating system.
+    pub fn set_withdrawn(&self, withdrawn: bool) {
+        self.0.set_withdrawn(withdrawn)
+    }
+
+    /// Returns the ID of this window, which is unique among the windows currently open on its connection.
+    pub fn id(&self) -> WindowId {
+        self.0.id()
+    }
+
+    /// Returns the index of the virtual desktop the window is on, counting from 0. This function is only available
+    /// on X11.
+    /// 
+    /// This is `0xFFFFFFFF` if the window is shown on all desktops, and `None` if the Window Manager hasn't said,
+    /// such as when it doesn't support virtual desktops.
+    #[cfg(unix)]
+    pub fn desktop(&self) -> Option<u32> {
+        self.0.desktop()
+    }
+
+    /// Moves the window to a virtual desktop, counting from 0, or onto all of them if `index` is `0xFFFFFFFF`. This
+    /// function is only available on X11.
+    /// 
+    /// This is a request to the Window Manager, which has to support `_NET_WM_DESKTOP`. If the window isn't shown
+    /// yet, it's created on that desktop when it is.
+    #[cfg(unix)]
+    pub fn set_desktop(&self, index: u32) {
+        self.0.set_desktop(index)
+    }
+
     /// Returns the win32 `HWND` of this window. This function is only available on Windows.
     #[cfg(target_os = "windows")]
     pub fn hwnd(&self) -> crate::platform::win32::HWND {
@@ -180,4 +945,93 @@ impl Window {
     pub fn xid(&self) -> crate::platform::linux::xcb_window_t {
         self.0.xid()
     }
+
+    /// Returns the colour depth of this window, in bits per pixel. This function is only available on Linux backends.
+    /// 
+    /// This is the depth passed to [`Builder::depth`], or the root window's if none was given.
+    #[cfg(target_os = "linux")]
+    pub fn depth(&self) -> u8 {
+        self.0.depth()
+    }
+
+    /// Returns the X11 visual ID of this window. This function is only available on Linux backends.
+    /// 
+    /// This is the visual passed to [`Builder::visual`], or the root window's if none was
+    /// given. It's needed to create compatible rendering resources, such as an `XImage` or a GLX/Vulkan surface.
+    #[cfg(target_os = "linux")]
+    pub fn visual_id(&self) -> crate::platform::linux::xcb_visualid_t {
+        self.0.visual_id()
+    }
+}
+
+/// On X11, this is an `Xcb` handle with the window's visual ID, and on Windows it's a `Win32` handle.
+#[cfg_attr(feature = "nightly-rustdoc", doc(cfg(feature = "raw-window-handle")))]
+#[cfg_attr(not(feature = "nightly-rustdoc"), cfg(feature = "raw-window-handle"))]
+unsafe impl raw_window_handle::HasRawWindowHandle for Window {
+    fn raw_window_handle(&self) -> raw_window_handle::RawWindowHandle {
+        #[cfg(unix)]
+        {
+            let mut handle = raw_window_handle::XcbWindowHandle::empty();
+            handle.window = self.0.xid();
+            handle.visual_id = self.0.visual_id();
+            raw_window_handle::RawWindowHandle::Xcb(handle)
+        }
+        #[cfg(windows)]
+        {
+            let mut handle = raw_window_handle::Win32WindowHandle::empty();
+            handle.hwnd = self.0.hwnd().cast();
+            handle.hinstance = crate::platform::win32::base_hinstance().cast();
+            raw_window_handle::RawWindowHandle::Win32(handle)
+        }
+    }
+}
+
+/// On X11, this is an `Xcb` handle for the window's connection and the X screen it's on. On Windows, there's nothing
+/// in the handle.
+#[cfg_attr(feature = "nightly-rustdoc", doc(cfg(feature = "raw-window-handle")))]
+#[cfg_attr(not(feature = "nightly-rustdoc"), cfg(feature = "raw-window-handle"))]
+unsafe impl raw_window_handle::HasRawDisplayHandle for Window {
+    fn raw_display_handle(&self) -> raw_window_handle::RawDisplayHandle {
+        #[cfg(unix)]
+        {
+            let mut handle = raw_window_handle::XcbDisplayHandle::empty();
+            handle.connection = self.0.xcb_connection();
+            handle.screen = self.0.screenid() as _;
+            raw_window_handle::RawDisplayHandle::Xcb(handle)
+        }
+        #[cfg(windows)]
+        {
+            raw_window_handle::RawDisplayHandle::Windows(raw_window_handle::WindowsDisplayHandle::empty())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn size_clamping() {
+        assert_eq!(clamp_size((800, 600), None, None), (800, 600));
+        assert_eq!(clamp_size((100, 600), Some((320, 240)), None), (320, 600));
+        assert_eq!(clamp_size((800, 600), Some((320, 240)), Some((640, 480))), (640, 480));
+        assert_eq!(clamp_size((800, 600), Some((700, 700)), Some((640, 480))), (640, 480));
+    }
+
+    #[test]
+    fn size_limit_validation() {
+        assert!(size_limits_valid(None, None));
+        assert!(size_limits_valid(Some((320, 240)), None));
+        assert!(size_limits_valid(Some((640, 480)), Some((640, 480))));
+        assert!(!size_limits_valid(Some((700, 240)), Some((640, 480))));
+        assert!(!size_limits_valid(Some((320, 500)), Some((640, 480))));
+    }
+
+    #[test]
+    fn anchor_offsets() {
+        assert_eq!(Gravity::NorthWest.offset((800, 600), (1000, 700)), (0, 0));
+        assert_eq!(Gravity::Center.offset((800, 600), (1000, 700)), (-100, -50));
+        assert_eq!(Gravity::SouthEast.offset((800, 600), (600, 500)), (200, 100));
+        assert_eq!(Gravity::South.offset((800, 600), (800, 800)), (0, -200));
+    }
 }
@@ -14,15 +14,39 @@ pub struct Builder {
     pub(crate) cursor: Cursor,
     pub(crate) connection: Connection,
     pub(crate) class_name: Cow<'static, str>,
+    pub(crate) icon: Option<(Vec<u8>, u32, u32)>, // RGBA pixels, width and height
+    pub(crate) always_on_top: bool,
+    pub(crate) fullscreen: bool,
     pub(crate) maximised: bool,
+    pub(crate) minimised: bool,
+    pub(crate) min_size: Option<(u16, u16)>,
+    pub(crate) max_size: Option<(u16, u16)>,
     pub(crate) position: Option<(i16, i16)>,
     pub(crate) size: (u16, u16),
     pub(crate) style: Style,
     pub(crate) title: Cow<'static, str>,
+    #[cfg(windows)]
+    pub(crate) dpi_aware: bool,
+    #[cfg(windows)]
+    pub(crate) modal_for: Option<usize>, // the owner's `HWND`, stored as an integer so the builder stays `Send`
+    #[cfg(unix)]
+    pub(crate) modal_for: Option<u32>,
+    #[cfg(unix)]
+    pub(crate) bit_gravity: super::Gravity,
     #[cfg(unix)]
     pub(crate) depth: Option<u8>,
     #[cfg(unix)]
-    pub(crate) visual: Option<u32>,
+    pub(crate) visual: Option<(u32, u8, u32)>, // visual ID, depth and colormap
+    #[cfg(unix)]
+    pub(crate) window_role: Option<Cow<'static, str>>,
+    #[cfg(unix)]
+    pub(crate) process_info: bool,
+    #[cfg(unix)]
+    pub(crate) screen: Option<i32>,
+    #[cfg(unix)]
+    pub(crate) pid_override: Option<u32>,
+    #[cfg(unix)]
+    pub(crate) offscreen: bool,
 }
 
 impl Builder {
@@ -31,7 +55,14 @@ impl Builder {
             cursor: Cursor::Arrow,
             connection,
             class_name: Cow::Borrowed("ramen_window"),
+            icon: None,
+            always_on_top: false,
+            fullscreen: false,
             maximised: false,
+            minimised: false,
+            min_size: None,
+            max_size: None,
+            modal_for: None,
             position: None,
             size: (800, 600),
             style: match style {
@@ -40,18 +71,54 @@ impl Builder {
                 None => Style::new(),
             },
             title: Cow::Borrowed("a nice window"),
+            #[cfg(windows)]
+            dpi_aware: true,
+            #[cfg(unix)]
+            bit_gravity: super::Gravity::NorthWest,
             #[cfg(unix)]
             depth: None,
             #[cfg(unix)]
             visual: None,
+            #[cfg(unix)]
+            window_role: None,
+            #[cfg(unix)]
+            process_info: true,
+            #[cfg(unix)]
+            screen: None,
+            #[cfg(unix)]
+            pid_override: None,
+            #[cfg(unix)]
+            offscreen: false,
         }
     }
 
     /// Attempt to build a Window, consuming this Builder object.
-    pub fn build(self) -> Result<super::Window, Error> {
+    /// 
+    /// Returns [`Error::Invalid`] if the icon's pixels don't match its size, or if the maximum size is smaller than
+    /// the minimum size in either dimension.
+    pub fn build(mut self) -> Result<super::Window, Error> {
+        if let Some((rgba, width, height)) = &self.icon {
+            if !super::icon_size_matches(rgba, *width, *height) {
+                return Err(Error::Invalid)
+            }
+        }
+        if !super::size_limits_valid(self.min_size, self.max_size) {
+            return Err(Error::Invalid)
+        }
+        self.size = super::clamp_size(self.size, self.min_size, self.max_size);
         imp::Window::new(self).map(super::Window)
     }
 
+    /// Sets whether the window should be kept above other windows, such as for an overlay or a tool palette.
+    /// 
+    /// This is a hint, and on X11 it's up to the Window Manager whether to follow it.
+    /// 
+    /// Defaults to `false`.
+    pub fn always_on_top(mut self, always_on_top: bool) -> Self {
+        self.always_on_top = always_on_top;
+        self
+    }
+
     /// Sets whether the window should be borderless.
     /// 
     /// Defaults to `false`.
@@ -88,6 +155,18 @@ impl Builder {
         self
     }
 
+    /// Sets the window's icon, which is usually shown in its title bar and in any taskbars. See
+    /// [`Window::set_icon`](super::Window::set_icon) for the format.
+    /// 
+    /// Building the window fails with [`Error::Invalid`](crate::error::Error::Invalid) if `rgba` isn't the right
+    /// length for the width and height.
+    /// 
+    /// Defaults to `None`, which leaves the icon up to the operating system.
+    pub fn icon(mut self, rgba: Vec<u8>, width: u32, height: u32) -> Self {
+        self.icon = Some((rgba, width, height));
+        self
+    }
+
     /// Sets whether the window can be initially interactively resized by the user.
     ///
     /// Note that this being `false` does not completely prevent a window from changing size; it only prevents the user
@@ -110,9 +189,22 @@ impl Builder {
         self
     }
 
+    /// Sets whether the window should begin fullscreen, covering the whole of the monitor it appears on.
+    /// 
+    /// A window which is also set to be maximised will be maximised when it leaves fullscreen.
+    /// 
+    /// This setting will have no effect if `visible` is set to `false`.
+    /// 
+    /// Defaults to `false`.
+    pub fn fullscreen(mut self, fullscreen: bool) -> Self {
+        self.fullscreen = fullscreen;
+        self
+    }
+
     /// Sets whether the window should begin maximised on the user's monitor.
     /// 
-    /// If the user has multiple monitors, the primary monitor will usually be selected.
+    /// If the user has multiple monitors, the primary monitor will usually be selected. The window is shown
+    /// maximised straight away, rather than appearing at its normal size first.
     /// 
     /// This setting will have no effect if `visible` is set to `false`.
     /// 
@@ -122,6 +214,19 @@ impl Builder {
         self
     }
 
+    /// Sets whether the window should begin minimised.
+    /// 
+    /// This takes priority over `maximised`. On X11, a window which is also set to be maximised will be maximised
+    /// when it's restored.
+    /// 
+    /// This setting will have no effect if `visible` is set to `false`.
+    /// 
+    /// Defaults to `false`.
+    pub fn minimised(mut self, minimised: bool) -> Self {
+        self.minimised = minimised;
+        self
+    }
+
     /// Sets the screen position of the top-left of the window's inner drawable area, in pixels, relative to the
     /// top-left of the user's desktop. If `None`, the operating system will decide where to place the window.
     /// 
@@ -135,7 +240,50 @@ impl Builder {
         self
     }
 
-    /// Sets the size of the window.
+    /// Makes the window a modal dialog for `parent`.
+    /// 
+    /// The operating system will keep it above its parent and minimise it along with the parent. On X11 this sets
+    /// `WM_TRANSIENT_FOR` and `_NET_WM_STATE_MODAL`, and the Window Manager will usually center it over the parent too.
+    /// On Windows the parent becomes the window's owner, and it's centered over the parent unless a `position` is set.
+    /// 
+    /// This doesn't stop the parent from receiving input - it's up to you to ignore it while the dialog is open.
+    /// 
+    /// Defaults to `None`.
+    pub fn modal_for(mut self, parent: &super::Window) -> Self {
+        #[cfg(unix)]
+        {
+            self.modal_for = Some(parent.xid());
+        }
+        #[cfg(windows)]
+        {
+            self.modal_for = Some(parent.hwnd() as usize);
+        }
+        self
+    }
+
+    /// Sets the largest size, in pixels, that the user can resize the inner drawable area of the window to, or `None`
+    /// for no limit. See [`Window::set_max_size`](super::Window::set_max_size).
+    /// 
+    /// Setting the same minimum and maximum size makes the window a fixed size, without changing whether it's
+    /// [`resizable`](Self::resizable).
+    /// 
+    /// Defaults to `None`.
+    pub fn max_size(mut self, size: Option<(u16, u16)>) -> Self {
+        self.max_size = size;
+        self
+    }
+
+    /// Sets the smallest size, in pixels, that the user can resize the inner drawable area of the window to, or
+    /// `None` for no limit. See [`Window::set_min_size`](super::Window::set_min_size).
+    /// 
+    /// Defaults to `None`.
+    pub fn min_size(mut self, size: Option<(u16, u16)>) -> Self {
+        self.min_size = size;
+        self
+    }
+
+    /// Sets the size of the window. It's clamped to fit within the [`min_size`](Self::min_size) and
+    /// [`max_size`](Self::max_size), if they're set.
     /// 
     /// Defaults to (800, 600).
     pub fn size(mut self, size: (u16, u16)) -> Self {
@@ -151,15 +299,121 @@ impl Builder {
         self
     }
 
+    /// Sets whether the process should declare itself as per-monitor DPI aware. This function is only available on
+    /// Windows.
+    /// 
+    /// DPI awareness is a process-wide setting, which ramen applies the first time it's needed: just before the first
+    /// window is created, or when [`Connection::default_scale_factor`] is first called if that's sooner. As such, this
+    /// setting only has an effect on the first window built, and only if the scale factor hasn't been read before.
+    /// [`Connection::set_dpi_aware`] makes the same choice without building a window.
+    /// If set to `false`, Windows will bitmap-scale the window on high-DPI monitors, which will look blurry.
+    /// 
+    /// Defaults to `true`.
+    #[cfg(windows)]
+    pub fn dpi_aware(mut self, dpi_aware: bool) -> Self {
+        self.dpi_aware = dpi_aware;
+        self
+    }
+
+    /// Sets where the window's contents are kept when it's resized. This function is only available on X11.
+    /// 
+    /// Keeping the contents avoids a flash of the background before the application has redrawn the window. ramen
+    /// also asks the X server for a backing store, to keep the contents of covered parts of the window, but that is
+    /// only advisory and the server or compositor may ignore it.
+    /// 
+    /// Defaults to `Gravity::NorthWest`.
+    #[cfg(unix)]
+    pub fn bit_gravity(mut self, gravity: super::Gravity) -> Self {
+        self.bit_gravity = gravity;
+        self
+    }
+
     #[cfg(unix)]
     pub fn depth(mut self, depth: u8) -> Self {
         self.depth = Some(depth);
         self
     }
 
+    /// Sets the visual, depth and colormap the window is created with. This function is only available on X11.
+    /// 
+    /// This is for rendering with a GL context or other API which isn't managed by ramen, such as one made by calling
+    /// GLX directly, where the window has to match the visual of the chosen framebuffer config. The colormap must
+    /// have been created for `visual_id`, and is not freed by ramen. The depth given here takes priority over
+    /// `depth`.
+    /// 
+    /// Defaults to `None`, which uses the root window's visual, depth and colormap.
+    #[cfg(unix)]
+    pub fn visual(
+        mut self,
+        visual_id: crate::platform::linux::xcb_visualid_t,
+        depth: u8,
+        colormap: crate::platform::linux::xcb_colormap_t,
+    ) -> Self {
+        self.visual = Some((visual_id, depth, colormap));
+        self
+    }
+
+    /// Sets the window's role, such as `"main"` or `"preferences"`. This function is only available on X11.
+    /// 
+    /// Session managers use this to tell apart the windows of an application, so that each one can be restored to
+    /// its saved geometry. It should be unique among the application's windows, and stay the same between runs.
+    /// 
+    /// Defaults to `None`.
+    #[cfg(unix)]
+    pub fn window_role<T>(mut self, role: T) -> Self
+    where
+        T: Into<Cow<'static, str>>,
+    {
+        self.window_role = Some(role.into());
+        self
+    }
+
+    /// Sets whether the window should be tagged with the process ID and the machine's hostname. This function is only
+    /// available on X11.
+    /// 
+    /// Window Managers use these to find the process which owns an unresponsive window, so that it can be killed.
+    /// The spec requires them to be set together, so disabling this skips both. This can be useful in sandboxed
+    /// or proxied setups, where the real values would be misleading or would leak details of the host.
+    /// 
+    /// Defaults to `true`.
+    #[cfg(unix)]
+    pub fn process_info(mut self, enabled: bool) -> Self {
+        self.process_info = enabled;
+        self
+    }
+
+    /// Sets which X screen the window is created on, counting from 0. This function is only available on X11.
+    /// 
+    /// The number of screens can be found with [`Connection::screen_count`](Connection::screen_count). Building
+    /// the window fails with [`Error::Invalid`](crate::error::Error::Invalid) if there's no screen with this index.
+    /// 
+    /// Defaults to `None`, which uses the display's default screen.
+    #[cfg(unix)]
+    pub fn screen(mut self, index: i32) -> Self {
+        self.screen = Some(index);
+        self
+    }
+
+    /// Sets the process ID the window reports, in place of the real one. This function is only available on X11.
+    /// 
+    /// This has no effect if `process_info` is disabled.
+    /// 
+    /// Defaults to `None`.
+    #[cfg(unix)]
+    pub fn pid_override(mut self, pid: u32) -> Self {
+        self.pid_override = Some(pid);
+        self
+    }
+
+    /// Makes the window an offscreen render target, which is never shown. This function is only available on X11.
+    /// 
+    /// The window is created but never mapped, even if `visible` is set or `set_visible` is called later, and none
+    /// of the Window Manager properties such as the title, class or process info are written. It can still be used
+    /// as a drawable for GL, Vulkan or XImage rendering, through [`Window::xid`](super::Window::xid) and
+    /// [`Window::visual_id`](super::Window::visual_id).
     #[cfg(unix)]
-    pub fn visual(mut self, visual: u32) -> Self {
-        self.visual = Some(visual);
+    pub fn offscreen(mut self) -> Self {
+        self.offscreen = true;
         self
     }
 }